@@ -0,0 +1,762 @@
+//! The accounts and the observers polling them. Every account configured with
+//! `--configure-accounts` gets an observer and state file of its own in the `accounts`
+//! directory, so the accounts are polled at the same time and a slow account doesn't delay the
+//! others. Accounts configured by earlier versions share the observer state of the `config` file
+//! and are polled together, until they are removed and configured again.
+use crate::backoff::{Backoff, BackoffConfig};
+use crate::cfg;
+use crate::state::StateFile;
+use crate::utils::{create_dir_user_only, get_config_file_path, GetSecretKeyState, Jitter};
+use anyhow::anyhow;
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+use log::{debug, error, info, warn};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use you_have_mail_common::backend::Backend;
+use you_have_mail_common::{
+    Account, EncryptionKey, ExposeSecret, Notifier, Observer, ObserverBuilder, ObserverError,
+    Secret,
+};
+
+/// Directory of the per account state files, relative to the config directory.
+const ACCOUNTS_DIR: &str = "accounts";
+
+/// Extension of the per account state files.
+const STATE_EXTENSION: &str = "state";
+
+/// Email, backend and login state of an account, as of the last poll of its observer.
+#[derive(Clone, Serialize)]
+pub struct AccountInfo {
+    pub email: String,
+    pub backend: String,
+    pub logged_in: bool,
+}
+
+impl AccountInfo {
+    fn of(observer: &Observer) -> Vec<Self> {
+        observer
+            .accounts()
+            .map(|(email, account)| Self {
+                email: email.to_string(),
+                backend: account.backend().name().to_string(),
+                logged_in: account.is_logged_in(),
+            })
+            .collect()
+    }
+}
+
+/// The accounts of one observer state file.
+pub struct Group {
+    path: PathBuf,
+    observer: Arc<Mutex<Observer>>,
+    accounts: Vec<AccountInfo>,
+    /// Set while the observer is given to the poll threads, until its poll completes.
+    polling: bool,
+    /// Shortest poll interval of the accounts.
+    interval: Duration,
+    next_poll: Instant,
+    backoff: Backoff,
+    /// Consecutive failed polls, the backoff stops counting once its retries are used up.
+    failures: u32,
+}
+
+impl Group {
+    fn new(path: PathBuf, observer: Observer, interval: Duration, backoff: BackoffConfig) -> Self {
+        Self {
+            accounts: AccountInfo::of(&observer),
+            path,
+            observer: Arc::new(Mutex::new(observer)),
+            polling: false,
+            interval,
+            next_poll: Instant::now(),
+            backoff: Backoff::with_max_delay(backoff, interval),
+            failures: 0,
+        }
+    }
+
+    /// The observer state file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn accounts(&self) -> &[AccountInfo] {
+        &self.accounts
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    fn refresh(&mut self) {
+        self.accounts = AccountInfo::of(&lock(&self.observer));
+    }
+}
+
+/// Result of the poll of a [`Group`].
+pub struct PollOutcome {
+    /// Index of the group, to record the result with [`Accounts::record_success`] or
+    /// [`Accounts::record_failure`].
+    pub group: usize,
+    /// The accounts of the group, as of the end of the poll if it completed.
+    pub accounts: Vec<AccountInfo>,
+    pub result: PollResult,
+    pub duration: Duration,
+}
+
+pub enum PollResult {
+    Completed(Result<(), ObserverError>),
+    /// The poll is still running after the timeout. Its accounts are skipped until it completes,
+    /// as the observer is busy.
+    TimedOut,
+    /// The observer panicked while polling.
+    Panicked,
+}
+
+/// Progress of a poll, sent by the poll threads.
+enum PollMessage {
+    Started(usize),
+    Finished(usize, PollOutcome),
+}
+
+pub struct Accounts {
+    config_dir: PathBuf,
+    notifier: Arc<dyn Notifier>,
+    key: Secret<EncryptionKey>,
+    backends: Vec<Arc<dyn Backend>>,
+    /// Shortest poll interval of all accounts.
+    poll_interval: Duration,
+    backoff: BackoffConfig,
+    jitter: Jitter,
+    groups: Vec<Group>,
+    /// State files which failed to load, they are kept on disk.
+    unavailable: Vec<PathBuf>,
+    pool: Option<PollPool>,
+}
+
+impl Accounts {
+    /// Load the observer states of `config_dir`, with the backends enabled in `config`. With a new
+    /// key the states can't be decrypted, they are moved aside and there are no accounts.
+    pub fn load(
+        config_dir: &Path,
+        notifier: Arc<dyn Notifier>,
+        key: GetSecretKeyState,
+        config: &cfg::Config,
+    ) -> anyhow::Result<Self> {
+        let (key, fresh) = match key {
+            GetSecretKeyState::New(key) => (key, true),
+            GetSecretKeyState::Existing(key) => (key, false),
+        };
+        let mut accounts = Self {
+            config_dir: config_dir.to_path_buf(),
+            notifier,
+            key,
+            backends: backends()
+                .into_iter()
+                .filter(|backend| config.backend_enabled(backend.name()))
+                .collect(),
+            poll_interval: Duration::from_secs(config.effective_poll_interval()),
+            backoff: BackoffConfig::default(),
+            jitter: Jitter::new(Duration::ZERO),
+            groups: Vec::new(),
+            unavailable: Vec::new(),
+            pool: None,
+        };
+
+        for path in state_files(config_dir)? {
+            if fresh {
+                // Replacing the state would lose the accounts for good, keep it around instead.
+                let lost = path.with_extension("lost");
+                warn!("Existing observer state {path:?} detected but we got a new encryption key, its accounts are lost, moving it to {lost:?}");
+                std::fs::rename(&path, &lost)
+                    .map_err(|e| anyhow!("Failed to move {path:?} to {lost:?}: {e}"))?;
+                continue;
+            }
+            // One state which can't be loaded, e.g. with an account of a backend which is no
+            // longer available, shouldn't take the other accounts down with it.
+            let observer = match accounts.open(&path, false) {
+                Ok(observer) => observer,
+                Err(e) => {
+                    warn!("Skipping the accounts of {path:?}: {e}");
+                    accounts.unavailable.push(path);
+                    continue;
+                }
+            };
+            if observer.is_empty() {
+                continue;
+            }
+            let group = Group::new(path, observer, accounts.poll_interval, accounts.backoff);
+            accounts.groups.push(group);
+        }
+        Ok(accounts)
+    }
+
+    /// Observer of the state file at `path`, a new empty one if `fresh`.
+    fn open(&self, path: &Path, fresh: bool) -> anyhow::Result<Observer> {
+        let key = copy_key(&self.key)?;
+        let config = if fresh {
+            you_have_mail_common::Config::new(key, path.to_path_buf(), self.poll_interval)
+        } else {
+            you_have_mail_common::Config::create_or_load(key, path.to_path_buf())
+        }
+        .map_err(|e| anyhow!("Failed to load observer state at {path:?}: {e}"))?;
+
+        let mut builder = ObserverBuilder::new(self.notifier.clone(), config);
+        for backend in &self.backends {
+            builder = builder.with_backend(backend.clone());
+        }
+        let mut observer = builder.load_from_config().map_err(|e| {
+            anyhow!("Failed to create observer for {path:?}: {e}, if an account uses a backend which is no longer available or not in enabled_backends remove this file and configure the account again")
+        })?;
+        observer
+            .set_poll_interval(self.poll_interval)
+            .map_err(|e| anyhow!("Failed to set poll interval on observer: {e}"))?;
+        Ok(observer)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.groups.iter().map(|g| g.accounts.len()).sum()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &AccountInfo> {
+        self.groups.iter().flat_map(|g| g.accounts.iter())
+    }
+
+    pub fn get(&self, email: &str) -> Option<&AccountInfo> {
+        self.iter().find(|a| a.email == email)
+    }
+
+    /// Whether `email` can be removed, it may be an account whose state failed to load.
+    pub fn contains(&self, email: &str) -> bool {
+        self.get(email).is_some()
+            || self
+                .unavailable
+                .contains(&account_state_path(&self.config_dir, email))
+    }
+
+    pub fn groups(&self) -> &[Group] {
+        &self.groups
+    }
+
+    /// Key the observer states are encrypted with.
+    pub fn key(&self) -> &Secret<EncryptionKey> {
+        &self.key
+    }
+
+    pub fn backend_by_name(&self, name: &str) -> Option<Arc<dyn Backend>> {
+        self.backends.iter().find(|b| b.name() == name).cloned()
+    }
+
+    pub fn poll_interval(&self) -> Duration {
+        self.poll_interval
+    }
+
+    /// Apply the poll intervals, jitter and backoff of `config`. The accounts of the shared
+    /// state are polled at the shortest of their intervals. A next poll is moved earlier if the
+    /// new interval is shorter.
+    pub fn configure(&mut self, config: &cfg::Config) -> anyhow::Result<()> {
+        self.poll_interval = Duration::from_secs(config.effective_poll_interval());
+        self.backoff = config.backoff;
+        self.jitter = Jitter::new(Duration::from_secs(config.poll_jitter_secs));
+        let now = Instant::now();
+        for group in &mut self.groups {
+            let interval = group
+                .accounts
+                .iter()
+                .map(|a| config.account_poll_interval(&a.email))
+                .min()
+                .map_or(self.poll_interval, Duration::from_secs);
+            lock(&group.observer)
+                .set_poll_interval(interval)
+                .map_err(|e| anyhow!("Failed to set poll interval on observer: {e}"))?;
+            group.interval = interval;
+            group.next_poll = group.next_poll.min(now + interval);
+            group.backoff = Backoff::with_max_delay(self.backoff, interval);
+            group.failures = 0;
+        }
+        Ok(())
+    }
+
+    /// Delay the polls of the accounts which were polled recently by the previous run, returns
+    /// the time until the first poll if it is delayed.
+    pub fn resume(&mut self, state: &StateFile) -> Option<Duration> {
+        let now = Instant::now();
+        for group in &mut self.groups {
+            let delay = group
+                .accounts
+                .iter()
+                .map(|a| state.time_until_next_poll(&a.email, group.interval))
+                .min()
+                .flatten();
+            group.next_poll = now + delay.unwrap_or_default();
+        }
+        self.next_poll()
+            .map(|next_poll| next_poll.saturating_duration_since(now))
+            .filter(|delay| !delay.is_zero())
+    }
+
+    /// Time of the next due poll, `None` if there are no accounts.
+    pub fn next_poll(&self) -> Option<Instant> {
+        self.groups.iter().map(|g| g.next_poll).min()
+    }
+
+    /// Whether the poll of any account is due.
+    pub fn is_due(&self) -> bool {
+        let now = Instant::now();
+        self.groups.iter().any(|g| g.next_poll <= now)
+    }
+
+    /// Make every account due, e.g. after the system resumed from a suspend.
+    pub fn poll_all_now(&mut self) {
+        let now = Instant::now();
+        for group in &mut self.groups {
+            group.next_poll = now;
+        }
+    }
+
+    /// Delay the accounts which are due by `delay`, when they can't be polled.
+    pub fn postpone(&mut self, delay: Duration) {
+        let now = Instant::now();
+        for group in self.groups.iter_mut().filter(|g| g.next_poll <= now) {
+            group.next_poll = now + delay;
+        }
+    }
+
+    /// Schedule the next poll of `group` after a successful poll, returns the number of failed
+    /// polls before it.
+    pub fn record_success(&mut self, group: usize) -> u32 {
+        let group = &mut self.groups[group];
+        group.next_poll = Instant::now() + self.jitter.apply(group.interval);
+        group.backoff.reset();
+        std::mem::take(&mut group.failures)
+    }
+
+    /// Schedule the retry of `group` after a failed poll, returns the number of consecutive
+    /// failures and the time until the retry.
+    pub fn record_failure(&mut self, group: usize) -> (u32, Duration) {
+        let group = &mut self.groups[group];
+        let delay = group.backoff.next_delay_unbounded();
+        group.next_poll = Instant::now() + delay;
+        group.failures = group.failures.saturating_add(1);
+        (group.failures, delay)
+    }
+
+    /// Add a logged in `account`. An account which is already known replaces the one in its
+    /// state file, otherwise the account gets a new state file.
+    pub fn add(&mut self, account: Account) -> anyhow::Result<()> {
+        let email = account.email().to_string();
+        if let Some(group) = self.group_mut(&email) {
+            lock(&group.observer)
+                .add_account(account)
+                .map_err(|e| anyhow!("Failed to add account {email}: {e}"))?;
+            group.refresh();
+            return Ok(());
+        }
+
+        let dir = accounts_dir(&self.config_dir);
+        create_dir_user_only(&dir).map_err(|e| anyhow!("Failed to create {dir:?}: {e}"))?;
+        let path = state_path(&self.config_dir, &[&email]);
+        let mut observer = self.open(&path, true)?;
+        observer
+            .add_account(account)
+            .map_err(|e| anyhow!("Failed to add account {email}: {e}"))?;
+        let group = Group::new(path, observer, self.poll_interval, self.backoff);
+        self.groups.push(group);
+        Ok(())
+    }
+
+    /// Replace the account with the same email as `account`, e.g. after logging it in again. It
+    /// keeps its state file.
+    pub fn replace(&mut self, account: Account) -> anyhow::Result<()> {
+        let email = account.email().to_string();
+        let Some(group) = self.group_mut(&email) else {
+            return self.add(account);
+        };
+        let mut observer = lock(&group.observer);
+        observer
+            .remove_account(&email)
+            .and_then(|()| observer.add_account(account))
+            .map_err(|e| anyhow!("Failed to replace account {email}: {e}"))?;
+        drop(observer);
+        group.refresh();
+        Ok(())
+    }
+
+    /// Log out and remove the account `email`. The state file is deleted with its last account,
+    /// unless it is the shared `config` file.
+    pub fn remove(&mut self, email: &str) -> anyhow::Result<()> {
+        let Some(index) = self
+            .groups
+            .iter()
+            .position(|g| g.accounts.iter().any(|a| a.email == email))
+        else {
+            // The state of the account may have failed to load, it can still be deleted.
+            let path = account_state_path(&self.config_dir, email);
+            let position = self.unavailable.iter().position(|p| *p == path);
+            let Some(position) = position else {
+                return Err(anyhow!("Unknown account {email}"));
+            };
+            std::fs::remove_file(&path).map_err(|e| anyhow!("Failed to delete {path:?}: {e}"))?;
+            self.unavailable.remove(position);
+            return Ok(());
+        };
+        let group = &mut self.groups[index];
+        lock(&group.observer)
+            .remove_account(email)
+            .map_err(|e| anyhow!("Failed to remove account {email}: {e}"))?;
+        group.refresh();
+
+        if group.accounts.is_empty() {
+            let group = self.groups.remove(index);
+            if group.path != get_config_file_path(&self.config_dir) {
+                // The observer may write its state when it is dropped.
+                drop(group.observer);
+                std::fs::remove_file(&group.path)
+                    .map_err(|e| anyhow!("Failed to delete {:?}: {e}", group.path))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Stop polling the accounts whose backend isn't `enabled`, they stay in their state files.
+    /// Accounts which share a state with such an account are skipped as well, as they are
+    /// polled together. Returns the skipped accounts.
+    pub fn skip_backends(&mut self, enabled: impl Fn(&str) -> bool) -> Vec<AccountInfo> {
+        let mut skipped = Vec::new();
+        self.groups.retain(|group| {
+            if group.accounts.iter().all(|a| enabled(&a.backend)) {
+                return true;
+            }
+            skipped.extend(group.accounts.iter().cloned());
+            false
+        });
+        skipped
+    }
+
+    fn group_mut(&mut self, email: &str) -> Option<&mut Group> {
+        self.groups
+            .iter_mut()
+            .find(|g| g.accounts.iter().any(|a| a.email == email))
+    }
+
+    /// Poll the groups which are due, at most `concurrency` at the same time, and wait for the
+    /// polls to complete or to run for longer than `timeout`. Groups whose previous poll is still
+    /// running are skipped until their next interval.
+    pub fn poll(
+        &mut self,
+        concurrency: usize,
+        timeout: Option<Duration>,
+    ) -> anyhow::Result<Vec<PollOutcome>> {
+        if self.pool.is_none() {
+            self.pool = Some(PollPool::spawn(concurrency)?);
+        }
+        let pool = self.pool.as_ref().expect("poll pool was just created");
+
+        // Polls which timed out before and completed since.
+        while let Ok(message) = pool.messages.try_recv() {
+            if let PollMessage::Finished(index, outcome) = message {
+                finish_late(&mut self.groups[index], outcome);
+            }
+        }
+
+        // The polls only start once a thread is free, their timeout starts with them.
+        let mut pending = BTreeMap::<usize, Option<Instant>>::new();
+        let now = Instant::now();
+        for (index, group) in self.groups.iter_mut().enumerate() {
+            if group.next_poll > now {
+                continue;
+            }
+            if group.polling {
+                warn!(
+                    "Skipping poll of {}, its previous poll is still running",
+                    emails(&group.accounts)
+                );
+                group.next_poll = now + group.interval;
+                continue;
+            }
+            group.polling = true;
+            pool.submit(index, group.observer.clone());
+            pending.insert(index, None);
+        }
+
+        let mut outcomes = Vec::with_capacity(pending.len());
+        while !pending.is_empty() {
+            let deadline = timeout.and_then(|timeout| {
+                pending
+                    .values()
+                    .filter_map(|started| started.map(|started| started + timeout))
+                    .min()
+            });
+            let message = match deadline {
+                Some(deadline) => match pool.messages.recv_deadline(deadline) {
+                    Ok(message) => Some(message),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => {
+                        return Err(anyhow!("Poll threads exited while polling"))
+                    }
+                },
+                None => Some(
+                    pool.messages
+                        .recv()
+                        .map_err(|_| anyhow!("Poll threads exited while polling"))?,
+                ),
+            };
+
+            match message {
+                Some(PollMessage::Started(index)) => {
+                    pending.insert(index, Some(Instant::now()));
+                }
+                Some(PollMessage::Finished(index, outcome)) => {
+                    let group = &mut self.groups[index];
+                    if pending.remove(&index).is_none() {
+                        finish_late(group, outcome);
+                        continue;
+                    }
+                    group.polling = false;
+                    group.accounts = outcome.accounts.clone();
+                    outcomes.push(outcome);
+                }
+                None => {
+                    let now = Instant::now();
+                    let timeout = timeout.unwrap_or_default();
+                    pending.retain(|&index, started| match started {
+                        Some(started) if now.duration_since(*started) >= timeout => {
+                            let group = &mut self.groups[index];
+                            group.next_poll = now + group.interval;
+                            outcomes.push(PollOutcome {
+                                group: index,
+                                accounts: group.accounts.clone(),
+                                result: PollResult::TimedOut,
+                                duration: now.duration_since(*started),
+                            });
+                            false
+                        }
+                        _ => true,
+                    });
+                }
+            }
+        }
+        Ok(outcomes)
+    }
+}
+
+/// Take the outcome of a poll which completed after its timeout, the accounts can be polled again.
+fn finish_late(group: &mut Group, outcome: PollOutcome) {
+    group.polling = false;
+    group.accounts = outcome.accounts;
+    info!(
+        "Poll of {} completed after {} seconds, past its timeout",
+        emails(&group.accounts),
+        outcome.duration.as_secs()
+    );
+}
+
+/// The emails of `accounts`, for the log.
+fn emails(accounts: &[AccountInfo]) -> String {
+    accounts
+        .iter()
+        .map(|a| a.email.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Threads polling one observer at a time.
+struct PollPool {
+    jobs: Sender<(usize, Arc<Mutex<Observer>>)>,
+    messages: Receiver<PollMessage>,
+}
+
+impl PollPool {
+    fn spawn(threads: usize) -> anyhow::Result<Self> {
+        let (jobs, job_receiver) = crossbeam_channel::unbounded::<(usize, Arc<Mutex<Observer>>)>();
+        let (message_sender, messages) = crossbeam_channel::unbounded();
+        for n in 0..threads.max(1) {
+            let job_receiver = job_receiver.clone();
+            let message_sender = message_sender.clone();
+            std::thread::Builder::new()
+                .name(format!("poll-{n}"))
+                .spawn(move || {
+                    for (index, observer) in job_receiver {
+                        if message_sender.send(PollMessage::Started(index)).is_err() {
+                            break;
+                        }
+                        let start = Instant::now();
+                        let mut observer = lock(&observer);
+                        let result = match catch_unwind(AssertUnwindSafe(|| observer.poll())) {
+                            Ok(result) => PollResult::Completed(result),
+                            Err(_) => PollResult::Panicked,
+                        };
+                        let outcome = PollOutcome {
+                            group: index,
+                            accounts: AccountInfo::of(&observer),
+                            result,
+                            duration: start.elapsed(),
+                        };
+                        drop(observer);
+                        if message_sender
+                            .send(PollMessage::Finished(index, outcome))
+                            .is_err()
+                        {
+                            break;
+                        }
+                    }
+                    debug!("Poll thread exiting");
+                })
+                .map_err(|e| anyhow!("Failed to spawn poll thread: {e}"))?;
+        }
+        Ok(Self { jobs, messages })
+    }
+
+    fn submit(&self, index: usize, observer: Arc<Mutex<Observer>>) {
+        if self.jobs.send((index, observer)).is_err() {
+            error!("Poll threads have exited");
+        }
+    }
+}
+
+/// The observer, a poll which panicked doesn't prevent the next one.
+fn lock(observer: &Mutex<Observer>) -> std::sync::MutexGuard<'_, Observer> {
+    observer.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The backends of this build, [`Accounts`] only uses those in `enabled_backends`.
+pub fn backends() -> Vec<Arc<dyn Backend>> {
+    vec![
+        you_have_mail_common::backend::proton::new_backend(),
+        you_have_mail_common::backend::null::new_backend(&[
+            you_have_mail_common::backend::null::NullTestAccount {
+                email: "foo".to_string(),
+                password: "foo".to_string(),
+                totp: None,
+                wait_time: None,
+                refresh: false,
+            },
+        ]),
+    ]
+}
+
+/// The observer state files in `config_dir`, the shared `config` file first if there is one.
+pub fn state_files(config_dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let shared = get_config_file_path(config_dir);
+    if shared.exists() {
+        files.push(shared);
+    }
+
+    let dir = accounts_dir(config_dir);
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(files),
+        Err(e) => return Err(anyhow!("Failed to read {dir:?}: {e}")),
+    };
+    let mut account_files = Vec::new();
+    for entry in entries {
+        let path = entry
+            .map_err(|e| anyhow!("Failed to read {dir:?}: {e}"))?
+            .path();
+        if path.is_file() && path.extension().is_some_and(|e| e == STATE_EXTENSION) {
+            account_files.push(path);
+        }
+    }
+    account_files.sort();
+    files.extend(account_files);
+    Ok(files)
+}
+
+/// Directory of the per account state files.
+pub fn accounts_dir(config_dir: &Path) -> PathBuf {
+    config_dir.join(ACCOUNTS_DIR)
+}
+
+/// State file of an observer with the accounts `emails`. A single account has a file of its own,
+/// several accounts can only come from the shared `config` file.
+pub fn state_path(config_dir: &Path, emails: &[&str]) -> PathBuf {
+    match emails {
+        [email] => account_state_path(config_dir, email),
+        _ => get_config_file_path(config_dir),
+    }
+}
+
+fn account_state_path(config_dir: &Path, email: &str) -> PathBuf {
+    let name = email
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '@' | '.' | '-' | '_' | '+') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    accounts_dir(config_dir).join(format!("{name}.{STATE_EXTENSION}"))
+}
+
+/// Each observer state takes ownership of its key.
+pub fn copy_key(key: &Secret<EncryptionKey>) -> anyhow::Result<Secret<EncryptionKey>> {
+    EncryptionKey::try_from(key.expose_secret().as_ref())
+        .map(Secret::new)
+        .map_err(|_| anyhow!("Invalid encryption key"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn account_state_path_keeps_the_email_readable() {
+        let dir = Path::new("/config");
+        assert_eq!(
+            account_state_path(dir, "foo+news@proton.me"),
+            Path::new("/config/accounts/foo+news@proton.me.state")
+        );
+        assert_eq!(
+            account_state_path(dir, "../foo@proton.me"),
+            Path::new("/config/accounts/.._foo@proton.me.state")
+        );
+    }
+
+    #[test]
+    fn state_path_shares_the_config_file_between_accounts() {
+        let dir = Path::new("/config");
+        assert_eq!(
+            state_path(dir, &["foo@proton.me"]),
+            Path::new("/config/accounts/foo@proton.me.state")
+        );
+        assert_eq!(
+            state_path(dir, &["foo@proton.me", "bar@proton.me"]),
+            Path::new("/config/config")
+        );
+    }
+
+    #[test]
+    fn state_files_lists_the_shared_state_first() {
+        let dir = std::env::temp_dir().join(format!("yhm-accounts-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join(ACCOUNTS_DIR)).unwrap();
+        assert!(state_files(&dir).unwrap().is_empty());
+
+        for name in [
+            "b@proton.me.state",
+            "a@proton.me.state",
+            "a@proton.me.rotated",
+        ] {
+            std::fs::write(dir.join(ACCOUNTS_DIR).join(name), b"").unwrap();
+        }
+        std::fs::write(get_config_file_path(&dir), b"").unwrap();
+        assert_eq!(
+            state_files(&dir).unwrap(),
+            vec![
+                dir.join("config"),
+                dir.join(ACCOUNTS_DIR).join("a@proton.me.state"),
+                dir.join(ACCOUNTS_DIR).join("b@proton.me.state"),
+            ]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
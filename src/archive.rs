@@ -0,0 +1,128 @@
+//! Encrypted export of the observer accounts, to move them to another machine without logging
+//! in again.
+use crate::accounts::Accounts;
+use crate::utils::write_user_file;
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use you_have_mail_common::{EncryptionKey, ExposeSecret, Secret};
+
+/// Version 1 archives have the single observer state of the versions before the accounts got a
+/// state each, they can still be imported.
+const VERSION: u32 = 2;
+
+/// Environment variable with the key of the archive, it is prompted for if not set.
+pub const IMPORT_KEY_VARIABLE: &str = "YHM_IMPORT_KEY";
+
+#[derive(Deserialize)]
+struct ArchiveVersion {
+    version: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Archive {
+    version: u32,
+    states: Vec<ArchivedState>,
+}
+
+#[derive(Deserialize)]
+struct ArchiveV1 {
+    accounts: Vec<ArchivedAccount>,
+    state: Vec<u8>,
+}
+
+/// Accounts of an archive and the observer state they are stored in.
+#[derive(Serialize, Deserialize)]
+pub struct ArchivedState {
+    pub accounts: Vec<ArchivedAccount>,
+    /// The decrypted observer state, with the account sessions.
+    state: Vec<u8>,
+}
+
+impl ArchivedState {
+    /// The observer state encrypted with `key`.
+    pub fn encrypt_state(&self, key: &Secret<EncryptionKey>) -> anyhow::Result<Vec<u8>> {
+        key.expose_secret()
+            .encrypt(&self.state)
+            .map_err(|e| anyhow!("Failed to encrypt observer state: {e}"))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ArchivedAccount {
+    pub email: String,
+    pub backend: String,
+}
+
+/// Write the accounts and their sessions, read from the observer states of `accounts`, to
+/// `path`. The archive is encrypted with a new key which is returned, the states are never
+/// written in plain text.
+pub fn export(
+    accounts: &Accounts,
+    key: &Secret<EncryptionKey>,
+    path: &Path,
+) -> anyhow::Result<Secret<EncryptionKey>> {
+    let mut states = Vec::with_capacity(accounts.groups().len());
+    for group in accounts.groups() {
+        let state_path = group.path();
+        let encrypted = std::fs::read(state_path)
+            .map_err(|e| anyhow!("Failed to read observer state at {state_path:?}: {e}"))?;
+        let state = key
+            .expose_secret()
+            .decrypt(&encrypted)
+            .map_err(|e| anyhow!("Failed to decrypt observer state at {state_path:?}: {e}"))?;
+        states.push(ArchivedState {
+            accounts: group
+                .accounts()
+                .iter()
+                .map(|account| ArchivedAccount {
+                    email: account.email.clone(),
+                    backend: account.backend.clone(),
+                })
+                .collect(),
+            state,
+        });
+    }
+    let archive = Archive {
+        version: VERSION,
+        states,
+    };
+    let contents =
+        serde_json::to_vec(&archive).map_err(|e| anyhow!("Failed to serialize accounts: {e}"))?;
+
+    let archive_key = EncryptionKey::new();
+    let encrypted = archive_key
+        .expose_secret()
+        .encrypt(&contents)
+        .map_err(|e| anyhow!("Failed to encrypt accounts: {e}"))?;
+    write_user_file(path, &encrypted).map_err(|e| anyhow!("Failed to write {path:?}: {e}"))?;
+    Ok(archive_key)
+}
+
+/// Read the archive at `path` written by [`export`] with its base64 encoded `key`.
+pub fn import(path: &Path, key: &str) -> anyhow::Result<Vec<ArchivedState>> {
+    let key = EncryptionKey::with_base64(key.trim()).map_err(|_| anyhow!("Invalid key format"))?;
+    let encrypted = std::fs::read(path).map_err(|e| anyhow!("Failed to read {path:?}: {e}"))?;
+    let contents = key
+        .decrypt(&encrypted)
+        .map_err(|e| anyhow!("Failed to decrypt {path:?}, check the key: {e}"))?;
+    let parse_error = |e: serde_json::Error| anyhow!("Failed to parse {path:?}: {e}");
+    match serde_json::from_slice::<ArchiveVersion>(&contents)
+        .map_err(parse_error)?
+        .version
+    {
+        1 => {
+            let archive = serde_json::from_slice::<ArchiveV1>(&contents).map_err(parse_error)?;
+            Ok(vec![ArchivedState {
+                accounts: archive.accounts,
+                state: archive.state,
+            }])
+        }
+        VERSION => Ok(serde_json::from_slice::<Archive>(&contents)
+            .map_err(parse_error)?
+            .states),
+        version => Err(anyhow!(
+            "Unsupported version {version} of {path:?}, it was exported by a newer version"
+        )),
+    }
+}
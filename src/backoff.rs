@@ -0,0 +1,165 @@
+//! Exponential backoff shared by the code paths which retry failed operations.
+use serde::Deserialize;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct BackoffConfig {
+    /// Delay in seconds before the first retry.
+    #[serde(default = "default_initial_delay")]
+    pub initial_delay: u64,
+    /// Upper bound in seconds for the delay between retries.
+    #[serde(default = "default_max_delay")]
+    pub max_delay: u64,
+    /// Factor applied to the delay after each retry.
+    #[serde(default = "default_multiplier")]
+    pub multiplier: f64,
+    /// Number of retries before giving up, 0 for unlimited retries.
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+}
+
+impl Default for BackoffConfig {
+    fn default() -> Self {
+        Self {
+            initial_delay: default_initial_delay(),
+            max_delay: default_max_delay(),
+            multiplier: default_multiplier(),
+            max_retries: default_max_retries(),
+        }
+    }
+}
+
+const fn default_initial_delay() -> u64 {
+    5
+}
+
+const fn default_max_delay() -> u64 {
+    600
+}
+
+const fn default_multiplier() -> f64 {
+    2.0
+}
+
+const fn default_max_retries() -> u32 {
+    5
+}
+
+/// Tracks the delay before the next retry of an operation.
+pub struct Backoff {
+    config: BackoffConfig,
+    retries: u32,
+}
+
+impl Backoff {
+    pub fn new(config: BackoffConfig) -> Self {
+        Self { config, retries: 0 }
+    }
+
+    /// Same as [`Backoff::new`], but the delay never exceeds `max_delay`.
+    pub fn with_max_delay(mut config: BackoffConfig, max_delay: Duration) -> Self {
+        config.max_delay = config.max_delay.min(max_delay.as_secs());
+        Self::new(config)
+    }
+
+    /// Delay before the next retry, `None` once all retries have been used.
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        if self.config.max_retries != 0 && self.retries >= self.config.max_retries {
+            return None;
+        }
+
+        let delay = self.delay(self.retries);
+        self.retries = self.retries.saturating_add(1);
+        Some(delay)
+    }
+
+    /// Like [`Backoff::next_delay`], but keeps retrying at the maximum delay once all retries
+    /// have been used.
+    pub fn next_delay_unbounded(&mut self) -> Duration {
+        self.next_delay().unwrap_or_else(|| self.max_delay())
+    }
+
+    /// Start over after the operation succeeded.
+    pub fn reset(&mut self) {
+        self.retries = 0;
+    }
+
+    pub fn max_delay(&self) -> Duration {
+        Duration::from_secs(self.config.max_delay)
+    }
+
+    fn delay(&self, retry: u32) -> Duration {
+        let multiplier = self.config.multiplier.max(1.0);
+        let exponent = i32::try_from(retry).unwrap_or(i32::MAX);
+        let delay = (self.config.initial_delay as f64 * multiplier.powi(exponent))
+            .min(self.config.max_delay as f64);
+        Duration::from_secs_f64(delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(max_retries: u32) -> BackoffConfig {
+        BackoffConfig {
+            initial_delay: 5,
+            max_delay: 30,
+            multiplier: 2.0,
+            max_retries,
+        }
+    }
+
+    fn secs(secs: u64) -> Option<Duration> {
+        Some(Duration::from_secs(secs))
+    }
+
+    #[test]
+    fn delays_grow_up_to_the_maximum() {
+        let mut backoff = Backoff::new(config(0));
+        let delays = (0..5).map(|_| backoff.next_delay()).collect::<Vec<_>>();
+        assert_eq!(delays, [secs(5), secs(10), secs(20), secs(30), secs(30)]);
+    }
+
+    #[test]
+    fn delays_stop_after_max_retries() {
+        let mut backoff = Backoff::new(config(2));
+        assert_eq!(backoff.next_delay(), secs(5));
+        assert_eq!(backoff.next_delay(), secs(10));
+        assert_eq!(backoff.next_delay(), None);
+        assert_eq!(backoff.next_delay_unbounded(), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn reset_starts_over() {
+        let mut backoff = Backoff::new(config(2));
+        backoff.next_delay();
+        backoff.next_delay();
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), secs(5));
+    }
+
+    #[test]
+    fn multiplier_below_one_keeps_the_delay() {
+        let mut backoff = Backoff::new(BackoffConfig {
+            multiplier: 0.5,
+            ..config(0)
+        });
+        assert_eq!(backoff.next_delay(), secs(5));
+        assert_eq!(backoff.next_delay(), secs(5));
+    }
+
+    #[test]
+    fn with_max_delay_lowers_the_maximum() {
+        let mut backoff = Backoff::with_max_delay(config(0), Duration::from_secs(8));
+        assert_eq!(backoff.next_delay(), secs(5));
+        assert_eq!(backoff.next_delay(), secs(8));
+        assert_eq!(backoff.max_delay(), Duration::from_secs(8));
+    }
+
+    #[test]
+    fn large_retry_counts_stay_at_the_maximum() {
+        let backoff = Backoff::new(config(0));
+        assert_eq!(backoff.delay(u32::MAX), Duration::from_secs(30));
+    }
+}
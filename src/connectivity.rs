@@ -0,0 +1,99 @@
+//! Detect network loss so polling can be paused until connectivity returns.
+use crate::backoff::{Backoff, BackoffConfig};
+use log::{info, warn};
+use serde::Deserialize;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+#[derive(Debug, Deserialize)]
+pub struct ConnectivityConfig {
+    /// `host:port` which is contacted to check whether the network is available.
+    #[serde(default = "default_address")]
+    pub address: String,
+    /// Connection timeout in seconds.
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    /// Send a notification when the network is available again.
+    #[serde(default)]
+    pub notify_online: bool,
+}
+
+fn default_address() -> String {
+    "mail.proton.me:443".to_string()
+}
+
+const fn default_timeout() -> u64 {
+    5
+}
+
+pub enum ConnectivityState {
+    Online {
+        /// The network was previously unavailable.
+        reconnected: bool,
+    },
+    Offline {
+        /// Delay before checking again.
+        retry_in: Duration,
+    },
+}
+
+pub struct Connectivity {
+    config: ConnectivityConfig,
+    backoff: Backoff,
+    offline: bool,
+}
+
+impl Connectivity {
+    /// `max_retry_delay` caps the backoff between checks while offline. Checks continue at the
+    /// maximum delay once the backoff's retries are exhausted.
+    pub fn new(
+        config: ConnectivityConfig,
+        backoff: BackoffConfig,
+        max_retry_delay: Duration,
+    ) -> Self {
+        Self {
+            config,
+            backoff: Backoff::with_max_delay(backoff, max_retry_delay),
+            offline: false,
+        }
+    }
+
+    pub fn notify_online(&self) -> bool {
+        self.config.notify_online
+    }
+
+    pub fn check(&mut self) -> ConnectivityState {
+        if is_reachable(
+            &self.config.address,
+            Duration::from_secs(self.config.timeout),
+        ) {
+            let reconnected = std::mem::take(&mut self.offline);
+            if reconnected {
+                info!("Network is available again, resuming polling");
+                self.backoff.reset();
+            }
+            return ConnectivityState::Online { reconnected };
+        }
+
+        if !self.offline {
+            warn!(
+                "Network is unavailable ({} unreachable), pausing polling",
+                self.config.address
+            );
+            self.offline = true;
+        }
+
+        ConnectivityState::Offline {
+            retry_in: self.backoff.next_delay_unbounded(),
+        }
+    }
+}
+
+fn is_reachable(address: &str, timeout: Duration) -> bool {
+    let Ok(addrs) = address.to_socket_addrs() else {
+        return false;
+    };
+    addrs
+        .into_iter()
+        .any(|addr| TcpStream::connect_timeout(&addr, timeout).is_ok())
+}
@@ -0,0 +1,66 @@
+//! `GET /health` endpoint for monitoring whether the observer is still polling.
+use crate::http_server::{self, Response};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Liveness of the observer loop, shared between the loop and the HTTP server.
+pub struct Health {
+    /// Unix time in seconds of the last successful poll, or of the start before the first one.
+    last_success: AtomicU64,
+    poll_interval: AtomicU64,
+    max_missed_polls: u64,
+}
+
+impl Health {
+    fn new(poll_interval: Duration, max_missed_polls: u64) -> Self {
+        Self {
+            last_success: AtomicU64::new(now()),
+            poll_interval: AtomicU64::new(poll_interval.as_secs()),
+            max_missed_polls,
+        }
+    }
+
+    pub fn record_poll(&self) {
+        self.last_success.store(now(), Ordering::Relaxed);
+    }
+
+    pub fn set_poll_interval(&self, poll_interval: Duration) {
+        self.poll_interval
+            .store(poll_interval.as_secs(), Ordering::Relaxed);
+    }
+
+    /// Healthy if a poll succeeded within the last `max_missed_polls` poll intervals.
+    fn is_healthy(&self) -> bool {
+        let max_age = self
+            .poll_interval
+            .load(Ordering::Relaxed)
+            .saturating_mul(self.max_missed_polls);
+        now().saturating_sub(self.last_success.load(Ordering::Relaxed)) <= max_age
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Answer `GET /health` requests on `address`.
+pub fn serve(
+    address: &str,
+    poll_interval: Duration,
+    max_missed_polls: u64,
+) -> anyhow::Result<Arc<Health>> {
+    let health = Arc::new(Health::new(poll_interval, max_missed_polls));
+    {
+        let health = health.clone();
+        http_server::serve(address, "health", move |path| match path {
+            "/health" if health.is_healthy() => Response::text("200 OK", "OK"),
+            "/health" => Response::text("503 Service Unavailable", "No recent successful poll"),
+            _ => Response::not_found(),
+        })?;
+    }
+    Ok(health)
+}
@@ -0,0 +1,194 @@
+//! Persistent record of the notifications which were delivered.
+use crate::notifiers::{visible_emails, Email, Event};
+use crate::utils::write_user_file;
+use anyhow::anyhow;
+use chrono::TimeZone;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const FILENAME: &str = "notification_history.jsonl";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Unix timestamp in seconds of the delivery.
+    pub ts: u64,
+    /// Notifier which delivered the notification.
+    pub notifier: String,
+    pub event: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    /// Emails of new email notifications, empty if the notification was redacted.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub emails: Vec<HistoryEmail>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistoryEmail {
+    pub sender: String,
+    pub subject: String,
+}
+
+impl From<&Email> for HistoryEmail {
+    fn from(email: &Email) -> Self {
+        Self {
+            sender: email.sender.clone(),
+            subject: email.subject.clone(),
+        }
+    }
+}
+
+/// History of delivered notifications, stored as JSON lines in the config directory. Entries
+/// above `max_entries` or older than `max_days` are pruned.
+pub struct History {
+    path: PathBuf,
+    max_entries: usize,
+    max_age_secs: u64,
+    entries: Mutex<Vec<HistoryEntry>>,
+}
+
+impl History {
+    /// Load and prune the history in `dir`.
+    pub fn open(dir: impl AsRef<Path>, max_entries: usize, max_days: u64) -> Self {
+        let history = Self {
+            path: dir.as_ref().join(FILENAME),
+            max_entries,
+            max_age_secs: max_days.saturating_mul(24 * 60 * 60),
+            entries: Mutex::new(load(dir)),
+        };
+
+        let mut entries = history.entries.lock().unwrap();
+        if history.prune(&mut entries) {
+            history.save(&entries);
+        }
+        drop(entries);
+        history
+    }
+
+    /// Add the delivery of `event` by the notifier `notifier`.
+    pub fn append(&self, notifier: &str, event: &Event) {
+        let (account, emails) = match event {
+            Event::NewEmail {
+                account,
+                emails,
+                redacted,
+                ..
+            } => (
+                Some(account.clone()),
+                visible_emails(emails, *redacted)
+                    .iter()
+                    .map(HistoryEmail::from)
+                    .collect(),
+            ),
+            Event::AccountLoggedOut { account, .. } | Event::AccountError { account, .. } => {
+                (Some(account.clone()), Vec::new())
+            }
+            _ => (None, Vec::new()),
+        };
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.push(HistoryEntry {
+            ts: unix_now(),
+            notifier: notifier.to_string(),
+            event: event.kind().to_string(),
+            account,
+            emails,
+        });
+        self.prune(&mut entries);
+        self.save(&entries);
+    }
+
+    /// Returns true if entries were removed.
+    fn prune(&self, entries: &mut Vec<HistoryEntry>) -> bool {
+        let len = entries.len();
+        let cutoff = unix_now().saturating_sub(self.max_age_secs);
+        entries.retain(|e| e.ts >= cutoff);
+        if entries.len() > self.max_entries {
+            entries.drain(..entries.len() - self.max_entries);
+        }
+        entries.len() != len
+    }
+
+    fn save(&self, entries: &[HistoryEntry]) {
+        let mut contents = Vec::new();
+        for entry in entries {
+            if let Err(e) = serde_json::to_writer(&mut contents, entry) {
+                warn!("Failed to serialize history entry: {e}");
+                continue;
+            }
+            contents.push(b'\n');
+        }
+
+        if let Err(e) = write_user_file(&self.path, &contents) {
+            warn!(
+                "Failed to write notification history to {:?}: {e}",
+                self.path
+            );
+        }
+    }
+}
+
+/// Read the history entries from `dir`, oldest first. Invalid lines are skipped.
+pub fn load(dir: impl AsRef<Path>) -> Vec<HistoryEntry> {
+    let path = dir.as_ref().join(FILENAME);
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!("Failed to read notification history {path:?}: {e}");
+            }
+            return Vec::new();
+        }
+    };
+
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                warn!("Skipping invalid notification history entry: {e}");
+                None
+            }
+        })
+        .collect()
+}
+
+/// Print the history in `dir` to stdout.
+pub fn print(dir: impl AsRef<Path>) -> anyhow::Result<()> {
+    let entries = load(dir);
+    if entries.is_empty() {
+        println!("No notifications in history");
+        return Ok(());
+    }
+
+    for entry in entries {
+        let time = chrono::Local
+            .timestamp_opt(entry.ts as i64, 0)
+            .single()
+            .ok_or_else(|| anyhow!("Invalid timestamp {} in history", entry.ts))?;
+        let mut line = format!(
+            "{} {} {}",
+            time.format("%Y-%m-%d %H:%M:%S"),
+            entry.notifier,
+            entry.event
+        );
+        if let Some(account) = &entry.account {
+            line.push_str(&format!(" {account}"));
+        }
+        println!("{line}");
+        for email in &entry.emails {
+            println!("    Sender={} Subject={}", email.sender, email.subject);
+        }
+    }
+    Ok(())
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
@@ -0,0 +1,70 @@
+//! Commands run around each poll of the observer.
+use anyhow::anyhow;
+use log::{debug, error, warn};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+
+/// Run `command` with the system shell and wait for it to exit. The command is killed if it
+/// does not finish within `timeout`. Returns whether the command succeeded.
+pub fn run_hook(kind: &str, command: &str, timeout: Duration) -> bool {
+    debug!("Running {kind} hook: {command}");
+    match run(command, timeout) {
+        Ok(status) if status.success() => true,
+        Ok(status) => {
+            warn!("{kind} hook failed with {status}");
+            false
+        }
+        Err(e) => {
+            error!("{kind} hook failed: {e}");
+            false
+        }
+    }
+}
+
+fn run(command: &str, timeout: Duration) -> anyhow::Result<ExitStatus> {
+    let mut child = shell(command)
+        .stdin(Stdio::null())
+        .spawn()
+        .map_err(|e| anyhow!("Failed to start '{command}': {e}"))?;
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child
+            .try_wait()
+            .map_err(|e| anyhow!("Failed to wait for '{command}': {e}"))?
+        {
+            return Ok(status);
+        }
+
+        if Instant::now() >= deadline {
+            kill(&mut child);
+            return Err(anyhow!(
+                "'{command}' did not finish within {} seconds",
+                timeout.as_secs()
+            ));
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+fn kill(child: &mut Child) {
+    if let Err(e) = child.kill() {
+        error!("Failed to kill hook process: {e}");
+    }
+    // Reap the process.
+    let _ = child.wait();
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command);
+    cmd
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.arg("/C").arg(command);
+    cmd
+}
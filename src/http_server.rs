@@ -0,0 +1,77 @@
+//! Minimal HTTP server for the monitoring endpoints, only `GET` requests are answered.
+use anyhow::anyhow;
+use log::{debug, info, warn};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+pub struct Response {
+    pub status: &'static str,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl Response {
+    pub fn text(status: &'static str, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            content_type: "text/plain",
+            body: body.into(),
+        }
+    }
+
+    pub fn not_found() -> Self {
+        Self::text("404 Not Found", "404 Not Found")
+    }
+}
+
+/// Listen on `address` and answer the requests with `handler`, which receives the request path,
+/// from a thread called `{name}-thread`.
+pub fn serve(
+    address: &str,
+    name: &'static str,
+    handler: impl Fn(&str) -> Response + Send + 'static,
+) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(address)
+        .map_err(|e| anyhow!("Failed to listen for {name} requests on {address}: {e}"))?;
+    info!("Serving {name} requests on http://{address}");
+
+    std::thread::Builder::new()
+        .name(format!("{name}-thread"))
+        .spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        if let Err(e) = respond(stream, &handler) {
+                            debug!("Failed to answer {name} request: {e}");
+                        }
+                    }
+                    Err(e) => warn!("Failed to accept {name} connection: {e}"),
+                }
+            }
+        })
+        .map_err(|e| anyhow!("Failed to spawn {name} thread: {e}"))?;
+    Ok(())
+}
+
+fn respond(mut stream: TcpStream, handler: &impl Fn(&str) -> Response) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+
+    let mut parts = request_line.split_whitespace();
+    let response = match (parts.next(), parts.next()) {
+        (Some("GET"), Some(path)) => handler(path),
+        _ => Response::text("405 Method Not Allowed", "405 Method Not Allowed"),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        response.content_type,
+        response.body.len(),
+        response.body
+    )?;
+    stream.flush()
+}
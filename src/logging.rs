@@ -1,61 +1,300 @@
+use crate::utils::is_disk_full;
 use anyhow::anyhow;
-use log::LevelFilter;
+use chrono::{DateTime, Local, NaiveDate};
+use log::{LevelFilter, Record};
 use log4rs::append::console::{ConsoleAppender, Target};
 use log4rs::append::rolling_file::policy::compound::roll::fixed_window::FixedWindowRoller;
 use log4rs::append::rolling_file::policy::compound::trigger::size::SizeTrigger;
+use log4rs::append::rolling_file::policy::compound::trigger::Trigger;
 use log4rs::append::rolling_file::policy::compound::CompoundPolicy;
-use log4rs::append::rolling_file::RollingFileAppender;
+use log4rs::append::rolling_file::{LogFile, RollingFileAppender};
+use log4rs::append::Append;
 use log4rs::config::{Appender, Logger, Root};
+use log4rs::encode::json::JsonEncoder;
 use log4rs::encode::pattern::PatternEncoder;
-use std::path::Path;
+use log4rs::encode::Encode;
+use log4rs::Handle;
+use serde::Deserialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 
-pub fn init_log(file_path: impl AsRef<Path>) -> Result<(), anyhow::Error> {
-    let pattern = "{d(%Y-%m-%d %H:%M:%S)} | {({l}):5.5} | {m}{n}";
+/// Format of the log messages.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human readable lines.
+    #[default]
+    Text,
+    /// One JSON object per line, for log collectors.
+    Json,
+}
+
+/// When the log file is rolled over.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum LogRotation {
+    /// On the first message of a new day.
+    Daily,
+    /// When the file exceeds this many bytes.
+    Size(u64),
+}
+
+impl FromStr for LogRotation {
+    type Err = anyhow::Error;
+
+    /// Parse "daily" or "size:<N>[B|KB|MB|GB]", e.g. "size:10MB".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            || anyhow!("Invalid log rotation '{s}', expected \"daily\" or e.g. \"size:10MB\"");
+        if s.eq_ignore_ascii_case("daily") {
+            return Ok(Self::Daily);
+        }
+
+        let size = s.strip_prefix("size:").ok_or_else(invalid)?.trim();
+        let split = size
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(size.len());
+        let (value, unit) = size.split_at(split);
+        let multiplier = match unit.trim().to_ascii_uppercase().as_str() {
+            "" | "B" => 1,
+            "KB" => 1024,
+            "MB" => 1024 * 1024,
+            "GB" => 1024 * 1024 * 1024,
+            _ => return Err(invalid()),
+        };
+        let value = value.parse::<u64>().map_err(|_| invalid())?;
+        match value.checked_mul(multiplier) {
+            Some(bytes) if bytes > 0 => Ok(Self::Size(bytes)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Runtime adjustable logging settings.
+#[derive(Debug, Copy, Clone)]
+pub struct LogSettings {
+    /// Level of the CLI's and the observer's log messages.
+    pub level: LevelFilter,
+    /// Also write the log messages to stdout.
+    pub console: bool,
+    pub format: LogFormat,
+    pub rotation: LogRotation,
+    /// Number of rolled over log files which are kept.
+    pub max_files: u32,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        Self {
+            level: LevelFilter::Debug,
+            console: true,
+            format: LogFormat::Text,
+            rotation: LogRotation::Daily,
+            max_files: 7,
+        }
+    }
+}
+
+/// Rolls the log file over when the local date changes, including when a file of a previous day
+/// is written to after a restart.
+#[derive(Debug)]
+struct DailyTrigger {
+    day: Mutex<NaiveDate>,
+}
+
+impl DailyTrigger {
+    fn new(file: &Path) -> Self {
+        let day = std::fs::metadata(file)
+            .and_then(|m| m.modified())
+            .map(|modified| DateTime::<Local>::from(modified).date_naive())
+            .unwrap_or_else(|_| Local::now().date_naive());
+        Self {
+            day: Mutex::new(day),
+        }
+    }
+}
+
+impl Trigger for DailyTrigger {
+    fn trigger(&self, _: &LogFile) -> anyhow::Result<bool> {
+        let today = Local::now().date_naive();
+        let mut day = self.day.lock().unwrap();
+        if *day == today {
+            return Ok(false);
+        }
+        *day = today;
+        Ok(true)
+    }
+}
+
+/// Drops the log messages which can't be written because the disk is full, instead of
+/// reporting every one of them on stderr. Writing resumes once space is available again.
+#[derive(Debug)]
+struct DiskFullAppender<A> {
+    inner: A,
+    disk_full: AtomicBool,
+}
+
+impl<A> DiskFullAppender<A> {
+    fn new(inner: A) -> Self {
+        Self {
+            inner,
+            disk_full: AtomicBool::new(false),
+        }
+    }
+}
+
+impl<A: Append> Append for DiskFullAppender<A> {
+    fn append(&self, record: &Record) -> anyhow::Result<()> {
+        match self.inner.append(record) {
+            Ok(()) => {
+                if self.disk_full.swap(false, Ordering::Relaxed) {
+                    let _ = writeln!(std::io::stderr(), "Log file is being written again");
+                }
+                Ok(())
+            }
+            Err(e) if is_disk_full(e.as_ref()) => {
+                if !self.disk_full.swap(true, Ordering::Relaxed) {
+                    let _ = writeln!(
+                        std::io::stderr(),
+                        "Disk is full, log messages are dropped until space is available: {e}"
+                    );
+                }
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+}
+
+pub struct Logging {
+    handle: Handle,
+    dir: PathBuf,
+}
+
+impl Logging {
+    /// Replace the logging settings, e.g. after the config file was loaded.
+    pub fn reconfigure(&self, settings: &LogSettings) -> Result<(), anyhow::Error> {
+        self.handle.set_config(build_config(&self.dir, settings)?);
+        Ok(())
+    }
+
+    /// Remove the rolled over log files beyond `max_files`, left behind when the number of kept
+    /// files was reduced.
+    pub fn prune_log_files(&self, max_files: u32) {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return;
+        };
+
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let index = name
+                .to_str()
+                .and_then(|name| name.strip_prefix("yhm."))
+                .and_then(|name| name.strip_suffix(".log"))
+                .and_then(|index| index.parse::<u32>().ok());
+            if matches!(index, Some(index) if index >= max_files) {
+                if let Err(e) = std::fs::remove_file(entry.path()) {
+                    log::warn!("Failed to remove old log file {:?}: {e}", entry.path());
+                }
+            }
+        }
+    }
+}
+
+/// Level set with the conventional `RUST_LOG` environment variable. Only a single level such as
+/// `RUST_LOG=trace` is supported, per module directives are ignored.
+pub fn rust_log_level() -> Option<LevelFilter> {
+    std::env::var("RUST_LOG").ok()?.trim().parse().ok()
+}
+
+pub fn init_log(
+    file_path: impl AsRef<Path>,
+    settings: &LogSettings,
+) -> Result<Logging, anyhow::Error> {
+    let config = build_config(file_path.as_ref(), settings)?;
+    let handle = log4rs::init_config(config).map_err(|e| anyhow!("Failed to init logger: {e}"))?;
+    Ok(Logging {
+        handle,
+        dir: file_path.as_ref().to_path_buf(),
+    })
+}
+
+/// Rolled over log files are named `yhm.{index}.log`, with 0 the most recent.
+const ROLLED_LOG_PATTERN: &str = "yhm.{}.log";
+
+fn build_config(file_path: &Path, settings: &LogSettings) -> Result<log4rs::Config, anyhow::Error> {
+    if settings.max_files == 0 {
+        return Err(anyhow!("log_max_files must be at least 1"));
+    }
+
+    let encoder = || -> Box<dyn Encode> {
+        match settings.format {
+            LogFormat::Text => Box::new(PatternEncoder::new(
+                "{d(%Y-%m-%d %H:%M:%S)} | {({l}):5.5} | {m}{n}",
+            )),
+            LogFormat::Json => Box::new(JsonEncoder::new()),
+        }
+    };
     let console = ConsoleAppender::builder()
-        .encoder(Box::new(PatternEncoder::new(pattern)))
+        .encoder(encoder())
         .target(Target::Stdout)
         .build();
+    let log_file_path = file_path.join("yhm.log");
+    let trigger: Box<dyn Trigger> = match settings.rotation {
+        LogRotation::Daily => Box::new(DailyTrigger::new(&log_file_path)),
+        LogRotation::Size(bytes) => Box::new(SizeTrigger::new(bytes)),
+    };
     let log_file = RollingFileAppender::builder()
-        .encoder(Box::new(PatternEncoder::new(pattern)))
+        .encoder(encoder())
         .append(true)
         .build(
-            file_path.as_ref().join("yhm.log"),
+            log_file_path,
             Box::new(CompoundPolicy::new(
-                Box::new(SizeTrigger::new(5 * 1024 * 1024)),
+                trigger,
                 Box::new(
                     FixedWindowRoller::builder()
                         .base(0)
-                        .build(&file_path.as_ref().join("yhm.{}.log").to_string_lossy(), 2)
+                        .build(
+                            &file_path.join(ROLLED_LOG_PATTERN).to_string_lossy(),
+                            settings.max_files,
+                        )
                         .map_err(|e| anyhow!("Failed to init window roller: {e}"))?,
                 ),
             )),
         )
         .map_err(|e| anyhow!("Failed to build file logger: {e}"))?;
 
-    let config = log4rs::Config::builder()
+    let appenders: &[&str] = if settings.console {
+        &["console", "logfile"]
+    } else {
+        &["logfile"]
+    };
+
+    log4rs::Config::builder()
         .appender(Appender::builder().build("console", Box::new(console)))
-        .appender(Appender::builder().build("logfile", Box::new(log_file)))
+        .appender(Appender::builder().build("logfile", Box::new(DiskFullAppender::new(log_file))))
         .logger(
             Logger::builder()
                 .additive(false)
                 .appender("logfile")
-                .build("you_have_mail_common", LevelFilter::Debug),
+                .build("you_have_mail_common", settings.level),
         )
         .logger(
             Logger::builder()
                 .additive(false)
-                .appender("console")
-                .appender("logfile")
-                .build("you_have_mail_cli", LevelFilter::Debug),
+                .appenders(appenders.iter().copied())
+                .build("you_have_mail_cli", settings.level),
         )
         .build(
             Root::builder()
-                .appender("console")
-                .appender("logfile")
-                .build(LevelFilter::Info),
+                .appenders(appenders.iter().copied())
+                .build(settings.level.min(LevelFilter::Info)),
         )
-        .map_err(|e| anyhow!("Failed to build log config: {e}"))?;
-
-    log4rs::init_config(config).map_err(|e| anyhow!("Failed to init logger: {e}"))?;
-    Ok(())
+        .map_err(|e| anyhow!("Failed to build log config: {e}"))
 }
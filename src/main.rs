@@ -27,38 +27,116 @@
 #![cfg_attr(feature = "clippy", warn(unseparated_literal_suffix))]
 #![cfg_attr(feature = "clippy", warn(wrong_pub_self_convention))]
 
+use crate::accounts::{AccountInfo, Accounts, PollResult};
 use crate::cfg::load_config;
-use crate::notifiers::NotifierMultiplexerBuilder;
-use crate::secrets::{new_secrets, SecretsType};
+use crate::connectivity::{Connectivity, ConnectivityState};
+use crate::history::History;
+use crate::logging::{LogFormat, LogRotation, LogSettings};
+use crate::notifiers::{
+    BreakerState, Event, NotifierContext, NotifierMultiplexer, NotifierMultiplexerBuilder,
+    Redaction, ReloadableNotifier, SeenEmails,
+};
+use crate::secrets::{failure_hint, new_secrets, self_test, Secrets, SecretsOptions, SecretsType};
+use crate::standby::Standby;
+use crate::state::StateFile;
+use crate::suspend::{SuspendDetector, SUSPEND_CHECK_INTERVAL};
+use crate::totp::{TotpSecret, TotpSecrets};
 use crate::utils::{
-    get_config_file_path, get_default_config_dir, get_default_log_dir, get_or_create_secret_key,
-    GetSecretKeyState,
+    check_user_only_permissions, create_dir_user_only, get_default_config_dir, get_default_log_dir,
+    get_or_create_secret_key, is_disk_full, is_fatal_error, write_user_file, GetSecretKeyState,
+    Jitter, Throttle,
 };
 use anyhow::anyhow;
+use chrono::{Local, TimeZone};
 use clap::Parser;
 use crossbeam_channel::select;
-use log::{debug, error, info, warn};
-use std::io::{stdin, stdout, BufRead, Write};
-use std::path::PathBuf;
+use log::{debug, error, info, warn, LevelFilter};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::io::{stdin, stdout, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use you_have_mail_common::backend::BackendError;
-use you_have_mail_common::{Account, AccountError, Observer, ObserverBuilder, Secret};
+use you_have_mail_common::{
+    Account, AccountError, EncryptionKey, ExposeSecret, Notification, Notifier, Secret,
+};
 
+mod accounts;
+mod archive;
+mod backoff;
 mod cfg;
+mod connectivity;
+#[cfg(feature = "healthcheck")]
+mod health;
+mod history;
+mod hooks;
+#[cfg(any(feature = "healthcheck", feature = "metrics"))]
+mod http_server;
 mod logging;
+#[cfg(feature = "metrics")]
+mod metrics;
 mod notifiers;
 mod secrets;
+mod standby;
+mod state;
+mod suspend;
+mod totp;
 mod utils;
 
 pub const APP_IDENTIFIER: &str = "dev.lbeernaert.you-have-mail-cli";
 
+/// Minimum time between two "disk full" notifications.
+const DISK_FULL_NOTIFICATION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Maximum time to wait for queued notifications to be sent on exit.
+const NOTIFIER_FLUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+const EXAMPLE_CONFIG: &str = include_str!("../example-config.toml");
+
 const LOG_DIR_DESC: &str = "Directory where the log files will be written";
 const CONFIG_DIR_DESC: &str = "Directory where the config files will be written";
 const CONFIGURE_ACCOUNTS_DESC:&str = "When used will start an interactive prompt to configure any accounts that do not exist or are logged out";
 const DELETE_ACCOUNTS_DESC: &str =
     "Log out and delete any accounts that are not listed in the config file";
+const REMOVE_ACCOUNT_DESC: &str =
+    "Log out and remove the account with this email, can be repeated. Without an email the accounts to remove are picked from a list";
 const CREATE_CONFIG_DESC: &str = "Create an empty config file if none exists";
+const VERIFY_SECRETS_DESC: &str =
+    "Check that the stored encryption key can decrypt the observer state, without modifying anything";
+const ROTATE_KEY_DESC: &str =
+    "Replace the encryption key with a new one and re-encrypt the observer state with it";
+const TEST_NOTIFIER_DESC: &str =
+    "Send a test new email notification through the notifier with the given name only and report whether it was delivered";
+const TEST_NOTIFIERS_DESC: &str =
+    "Send a test new email notification through every notifier and exit, no accounts need to be configured";
+const TEST_ALL_EVENTS_DESC: &str =
+    "With --test-notifier or --test-notifiers, also send a test notification of every other kind, such as errors";
+const DRY_RUN_DESC: &str =
+    "Poll as usual but only log the notifications instead of sending them to the notifiers";
+const FOREGROUND_DESC: &str =
+    "Also write the log to stdout, use `--foreground=false` to only log to file";
+const CHECK_ONCE_DESC: &str =
+    "Poll the accounts a single time, wait for the notifications to be sent and exit";
+const LIST_ACCOUNTS_DESC: &str =
+    "Print the configured accounts with their backend and login state and exit";
+const EXPORT_ACCOUNTS_DESC: &str =
+    "Write the accounts and their sessions to an encrypted file, the key to import it is printed";
+const IMPORT_ACCOUNTS_DESC: &str =
+    "Restore the accounts of a file written by `--export-accounts`, the observer must have no accounts";
+const STATUS_DESC: &str =
+    "Print the login state, last successful poll, last error and next poll of every account and exit";
+const OUTPUT_DESC: &str = "Format of the output of `--list-accounts` and `--status`";
+const VALIDATE_CONFIG_DESC: &str =
+    "Check the config file, notifiers, secret store and account backends and exit, without polling or modifying the accounts";
+const PRINT_EXAMPLE_CONFIG_DESC: &str =
+    "Print a commented example config file with every supported setting and exit";
+const HISTORY_DESC: &str = "Print the history of delivered notifications and exit";
+const LOG_LEVEL_DESC: &str = "Level of the log messages: off, error, warn, info, debug or trace";
+const LOG_FORMAT_DESC: &str =
+    "Format of the log messages: text, or json to write one JSON object per line";
+const ACCEPT_PLAIN_SECRETS_DESC: &str =
+    "Consent to the risks of the Plain secrets storage, same as `accept_plain_secrets_insecure=true` in the config file";
 
 #[derive(Debug, Parser)]
 #[command(author, version, about, long_about = None)]
@@ -71,12 +149,90 @@ struct Options {
     configure_accounts: bool,
     #[arg(long, help=DELETE_ACCOUNTS_DESC)]
     delete_accounts: bool,
+    #[arg(long, value_name = "EMAIL", num_args = 0..=1, action = clap::ArgAction::Append, conflicts_with = "delete_accounts", help=REMOVE_ACCOUNT_DESC)]
+    remove_account: Option<Vec<String>>,
     #[arg(long, help=CREATE_CONFIG_DESC)]
     create_config: bool,
+    #[arg(long, help=PRINT_EXAMPLE_CONFIG_DESC)]
+    print_example_config: bool,
+    #[arg(long, env = "YHM_ACCEPT_PLAIN_SECRETS", help=ACCEPT_PLAIN_SECRETS_DESC)]
+    accept_plain_secrets: bool,
+    #[arg(long, help=VERIFY_SECRETS_DESC)]
+    verify_secrets: bool,
+    #[arg(long, conflicts_with = "verify_secrets", help=ROTATE_KEY_DESC)]
+    rotate_key: bool,
+    #[arg(long, help=VALIDATE_CONFIG_DESC)]
+    validate_config: bool,
+    #[arg(long, value_name = "NAME", help=TEST_NOTIFIER_DESC)]
+    test_notifier: Option<String>,
+    #[arg(long, conflicts_with = "test_notifier", help=TEST_NOTIFIERS_DESC)]
+    test_notifiers: bool,
+    #[arg(long, help=TEST_ALL_EVENTS_DESC)]
+    test_all_events: bool,
+    #[arg(long, help=HISTORY_DESC)]
+    history: bool,
+    #[arg(long, help=CHECK_ONCE_DESC)]
+    check_once: bool,
+    #[arg(long, help=LIST_ACCOUNTS_DESC)]
+    list_accounts: bool,
+    #[arg(long, conflicts_with = "list_accounts", help=STATUS_DESC)]
+    status: bool,
+    #[arg(long, value_name = "FILE", value_hint = clap::ValueHint::FilePath, help=EXPORT_ACCOUNTS_DESC)]
+    export_accounts: Option<PathBuf>,
+    #[arg(long, value_name = "FILE", value_hint = clap::ValueHint::FilePath, conflicts_with = "export_accounts", help=IMPORT_ACCOUNTS_DESC)]
+    import_accounts: Option<PathBuf>,
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text, help=OUTPUT_DESC)]
+    output: OutputFormat,
+    #[arg(long, env = "YHM_DRY_RUN", num_args = 0..=1, default_missing_value = "true", help=DRY_RUN_DESC)]
+    dry_run: Option<bool>,
+    #[arg(long, env = "YHM_FOREGROUND", num_args = 0..=1, default_missing_value = "true", help=FOREGROUND_DESC)]
+    foreground: Option<bool>,
+    #[arg(long, env = "YHM_LOG_LEVEL", help=LOG_LEVEL_DESC)]
+    log_level: Option<LevelFilter>,
+    #[arg(long, value_enum, env = "YHM_LOG_FORMAT", help=LOG_FORMAT_DESC)]
+    log_format: Option<LogFormat>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+/// Entry of `--status --output json`, times are unix timestamps in seconds.
+#[derive(Serialize)]
+struct AccountStatus {
+    email: String,
+    backend: String,
+    logged_in: bool,
+    last_success: Option<u64>,
+    last_error: Option<String>,
+    last_error_at: Option<u64>,
+    next_poll: Option<u64>,
+}
+
+/// Circuit breaker of a notifier in `--status --output json`.
+#[derive(Serialize)]
+struct NotifierStatus {
+    name: String,
+    breaker: BreakerState,
+    paused_until: Option<u64>,
+}
+
+/// Output of `--status --output json`.
+#[derive(Serialize)]
+struct Status {
+    accounts: Vec<AccountStatus>,
+    notifiers: Vec<NotifierStatus>,
 }
 
 fn main() -> Result<(), anyhow::Error> {
     let options = Options::parse();
+    if options.print_example_config {
+        print!("{EXAMPLE_CONFIG}");
+        return Ok(());
+    }
+
     let config_dir = if let Some(cfg_dir) = options.config_dir {
         if !cfg_dir.is_dir() {
             return Err(anyhow!("Supplied config directory is not a directory"));
@@ -95,136 +251,341 @@ fn main() -> Result<(), anyhow::Error> {
         get_default_log_dir()?
     };
 
-    std::fs::create_dir_all(&config_dir)
+    create_dir_user_only(&config_dir)
         .map_err(|e| anyhow!("Failed to create config dir '{config_dir:?}': {e}"))?;
     std::fs::create_dir_all(&log_dir)
         .map_err(|e| anyhow!("Failed to create log dir '{log_dir:?}': {e}"))?;
 
-    logging::init_log(&log_dir)?;
+    // Command line arguments and environment variables take precedence over the config file.
+    let mut log_settings = LogSettings::default();
+    let log_level = options.log_level.or_else(logging::rust_log_level);
+    if let Some(level) = log_level {
+        log_settings.level = level;
+    }
+    if let Some(format) = options.log_format {
+        log_settings.format = format;
+    }
+    // Keep stdout for the account list, unless asked otherwise.
+    if options.list_accounts || options.status {
+        log_settings.console = false;
+    }
+    if let Some(foreground) = options.foreground {
+        log_settings.console = foreground;
+    }
+    let logging = logging::init_log(&log_dir, &log_settings)?;
 
     debug!("-------------------------------------------------------------------------------------");
     info!("Starting You Have Mail CLI");
 
-    let config = load_config(&config_dir, options.create_config)?;
+    let mut config = load_config(&config_dir, options.create_config)?;
+    check_user_only_permissions(&config_dir, config.strict_permissions)?;
+
+    let config_log_level = match (log_level, &config.log_level) {
+        (None, Some(level)) => Some(
+            level
+                .parse::<LevelFilter>()
+                .map_err(|_| anyhow!("Invalid log_level '{level}' in config file"))?,
+        ),
+        _ => None,
+    };
+    let config_foreground = match (options.foreground, config.foreground) {
+        (None, Some(foreground)) if !options.list_accounts && !options.status => Some(foreground),
+        _ => None,
+    };
+    let config_log_format = match (options.log_format, config.log_format) {
+        (None, Some(format)) => Some(format),
+        _ => None,
+    };
+    let config_log_rotation = config
+        .log_rotation
+        .as_deref()
+        .map(str::parse::<LogRotation>)
+        .transpose()?;
+    if config_log_level.is_some()
+        || config_foreground.is_some()
+        || config_log_format.is_some()
+        || config_log_rotation.is_some()
+        || config.log_max_files.is_some()
+    {
+        log_settings.level = config_log_level.unwrap_or(log_settings.level);
+        log_settings.console = config_foreground.unwrap_or(log_settings.console);
+        log_settings.format = config_log_format.unwrap_or(log_settings.format);
+        log_settings.rotation = config_log_rotation.unwrap_or(log_settings.rotation);
+        log_settings.max_files = config.log_max_files.unwrap_or(log_settings.max_files);
+        logging.reconfigure(&log_settings)?;
+    }
+    logging.prune_log_files(log_settings.max_files);
+
+    let dry_run = options.dry_run.unwrap_or(config.dry_run);
+    if dry_run {
+        info!("Dry run, notifications are only logged");
+    }
 
     debug!("Secret store = {:?}", config.secrets);
 
-    if config.secrets == SecretsType::Plain && !config.accept_plain_secrets_insecure {
-        let msg = "Plain unencrypted secrets storage, please consent to the risks by setting `accept_plain_secrets_insecure=true` in your config file";
+    if config.secrets == SecretsType::Plain
+        && !(config.accept_plain_secrets_insecure || options.accept_plain_secrets)
+    {
+        let msg = "Plain unencrypted secrets storage, please consent to the risks by setting `accept_plain_secrets_insecure=true` in your config file or with `--accept-plain-secrets`";
         error!("{msg}");
         return Err(anyhow!(msg));
     }
 
-    if !config.has_notifiers() {
+    if options.history {
+        return history::print(&config_dir);
+    }
+
+    if options.verify_secrets {
+        return verify_secrets(config.secrets, &config_dir, &config.secrets_options()).map_err(
+            |e| {
+                error!("{e}");
+                eprintln!("FAILED: {e}");
+                e
+            },
+        );
+    }
+
+    if options.rotate_key {
+        return rotate_key(config.secrets, &config_dir, &config.secrets_options()).map_err(|e| {
+            error!("{e}");
+            eprintln!("FAILED: {e}");
+            e
+        });
+    }
+
+    if options.validate_config {
+        return validate_config(&mut config, &config_dir).map_err(|e| {
+            error!("{e}");
+            eprintln!("FAILED: {e}");
+            e
+        });
+    }
+
+    if !config.has_notifiers() && !options.list_accounts && !options.status {
         let msg = "No notifiers specified";
         error!("{msg}");
         return Err(anyhow!(msg));
     }
 
-    let notifier = {
-        let mut builder = NotifierMultiplexerBuilder::new();
-        if config.stdout_notifier {
-            builder = builder.with_notifier(notifiers::new_stdout_notifier());
-        }
+    let redaction = Redaction::new(
+        config.redact,
+        config
+            .account
+            .iter()
+            .flatten()
+            .filter(|a| a.redact)
+            .map(|a| a.email.clone())
+            .collect(),
+    );
 
-        #[cfg(feature = "notifier-ntfy")]
-        {
-            if let Some(unified_pushers) = config.ntfy {
-                for cfg in unified_pushers {
-                    info!("Adding ntfy Notifier: name={}, url={}", cfg.name, cfg.url);
-                    let notifier = cfg
-                        .into_notifier()
-                        .map_err(|e| anyhow!("Failed to create ntfy notifier: {e}"))?;
-                    builder = builder.with_notifier(notifier);
+    #[cfg(unix)]
+    {
+        let redaction = redaction.clone();
+        let mut signals = signal_hook::iterator::Signals::new([signal_hook::consts::SIGUSR1])
+            .map_err(|e| anyhow!("Failed to install SIGUSR1 handler: {e}"))?;
+        std::thread::Builder::new()
+            .name("redaction-signal".to_string())
+            .spawn(move || {
+                for _ in signals.forever() {
+                    if redaction.toggle() {
+                        info!("Received SIGUSR1, email details are now hidden");
+                    } else {
+                        info!("Received SIGUSR1, email details are now shown");
+                    }
                 }
-            }
-        }
+            })
+            .map_err(|e| anyhow!("Failed to spawn signal thread: {e}"))?;
+    }
 
-        Arc::new(builder.build())
+    let notifier_ctx = NotifierContext {
+        ca_bundle: config.ca_bundle.clone(),
+        config_dir: config_dir.clone(),
+        history: config.history.then(|| {
+            Arc::new(History::open(
+                &config_dir,
+                config.history_max_entries,
+                config.history_max_days,
+            ))
+        }),
+        seen_emails: Some(Arc::new(SeenEmails::open(&config_dir))),
+        ..Default::default()
     };
 
-    let mut secret_store = new_secrets(config.secrets, &config_dir).map_err(|e| {
-        error!("{e}");
-        e
-    })?;
-    let encryption_key = get_or_create_secret_key(secret_store.as_mut())?;
-
-    let config_file_path = get_config_file_path(&config_dir);
-
-    let observer_config = match encryption_key {
-        GetSecretKeyState::New(key) => {
-            if config_file_path.exists() {
-                warn!("Existing config file detected but we got a new encryption key, previous state will be lost");
-            }
-            you_have_mail_common::Config::new(
-                key,
-                config_file_path,
-                Duration::from_secs(config.poll_interval),
-            )
-            .map_err(|e| {
+    if let Some(name) = &options.test_notifier {
+        return test_notifier(&mut config, name, &notifier_ctx, options.test_all_events).map_err(
+            |e| {
                 error!("{e}");
+                eprintln!("FAILED: {e}");
                 e
-            })?
-        }
-        GetSecretKeyState::Existing(key) => {
-            you_have_mail_common::Config::create_or_load(key, config_file_path).map_err(|e| {
-                error!("{e}");
-                e
-            })?
-        }
-    };
+            },
+        );
+    }
 
-    let null_backend = you_have_mail_common::backend::null::new_backend(&[
-        you_have_mail_common::backend::null::NullTestAccount {
-            email: "foo".to_string(),
-            password: "foo".to_string(),
-            totp: None,
-            wait_time: None,
-            refresh: false,
-        },
-    ]);
-
-    let mut observer = {
-        ObserverBuilder::new(notifier, observer_config)
-            .default_backends()
-            .with_backend(null_backend)
-            .load_from_config()
-            .map_err(|e| {
+    if options.test_notifiers {
+        let names = config
+            .notifier_names()
+            .into_iter()
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        return test_notifiers(&config_dir, &names, &notifier_ctx, options.test_all_events);
+    }
+
+    let notifier = Arc::new(ReloadableNotifier::new(
+        notifiers::new_notifier(&mut config, &notifier_ctx, redaction.clone(), dry_run).map_err(
+            |e| {
                 error!("{e}");
                 e
-            })?
-    };
+            },
+        )?,
+    ));
 
-    observer
-        .set_poll_interval(Duration::from_secs(config.poll_interval))
-        .map_err(|e| anyhow!("Failed to set poll interval on observer: {e}"))?;
+    let mut secret_store = new_secrets(config.secrets, &config_dir, &config.secrets_options())
+        .map_err(|e| {
+            error!("{e}");
+            e
+        })?;
+    let encryption_key = get_or_create_secret_key(secret_store.as_mut())
+        .and_then(|state| {
+            if let GetSecretKeyState::New(key) = &state {
+                self_test(config.secrets, secret_store.as_mut(), key.expose_secret())?;
+            }
+            Ok(state)
+        })
+        .map_err(|e| {
+            let mut msg = format!("{:?} secret store self-test failed: {e}", config.secrets);
+            if let Some(hint) = failure_hint(config.secrets) {
+                msg = format!("{msg}\n{hint}");
+            }
+            error!("{msg}");
+            eprintln!("FAILED: {msg}");
+            anyhow!(msg)
+        })?;
+    if let GetSecretKeyState::Existing(_) = &encryption_key {
+        info!(
+            "{:?} secret store self-test passed, found the encryption key",
+            config.secrets
+        );
+    }
+
+    if let GetSecretKeyState::New(_) = &encryption_key {
+        totp::discard_secrets(&config_dir)?;
+    }
+    let mut accounts = Accounts::load(&config_dir, notifier.clone(), encryption_key, &config)
+        .map_err(|e| {
+            error!("{e}");
+            e
+        })?;
+
+    let backends = accounts::backends();
+    let unknown_backends = config
+        .enabled_backends
+        .iter()
+        .filter(|name| {
+            !backends
+                .iter()
+                .any(|backend| backend.name() == name.as_str())
+        })
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    if !unknown_backends.is_empty() {
+        let msg = format!(
+            "Unknown backend(s) in enabled_backends: {}",
+            unknown_backends.join(", ")
+        );
+        error!("{msg}");
+        return Err(anyhow!(msg));
+    }
+
+    accounts.configure(&config)?;
+
+    if options.list_accounts {
+        return list_accounts(&accounts, options.output);
+    }
+
+    if options.status {
+        return print_status(&accounts, &StateFile::load(&config_dir), options.output);
+    }
+
+    if let Some(path) = &options.export_accounts {
+        return export_accounts(&accounts, secret_store.as_mut(), path).map_err(|e| {
+            error!("{e}");
+            e
+        });
+    }
+
+    if let Some(path) = &options.import_accounts {
+        let mut state = StateFile::load(&config_dir);
+        import_accounts(
+            accounts,
+            secret_store.as_mut(),
+            &config_dir,
+            path,
+            &mut state,
+        )
+        .map_err(|e| {
+            error!("{e}");
+            e
+        })?;
+        return state.save();
+    }
+
+    if let Some(emails) = options.remove_account {
+        let mut state = StateFile::load(&config_dir);
+        let mut totp_secrets = TotpSecrets::load(&config_dir, accounts.key())?;
+        remove_accounts(&mut accounts, emails, &mut state, &mut totp_secrets)?;
+        totp_secrets.save()?;
+        return state.save();
+    }
 
     if options.delete_accounts {
-        delete_accounts(&mut observer, config.account)?;
-        return Ok(());
+        let mut state = StateFile::load(&config_dir);
+        let mut totp_secrets = TotpSecrets::load(&config_dir, accounts.key())?;
+        delete_accounts(&mut accounts, config.account, &mut state, &mut totp_secrets)?;
+        totp_secrets.save()?;
+        return state.save();
     }
 
     if options.configure_accounts {
-        if let Some(accounts) = config.account {
-            configure_accounts(&mut observer, accounts)?
+        if let Some(cfg_accounts) = config.account {
+            let mut state = StateFile::load(&config_dir);
+            let mut totp_secrets = TotpSecrets::load(&config_dir, accounts.key())?;
+            let result =
+                configure_accounts(&mut accounts, cfg_accounts, &mut state, &mut totp_secrets);
+            // The secrets of the accounts configured before a failure are kept.
+            totp_secrets.save()?;
+            result?;
+            state.save()?;
         }
         return Ok(());
     }
 
-    if observer.is_empty() {
-        if let Some(accounts) = &config.account {
-            if !accounts.is_empty() {
-                warn!("No accounts in observer, but found {} account(s) in config file, use --configure-accounts to configure them.", accounts.len());
+    let disabled = accounts
+        .skip_backends(|backend| config.backend_enabled(backend))
+        .into_iter()
+        .map(|account| format!("{} ({})", account.email, account.backend))
+        .collect::<Vec<_>>();
+    if !disabled.is_empty() {
+        warn!(
+            "Not polling accounts whose backend is not in enabled_backends, or which share their state with such an account: {}, enable the backends or remove the accounts with --remove-account",
+            disabled.join(", ")
+        );
+    }
+
+    let mut state = StateFile::load(&config_dir);
+    if accounts.is_empty() {
+        if let Some(cfg_accounts) = &config.account {
+            if !cfg_accounts.is_empty() {
+                warn!("No accounts in observer, but found {} account(s) in config file, use --configure-accounts to configure them.", cfg_accounts.len());
             }
         }
     } else {
         info!("Detected the following accounts:");
-        for (email, account) in observer.accounts() {
+        for account in accounts.iter() {
             info!(
                 "  {} ({}) State: {}",
-                email,
-                account.backend().name(),
-                if account.is_logged_in() {
+                account.email,
+                account.backend,
+                if account.logged_in {
                     "Logged In"
                 } else {
                     "Logged Out/Session Expired"
@@ -232,88 +593,1264 @@ fn main() -> Result<(), anyhow::Error> {
             )
         }
 
-        if let Some(accounts) = &config.account {
-            for account in accounts {
-                if observer.get_account(&account.email).is_none() {
+        if let Some(cfg_accounts) = &config.account {
+            for account in supported_accounts(&accounts, cfg_accounts) {
+                if accounts.get(&account.email).is_none() {
                     warn!("Account {} is in config file, but not configured. Use --configure-accounts to configure.", account.email);
+                } else if state.proxy_changed(&account.email, account.proxy.as_deref()) {
+                    warn!("The proxy of account {} changed in the config file, it still connects through the previous one. Use --configure-accounts to log in again with the new proxy.", account.email);
                 }
             }
         }
     }
 
     info!(
-        "Poll interval {} seconds",
-        observer.get_poll_interval().as_secs()
+        "Poll interval {} seconds, at most {} account(s) polled at the same time",
+        accounts.poll_interval().as_secs(),
+        config.max_concurrent_polls
     );
 
     info!("Starting observer loop - Ctrl+C to Quit");
     let (signal_sender, signal_receiver) = crossbeam_channel::bounded::<()>(0);
+    let shutdown = notifier_ctx.shutdown.clone();
     ctrlc::set_handler(move || {
         info!("Received CtrlC signal");
+        shutdown.request();
         signal_sender.send(()).expect("failed to send signal");
     })
     .expect("Failed to install ctrl+c handler");
 
-    let timer = crossbeam_channel::tick(observer.get_poll_interval());
+    #[cfg(unix)]
+    let reload_receiver = {
+        let (reload_sender, reload_receiver) = crossbeam_channel::bounded::<()>(1);
+        let mut signals = signal_hook::iterator::Signals::new([signal_hook::consts::SIGHUP])
+            .map_err(|e| anyhow!("Failed to install SIGHUP handler: {e}"))?;
+        std::thread::Builder::new()
+            .name("reload-signal".to_string())
+            .spawn(move || {
+                for _ in signals.forever() {
+                    info!("Received SIGHUP, reloading config");
+                    // A reload is already pending if the channel is full.
+                    let _ = reload_sender.try_send(());
+                }
+            })
+            .map_err(|e| anyhow!("Failed to spawn signal thread: {e}"))?;
+        reload_receiver
+    };
+    #[cfg(not(unix))]
+    let reload_receiver = crossbeam_channel::never::<()>();
+
+    let mut poll_interval = accounts.poll_interval();
+    let mut disk_full_throttle = Throttle::new(DISK_FULL_NOTIFICATION_INTERVAL);
+    let mut disk_full = false;
+    let mut jitter = Jitter::new(Duration::from_secs(config.poll_jitter_secs));
+    #[cfg(feature = "healthcheck")]
+    let health = config
+        .health_listen
+        .as_deref()
+        .map(|address| health::serve(address, poll_interval, config.health_max_missed_polls))
+        .transpose()?;
+    #[cfg(feature = "metrics")]
+    if let Some(address) = &config.metrics_listen {
+        metrics::serve(address, notifier_ctx.stats.clone())?;
+    }
+    let mut standby = config.standby.map(Standby::new);
+    let mut connectivity = config
+        .connectivity_check
+        .map(|c| Connectivity::new(c, config.backoff, poll_interval));
+
+    // Don't poll an account right away if the previous run polled it recently, unless it's a
+    // one-shot run in which case whoever invokes us decides on the interval.
+    if !options.check_once {
+        if let Some(delay) = accounts.resume(&state) {
+            info!(
+                "Previous poll was recent, next poll in {} seconds",
+                delay.as_secs()
+            );
+        }
+    }
+    // Without accounts there is nothing to poll, the hooks still run every poll interval.
+    let mut idle_poll = Instant::now();
+    let totp_secrets = TotpSecrets::load(&config_dir, accounts.key())
+        .map_err(|e| warn!("{e}, the accounts are logged in again without TOTP secret"))
+        .ok();
+    // Accounts whose session expired are logged in again once, a wrong password would
+    // otherwise be sent on every poll.
+    let mut relogin_attempted = HashSet::new();
 
     loop {
-        observer.poll().expect("Failed to poll");
-        select! {
-            recv(timer) -> _ => continue,
-            recv(signal_receiver) -> _ =>  {
-                info!("Exiting");
-                return Ok(());
+        let poll_command_timeout = Duration::from_secs(config.poll_command_timeout);
+        let mut check_once_result = Ok(());
+        let mut should_poll = if accounts.is_empty() {
+            Instant::now() >= idle_poll
+        } else {
+            accounts.is_due()
+        };
+        // Delay of the due polls if they are skipped.
+        let mut skip_delay = jitter.apply(poll_interval);
+
+        if let (true, Some(standby)) = (should_poll, &mut standby) {
+            should_poll = standby.should_poll();
+        }
+
+        if let (true, Some(connectivity)) = (should_poll, &mut connectivity) {
+            match connectivity.check() {
+                ConnectivityState::Online { reconnected } => {
+                    if reconnected && connectivity.notify_online() {
+                        notifier.current().notify_event(Event::Info {
+                            title: "Back online".to_string(),
+                            message: Some(
+                                "Network is available again, resuming polling".to_string(),
+                            ),
+                        });
+                    }
+                }
+                ConnectivityState::Offline { retry_in } => {
+                    should_poll = false;
+                    skip_delay = retry_in;
+                    check_once_result = Err(anyhow!("Network is unavailable, could not poll"));
+                }
+            }
+        }
+
+        if let (true, Some(command)) = (should_poll, &config.pre_poll_command) {
+            if !hooks::run_hook("pre poll", command, poll_command_timeout)
+                && config.skip_poll_on_pre_command_failure
+            {
+                warn!("Skipping poll, pre poll command failed");
+                should_poll = false;
+            }
+        }
+
+        if !should_poll {
+            accounts.postpone(skip_delay);
+            if Instant::now() >= idle_poll {
+                idle_poll = Instant::now() + skip_delay;
+            }
+        } else {
+            let poll_timeout = (config.poll_timeout_secs != 0)
+                .then(|| Duration::from_secs(config.poll_timeout_secs));
+            let outcomes = accounts.poll(config.max_concurrent_polls, poll_timeout)?;
+            notifier.current().end_poll();
+            let mut account_errors = notifier_ctx.stats.take_account_errors();
+            let mut poll_ok = true;
+            let mut state_saved = true;
+            let mut logged_out = Vec::new();
+            for outcome in outcomes {
+                let emails = outcome
+                    .accounts
+                    .iter()
+                    .map(|a| a.email.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                if outcome.duration > accounts.groups()[outcome.group].interval() {
+                    warn!(
+                        "Poll of {emails} took {} seconds, longer than the poll interval",
+                        outcome.duration.as_secs()
+                    );
+                } else {
+                    debug!("Poll of {emails} took {} ms", outcome.duration.as_millis());
+                }
+                let result = match outcome.result {
+                    PollResult::Completed(result) => result,
+                    PollResult::TimedOut => {
+                        // The observer is still busy, its accounts are skipped until the poll
+                        // completes and they keep their login state meanwhile.
+                        for account in &outcome.accounts {
+                            let error = format!(
+                                "Poll has not completed after {} seconds, the server may have stopped responding, skipping the account until it completes",
+                                outcome.duration.as_secs()
+                            );
+                            error!("{}: {error}", account.email);
+                            state.record_account_poll(&account.email, Some(error.clone()));
+                            notifier.current().notify_event(Event::AccountError {
+                                account: account.email.clone(),
+                                error,
+                                escalated: false,
+                            });
+                        }
+                        poll_ok = false;
+                        check_once_result = Err(anyhow!("Poll of {emails} timed out"));
+                        continue;
+                    }
+                    PollResult::Panicked => {
+                        let (failures, retry_in) = accounts.record_failure(outcome.group);
+                        error!(
+                            "Poll of {emails} panicked ({failures} consecutive failure(s)), retrying in {} seconds",
+                            retry_in.as_secs()
+                        );
+                        poll_ok = false;
+                        check_once_result = Err(anyhow!("Poll of {emails} panicked"));
+                        continue;
+                    }
+                };
+                for account in &outcome.accounts {
+                    notifier_ctx
+                        .stats
+                        .record_login_state(&account.email, account.logged_in);
+                    if account.logged_in {
+                        relogin_attempted.remove(&account.email);
+                    } else if relogin_attempted.insert(account.email.clone()) {
+                        logged_out.push(account.email.clone());
+                    }
+                    match account_errors.remove(&account.email) {
+                        Some(error) => state.record_account_poll(&account.email, Some(error)),
+                        None if result.is_ok() && account.logged_in => {
+                            state.record_account_poll(&account.email, None);
+                            notifier_ctx.account_failures.record_success(&account.email);
+                        }
+                        None => {}
+                    }
+                }
+
+                poll_ok &= result.is_ok();
+                match result {
+                    Ok(()) => {
+                        let failures = accounts.record_success(outcome.group);
+                        if failures != 0 {
+                            info!("Poll of {emails} succeeded after {failures} failure(s), resuming the poll interval");
+                        }
+                        if config.notify_first_poll {
+                            notify_first_polls(&outcome.accounts, &notifier.current(), &mut state);
+                        }
+                    }
+                    Err(e) if is_disk_full(&e) => {
+                        // Keep running with the in-memory state, it will be saved on the next
+                        // successful poll once space is available again.
+                        error!("Failed to save observer state of {emails}, disk is full: {e}");
+                        accounts.record_success(outcome.group);
+                        disk_full = true;
+                        state_saved = false;
+                        if disk_full_throttle.ready() {
+                            notifier.notify(Notification::Error(
+                                "disk full, state not saved".to_string(),
+                            ));
+                        }
+                        check_once_result = Err(anyhow!("Failed to save observer state: {e}"));
+                    }
+                    Err(e) if options.check_once => {
+                        error!("Failed to poll {emails}: {e}");
+                        check_once_result = Err(anyhow!("Failed to poll: {e}"));
+                    }
+                    Err(e) if is_fatal_error(&e) => {
+                        error!("Failed to poll {emails}, giving up: {e}");
+                        let current = notifier.current();
+                        current.notify_event(Event::Error {
+                            error: format!("Failed to poll, exiting: {e}"),
+                        });
+                        if !current.shutdown(NOTIFIER_FLUSH_TIMEOUT) {
+                            warn!("Timed out waiting for the notifications to be sent");
+                        }
+                        return Err(anyhow!("Failed to poll: {e}"));
+                    }
+                    Err(e) => {
+                        let (failures, retry_in) = accounts.record_failure(outcome.group);
+                        error!(
+                            "Failed to poll {emails} ({failures} consecutive failure(s)), retrying in {} seconds: {e}",
+                            retry_in.as_secs()
+                        );
+                    }
+                }
+            }
+            if let (false, Some(cfg_accounts)) = (logged_out.is_empty(), &config.account) {
+                relogin_accounts(
+                    &mut accounts,
+                    cfg_accounts,
+                    totp_secrets.as_ref(),
+                    &logged_out,
+                    &notifier.current(),
+                    &mut state,
+                );
+            }
+            notifier_ctx.stats.record_poll(poll_ok);
+            state.record_poll();
+            state.record_breakers(notifier_ctx.stats.breakers());
+            if let Err(e) = state.save() {
+                warn!("{e}");
+            }
+
+            #[cfg(feature = "healthcheck")]
+            if let (true, Some(health)) = (poll_ok, &health) {
+                health.record_poll();
+            }
+            if disk_full && state_saved {
+                info!("Observer state is being saved again");
+                disk_full = false;
+                disk_full_throttle.reset();
+            }
+            if accounts.is_empty() {
+                idle_poll = Instant::now() + jitter.apply(poll_interval);
+            }
+
+            if let Some(command) = &config.post_poll_command {
+                hooks::run_hook("post poll", command, poll_command_timeout);
+            }
+        }
+
+        if options.check_once {
+            if !notifier.current().shutdown(NOTIFIER_FLUSH_TIMEOUT) {
+                warn!("Timed out waiting for the notifications to be sent");
+            }
+            info!("Exiting after a single poll");
+            return check_once_result;
+        }
+
+        // Wait in short steps, the timer doesn't advance while the system is suspended.
+        let deadline = accounts.next_poll().unwrap_or(idle_poll);
+        let mut suspend = SuspendDetector::new();
+        loop {
+            let step = deadline
+                .saturating_duration_since(Instant::now())
+                .min(SUSPEND_CHECK_INTERVAL);
+            select! {
+                recv(crossbeam_channel::after(step)) -> _ => {
+                    if let Some(suspended) = suspend.check() {
+                        info!(
+                            "System resumed after being suspended for {} seconds, polling now",
+                            suspended.as_secs()
+                        );
+                        accounts.poll_all_now();
+                        idle_poll = Instant::now();
+                        break;
+                    }
+                    if Instant::now() >= deadline {
+                        break;
+                    }
+                },
+                recv(reload_receiver) -> _ => {
+                    match reload_config(
+                        &config_dir,
+                        &notifier,
+                        &notifier_ctx,
+                        &redaction,
+                        options.dry_run,
+                        &mut accounts,
+                    ) {
+                        Ok(new_config) => {
+                            config = new_config;
+                            poll_interval = accounts.poll_interval();
+                            jitter = Jitter::new(Duration::from_secs(config.poll_jitter_secs));
+                            #[cfg(feature = "healthcheck")]
+                            if let Some(health) = &health {
+                                health.set_poll_interval(poll_interval);
+                            }
+                        }
+                        Err(e) => {
+                            error!("Failed to reload config, keeping the current one: {e}");
+                            notifier.current().notify_event(Event::ConfigError {
+                                error: format!("Failed to reload config: {e}"),
+                            });
+                        }
+                    }
+                    // Keep the current schedule, unless the new interval is shorter.
+                    idle_poll = idle_poll.min(Instant::now() + poll_interval);
+                    break;
+                },
+                recv(signal_receiver) -> _ =>  {
+                    report_session_summary(
+                        &notifier.current(),
+                        &notifier_ctx,
+                        config.notify_session_summary,
+                    );
+                    if !notifier.current().shutdown(NOTIFIER_FLUSH_TIMEOUT) {
+                        warn!("Timed out waiting for the notifiers to send their notifications");
+                    }
+                    info!("Exiting");
+                    return Ok(());
+                },
+            }
+        }
+    }
+}
+
+/// Re-read the config file and apply the settings which can be changed while running: the poll
+/// intervals, the notifiers and how notifications are filtered and grouped, and the poll hooks.
+/// Returns the new config, the current one is kept if the new one can't be applied.
+fn reload_config(
+    config_dir: &Path,
+    notifier: &ReloadableNotifier,
+    ctx: &NotifierContext,
+    redaction: &Redaction,
+    dry_run: Option<bool>,
+    accounts: &mut Accounts,
+) -> anyhow::Result<cfg::Config> {
+    let mut config = load_config(config_dir, false)?;
+    if !config.has_notifiers() {
+        return Err(anyhow!("No notifiers specified"));
+    }
+
+    // The notifier configurations are moved out of `config` by `new_notifier`.
+    let notifier_names = config.notifier_names().join(", ");
+    // The redaction state is kept as it may have been toggled with SIGUSR1.
+    let dry_run = dry_run.unwrap_or(config.dry_run);
+    let new_notifier = notifiers::new_notifier(&mut config, ctx, redaction.clone(), dry_run)?;
+
+    accounts.configure(&config)?;
+    info!(
+        "Reloaded poll intervals: {} seconds, accounts may override it",
+        config.poll_interval
+    );
+
+    let previous = notifier.replace(new_notifier);
+    if !previous.shutdown(NOTIFIER_FLUSH_TIMEOUT) {
+        warn!("Timed out waiting for the previous notifiers to send their notifications");
+    }
+    info!("Reloaded notifiers: {notifier_names}");
+    info!("Reloaded notification filters, aggregation, quiet hours and poll hooks");
+    info!(
+        "Changes to the secrets, logging, history, redaction, standby, connectivity check and accounts require a restart"
+    );
+    Ok(config)
+}
+
+/// Load the encryption key and check it decrypts the observer states and the TOTP secrets.
+/// Neither the key nor the states are created if they are missing.
+fn verify_secrets(
+    secrets: SecretsType,
+    config_dir: &Path,
+    options: &SecretsOptions,
+) -> anyhow::Result<()> {
+    let mut secret_store = new_secrets(secrets, config_dir, options)?;
+    let key = secret_store
+        .load()
+        .map_err(|e| anyhow!("Failed to load encryption key from {secrets:?} store: {e}"))?
+        .ok_or_else(|| anyhow!("No encryption key found in {secrets:?} store"))?;
+
+    let state_files = accounts::state_files(config_dir)?;
+    if state_files.is_empty() {
+        println!("OK: encryption key found, there is no observer state to verify");
+    }
+
+    for path in &state_files {
+        you_have_mail_common::Config::create_or_load(accounts::copy_key(&key)?, path.clone())
+            .map_err(|e| anyhow!("Encryption key can't decrypt observer state at {path:?}: {e}"))?;
+        println!("OK: encryption key matches the observer state at {path:?}");
+    }
+
+    let totp_path = totp::secrets_path(config_dir);
+    if totp_path.exists() {
+        TotpSecrets::open(totp_path.clone(), &key)?;
+        println!("OK: encryption key matches the TOTP secrets at {totp_path:?}");
+    }
+    Ok(())
+}
+
+/// Generate a new encryption key and re-encrypt the observer states and the TOTP secrets with it.
+/// The states are only replaced once the new key is stored, so a failure at any step leaves the
+/// old key and states usable.
+fn rotate_key(
+    secrets: SecretsType,
+    config_dir: &Path,
+    options: &SecretsOptions,
+) -> anyhow::Result<()> {
+    let mut secret_store = new_secrets(secrets, config_dir, options)?;
+    let old_key = secret_store
+        .load()
+        .map_err(|e| anyhow!("Failed to load encryption key from {secrets:?} store: {e}"))?
+        .ok_or_else(|| anyhow!("No encryption key found in {secrets:?} store"))?;
+
+    let mut state_files = accounts::state_files(config_dir)?;
+    // The TOTP secrets are encrypted with the same key, they are replaced like the states.
+    let totp_path = totp::secrets_path(config_dir);
+    if totp_path.exists() {
+        state_files.push(totp_path.clone());
+    }
+    let new_key = EncryptionKey::new();
+    if state_files.is_empty() {
+        secret_store.store(new_key.expose_secret())?;
+        println!("OK: new encryption key stored, there is no observer state to re-encrypt");
+        return Ok(());
+    }
+
+    let mut rotated = Vec::with_capacity(state_files.len());
+    let remove_rotated = |rotated: &[PathBuf]| {
+        for path in rotated {
+            let _ = std::fs::remove_file(path);
+        }
+    };
+    for path in &state_files {
+        let rotated_path = path.with_extension("rotated");
+        let result = if *path == totp_path {
+            TotpSecrets::open(path.clone(), &old_key)
+                .and_then(|secrets| secrets.write_to(&rotated_path, &new_key))
+        } else {
+            reencrypt_state(path, &rotated_path, &old_key, &new_key)
+        };
+        if let Err(e) = result {
+            remove_rotated(&rotated);
+            return Err(e);
+        }
+        rotated.push(rotated_path);
+    }
+
+    if let Err(e) = secret_store.store(new_key.expose_secret()) {
+        remove_rotated(&rotated);
+        return Err(anyhow!(
+            "Failed to store new encryption key, the old key and states are unchanged: {e}"
+        ));
+    }
+
+    // Keep the old states until all of them are replaced, so they can be put back together with
+    // the old key.
+    let mut replaced = Vec::with_capacity(state_files.len());
+    let mut failure = None;
+    for (path, rotated_path) in state_files.iter().zip(&rotated) {
+        let old_path = path.with_extension("old");
+        if let Err(e) = std::fs::rename(path, &old_path) {
+            failure = Some(anyhow!("Failed to back up observer state at {path:?}: {e}"));
+            break;
+        }
+        replaced.push((path, old_path));
+        if let Err(e) = std::fs::rename(rotated_path, path) {
+            failure = Some(anyhow!("Failed to replace observer state at {path:?}: {e}"));
+            break;
+        }
+    }
+
+    if let Some(e) = failure {
+        let mut restore_errors = Vec::new();
+        for (path, old_path) in &replaced {
+            if let Err(e) = std::fs::rename(old_path, path) {
+                restore_errors.push(format!("move {old_path:?} to {path:?} ({e})"));
+            }
+        }
+        if let Err(e) = secret_store.store(old_key.expose_secret()) {
+            restore_errors.push(format!("restore the old key ({e})"));
+        }
+        if !restore_errors.is_empty() {
+            return Err(anyhow!(
+                "{e}, and failed to undo the rotation: {}",
+                restore_errors.join(", ")
+            ));
+        }
+        remove_rotated(&rotated);
+        return Err(anyhow!("{e}, the old key and states were restored"));
+    }
+
+    for (_, old_path) in &replaced {
+        let _ = std::fs::remove_file(old_path);
+    }
+    info!("Rotated encryption key");
+    for path in &state_files {
+        if *path == totp_path {
+            println!("OK: TOTP secrets at {path:?} re-encrypted");
+        } else {
+            println!("OK: observer state at {path:?} re-encrypted");
+        }
+    }
+    println!("OK: new encryption key stored");
+    Ok(())
+}
+
+/// Write the observer state at `path` encrypted with `new_key` to `rotated_path`, and check the
+/// copy can be loaded.
+fn reencrypt_state(
+    path: &Path,
+    rotated_path: &Path,
+    old_key: &Secret<EncryptionKey>,
+    new_key: &Secret<EncryptionKey>,
+) -> anyhow::Result<()> {
+    let encrypted = std::fs::read(path)
+        .map_err(|e| anyhow!("Failed to read observer state at {path:?}: {e}"))?;
+    let state = old_key
+        .expose_secret()
+        .decrypt(&encrypted)
+        .map_err(|e| anyhow!("Encryption key can't decrypt observer state at {path:?}: {e}"))?;
+    let reencrypted = new_key
+        .expose_secret()
+        .encrypt(&state)
+        .map_err(|e| anyhow!("Failed to encrypt observer state: {e}"))?;
+
+    write_user_file(rotated_path, &reencrypted)
+        .map_err(|e| anyhow!("Failed to write re-encrypted state to {rotated_path:?}: {e}"))?;
+    // Loading the copy checks the state is intact before anything is replaced.
+    if let Err(e) = you_have_mail_common::Config::create_or_load(
+        accounts::copy_key(new_key)?,
+        rotated_path.to_path_buf(),
+    ) {
+        let _ = std::fs::remove_file(rotated_path);
+        return Err(anyhow!(
+            "Re-encrypted observer state of {path:?} can't be loaded: {e}"
+        ));
+    }
+    Ok(())
+}
+
+/// Build the notifiers, load the encryption key and check the account backends exist, printing
+/// the result of each check. Nothing is created or modified.
+fn validate_config(config: &mut cfg::Config, config_dir: &Path) -> anyhow::Result<()> {
+    let mut failures = 0;
+    let mut report = |check: &str, result: anyhow::Result<String>| match result {
+        Ok(summary) => println!("OK: {check}: {summary}"),
+        Err(e) => {
+            failures += 1;
+            println!("FAILED: {check}: {e}");
+        }
+    };
+
+    let accounts = config.account.take().unwrap_or_default();
+    report("config", Ok(format!("{} account(s)", accounts.len())));
+
+    let ctx = NotifierContext {
+        ca_bundle: config.ca_bundle.clone(),
+        config_dir: config_dir.to_path_buf(),
+        ..Default::default()
+    };
+    let names = config.notifier_names().join(", ");
+    let notifier = if config.has_notifiers() {
+        notifiers::new_notifier(config, &ctx, Redaction::default(), true)
+    } else {
+        Err(anyhow!("No notifiers specified"))
+    };
+    let notifier = match notifier {
+        Ok(notifier) => {
+            report("notifiers", Ok(names));
+            notifier
+        }
+        Err(e) => {
+            report("notifiers", Err(e));
+            NotifierMultiplexerBuilder::new().build()?
+        }
+    };
+    notifier.shutdown(NOTIFIER_FLUSH_TIMEOUT);
+
+    let key = new_secrets(config.secrets, config_dir, &config.secrets_options())
+        .and_then(|mut store| store.load());
+    let key = match key {
+        Ok(Some(key)) => {
+            report(
+                "secrets",
+                Ok(format!("{:?} store has an encryption key", config.secrets)),
+            );
+            Some(key)
+        }
+        Ok(None) => {
+            report(
+                "secrets",
+                Ok(format!(
+                    "{:?} store has no encryption key yet, it is created on the first run",
+                    config.secrets
+                )),
+            );
+            None
+        }
+        Err(e) => {
+            report("secrets", Err(e));
+            None
+        }
+    };
+
+    let has_state = accounts::state_files(config_dir).map_or(true, |files| !files.is_empty());
+    match key {
+        Some(key) if has_state => {
+            let observers = Accounts::load(
+                config_dir,
+                Arc::new(notifier),
+                GetSecretKeyState::Existing(key),
+                config,
+            );
+            let result = observers.and_then(|observers| {
+                let unknown = accounts
+                    .iter()
+                    .filter(|a| observers.backend_by_name(&a.backend).is_none())
+                    .map(|a| format!("{} ({})", a.email, a.backend))
+                    .collect::<Vec<_>>();
+                if unknown.is_empty() {
+                    Ok("all account backends are available".to_string())
+                } else {
+                    Err(anyhow!("unknown backend for {}", unknown.join(", ")))
+                }
+            });
+            report("backends", result);
+        }
+        _ => report(
+            "backends",
+            Ok("skipped, there is no observer state yet".to_string()),
+        ),
+    }
+
+    if failures != 0 {
+        return Err(anyhow!("{failures} check(s) failed"));
+    }
+    println!("Config is valid");
+    Ok(())
+}
+
+/// Send a test new email notification, and with `all_events` one of every other kind, through
+/// the notifier called `name` only.
+fn test_notifier(
+    config: &mut cfg::Config,
+    name: &str,
+    ctx: &NotifierContext,
+    all_events: bool,
+) -> anyhow::Result<()> {
+    if !config.retain_notifier(name) {
+        return Err(anyhow!(
+            "No notifier named '{name}', configured notifiers: {}",
+            config.notifier_names().join(", ")
+        ));
+    }
+    config.disable_notification_rules();
+    // Repeated tests send the same email.
+    let ctx = &NotifierContext {
+        seen_emails: None,
+        ..ctx.clone()
+    };
+
+    let (account, backend) = config
+        .account
+        .iter()
+        .flatten()
+        .next()
+        .map(|a| (a.email.clone(), a.backend.clone()))
+        .unwrap_or_else(|| ("test@example.com".to_string(), "Test".to_string()));
+    let mut events = vec![Event::NewEmail {
+        account: account.clone(),
+        backend: backend.clone(),
+        emails: vec![notifiers::Email {
+            sender: "You Have Mail CLI".to_string(),
+            subject: format!("Test notification sent to the '{name}' notifier"),
+            received_at: Local::now().timestamp(),
+            account: account.clone(),
+            backend,
+            seen_hash: None,
+            priorities: Default::default(),
+        }],
+        open_url: None,
+        redacted: false,
+    }];
+    if all_events {
+        events.extend([
+            Event::AccountLoggedOut {
+                account: account.clone(),
+                escalated: false,
+            },
+            Event::AccountError {
+                account,
+                error: "Test account error".to_string(),
+                escalated: false,
+            },
+            Event::ConfigError {
+                error: "Test config error".to_string(),
             },
+            Event::Error {
+                error: "Test error".to_string(),
+            },
+            Event::Info {
+                title: "Test notification".to_string(),
+                message: Some(format!(
+                    "Sent to the '{name}' notifier by You Have Mail CLI"
+                )),
+            },
+        ]);
+    }
+
+    let notifier = notifiers::new_notifier(config, ctx, Redaction::default(), false)?;
+    let sent = events.len() as u64;
+    for event in events {
+        notifier.notify_event(event);
+    }
+
+    let flushed = notifier.shutdown(NOTIFIER_FLUSH_TIMEOUT);
+    let delivered = ctx.stats.delivered();
+    if !flushed {
+        return Err(anyhow!(
+            "Timed out waiting for notifier '{name}' to send the test notifications, {delivered} of {sent} delivered"
+        ));
+    }
+    if delivered < sent {
+        return Err(anyhow!(
+            "Notifier '{name}' delivered {delivered} of {sent} test notifications, check the logs for details"
+        ));
+    }
+
+    println!("OK: {sent} test notification(s) delivered by '{name}'");
+    Ok(())
+}
+
+/// Send a test notification through each of the notifiers `names` in turn. The accounts of the
+/// config are ignored, so notifiers can be set up before any account is configured.
+fn test_notifiers(
+    config_dir: &Path,
+    names: &[String],
+    ctx: &NotifierContext,
+    all_events: bool,
+) -> anyhow::Result<()> {
+    let mut failed = Vec::new();
+    for name in names {
+        // Building the notifiers consumes their config, every test needs a fresh copy. The
+        // delivery count is per notifier.
+        let mut config = load_config(config_dir, false)?;
+        let ctx = NotifierContext {
+            stats: Arc::default(),
+            ..ctx.clone()
+        };
+        if let Err(e) = test_notifier(&mut config, name, &ctx, all_events) {
+            error!("{e}");
+            eprintln!("FAILED: {e}");
+            failed.push(name.as_str());
         }
     }
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "{} of {} notifier(s) failed: {}",
+            failed.len(),
+            names.len(),
+            failed.join(", ")
+        ))
+    }
 }
 
-fn configure_accounts(observer: &mut Observer, accounts: Vec<cfg::Account>) -> anyhow::Result<()> {
+fn report_session_summary(notifier: &NotifierMultiplexer, ctx: &NotifierContext, notify: bool) {
+    let summary = ctx.stats.summary();
+    info!("Session summary:");
+    for line in &summary {
+        info!("  {line}");
+    }
+
+    if notify {
+        notifier.notify_event(Event::Info {
+            title: "You Have Mail session summary".to_string(),
+            message: Some(summary.join("\n")),
+        });
+        if !notifier.flush(NOTIFIER_FLUSH_TIMEOUT) {
+            warn!("Timed out waiting for the session summary to be sent");
+        }
+    }
+}
+
+/// Print the accounts of the observer to stdout.
+fn list_accounts(accounts: &Accounts, output: OutputFormat) -> anyhow::Result<()> {
+    let accounts = accounts.iter().collect::<Vec<_>>();
+    match output {
+        OutputFormat::Text => {
+            for account in accounts {
+                let state = if account.logged_in {
+                    "Logged In"
+                } else {
+                    "Logged Out/Session Expired"
+                };
+                println!("{} ({}) State: {state}", account.email, account.backend);
+            }
+        }
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&accounts)
+                .map_err(|e| anyhow!("Failed to serialize accounts: {e}"))?
+        ),
+    }
+    Ok(())
+}
+
+fn export_accounts(
+    accounts: &Accounts,
+    secret_store: &mut dyn Secrets,
+    path: &Path,
+) -> anyhow::Result<()> {
+    let key = secret_store
+        .load()?
+        .ok_or_else(|| anyhow!("No encryption key found"))?;
+    let archive_key = archive::export(accounts, &key, path)?;
+    info!("Exported {} account(s) to {path:?}", accounts.len());
+    println!(
+        "Exported {} account(s) to {path:?}, import them with `--import-accounts` and the key below. Keep the key secret, it gives access to the accounts.",
+        accounts.len()
+    );
+    println!("{}", archive_key.expose_secret().to_base64());
+    Ok(())
+}
+
+/// Replace the observer states with the accounts exported to `path`, once their backends are
+/// known to be available.
+fn import_accounts(
+    accounts: Accounts,
+    secret_store: &mut dyn Secrets,
+    config_dir: &Path,
+    path: &Path,
+    state: &mut StateFile,
+) -> anyhow::Result<()> {
+    if !accounts.is_empty() {
+        return Err(anyhow!(
+            "Observer already has accounts, remove them with --remove-account before importing"
+        ));
+    }
+
+    let archive_key = match std::env::var(archive::IMPORT_KEY_VARIABLE) {
+        Ok(key) if !key.is_empty() => key,
+        _ if !stdin().is_terminal() => {
+            return Err(anyhow!(
+                "No key for {path:?}, set {} or run in a terminal",
+                archive::IMPORT_KEY_VARIABLE
+            ));
+        }
+        _ => rpassword::prompt_password(format!("Please type the key of {path:?}: "))
+            .map_err(|_| anyhow!("Failed to read key"))?,
+    };
+    let imported = archive::import(path, &archive_key)?;
+
+    let unknown = imported
+        .iter()
+        .flat_map(|s| &s.accounts)
+        .filter(|a| accounts.backend_by_name(&a.backend).is_none())
+        .map(|a| format!("{} ({})", a.email, a.backend))
+        .collect::<Vec<_>>();
+    if !unknown.is_empty() {
+        return Err(anyhow!(
+            "Unknown backend for {}, it may not be enabled in this build or in enabled_backends",
+            unknown.join(", ")
+        ));
+    }
+
+    let key = secret_store
+        .load()?
+        .ok_or_else(|| anyhow!("No encryption key found"))?;
+    // Make sure no observer writes its state after it was replaced.
+    drop(accounts);
+
+    let accounts_dir = accounts::accounts_dir(config_dir);
+    create_dir_user_only(&accounts_dir)
+        .map_err(|e| anyhow!("Failed to create {accounts_dir:?}: {e}"))?;
+    let mut written = Vec::with_capacity(imported.len());
+    let remove_written = |written: &[(PathBuf, PathBuf)]| {
+        for (imported_path, _) in written {
+            let _ = std::fs::remove_file(imported_path);
+        }
+    };
+    for archived in &imported {
+        let emails = archived
+            .accounts
+            .iter()
+            .map(|a| a.email.as_str())
+            .collect::<Vec<_>>();
+        let state_path = accounts::state_path(config_dir, &emails);
+        let imported_path = state_path.with_extension("imported");
+        let result = archived.encrypt_state(&key).and_then(|encrypted| {
+            write_user_file(&imported_path, &encrypted)
+                .map_err(|e| anyhow!("Failed to write imported state to {imported_path:?}: {e}"))
+        });
+        if let Err(e) = result {
+            remove_written(&written);
+            return Err(e);
+        }
+        written.push((imported_path.clone(), state_path));
+        if let Err(e) =
+            you_have_mail_common::Config::create_or_load(accounts::copy_key(&key)?, imported_path)
+        {
+            remove_written(&written);
+            return Err(anyhow!("Imported observer state can't be loaded: {e}"));
+        }
+    }
+    for (imported_path, state_path) in &written {
+        std::fs::rename(imported_path, state_path)
+            .map_err(|e| anyhow!("Failed to replace observer state at {state_path:?}: {e}"))?;
+    }
+
+    let imported = imported
+        .iter()
+        .flat_map(|s| &s.accounts)
+        .collect::<Vec<_>>();
+    for account in &imported {
+        state.forget_account(&account.email);
+        info!("Account {} ({}) imported", account.email, account.backend);
+    }
+    println!("Imported {} account(s) from {path:?}", imported.len());
+    Ok(())
+}
+
+fn print_status(
+    accounts: &Accounts,
+    state: &StateFile,
+    output: OutputFormat,
+) -> anyhow::Result<()> {
+    let accounts = accounts
+        .groups()
+        .iter()
+        .flat_map(|group| group.accounts().iter().map(|a| (a, group.interval())))
+        .map(|(account, interval)| {
+            let next_poll = state.next_poll(&account.email, interval);
+            let poll_state = state
+                .state
+                .accounts
+                .get(&account.email)
+                .cloned()
+                .unwrap_or_default();
+            let (last_error_at, last_error) = poll_state.last_error.unzip();
+            AccountStatus {
+                email: account.email.clone(),
+                backend: account.backend.clone(),
+                logged_in: account.logged_in,
+                last_success: poll_state.last_success,
+                last_error,
+                last_error_at,
+                next_poll: account.logged_in.then_some(next_poll).flatten(),
+            }
+        })
+        .collect::<Vec<_>>();
+    let notifiers = state
+        .state
+        .breakers
+        .iter()
+        .map(|(name, paused_until)| NotifierStatus {
+            name: name.clone(),
+            breaker: BreakerState::paused_until(*paused_until),
+            paused_until: *paused_until,
+        })
+        .collect::<Vec<_>>();
+
+    match output {
+        OutputFormat::Text => {
+            if accounts.is_empty() {
+                println!("No accounts configured");
+            }
+            for account in accounts {
+                println!("{} ({})", account.email, account.backend);
+                let state = if account.logged_in {
+                    "Logged In"
+                } else {
+                    "Logged Out/Session Expired"
+                };
+                println!("  State: {state}");
+                println!(
+                    "  Last successful poll: {}",
+                    format_timestamp(account.last_success)
+                );
+                match (account.last_error, account.last_error_at) {
+                    (Some(error), at) => {
+                        println!("  Last error: {error} ({})", format_timestamp(at))
+                    }
+                    _ => println!("  Last error: none"),
+                }
+                if account.logged_in {
+                    println!("  Next poll: {}", format_timestamp(account.next_poll));
+                } else {
+                    println!("  Next poll: none, run --configure-accounts to log in again");
+                }
+            }
+            if !notifiers.is_empty() {
+                println!("Notifiers");
+            }
+            for notifier in notifiers {
+                match notifier.breaker {
+                    BreakerState::Closed => println!("  {}: sending", notifier.name),
+                    BreakerState::Open => println!(
+                        "  {}: paused after repeated failures until {}",
+                        notifier.name,
+                        format_timestamp(notifier.paused_until)
+                    ),
+                    BreakerState::HalfOpen => println!(
+                        "  {}: paused, the next notification tests whether it recovered",
+                        notifier.name
+                    ),
+                }
+            }
+        }
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&Status {
+                accounts,
+                notifiers
+            })
+            .map_err(|e| anyhow!("Failed to serialize status: {e}"))?
+        ),
+    }
+    Ok(())
+}
+
+/// Local time of the unix `timestamp` in seconds, `never` if there is none.
+fn format_timestamp(timestamp: Option<u64>) -> String {
+    timestamp
+        .and_then(|t| Local.timestamp_opt(t as i64, 0).single())
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| "never".to_string())
+}
+
+/// Config accounts whose backend is available in this build. Accounts referencing an unknown
+/// backend are reported and skipped so they don't prevent the others from working.
+fn supported_accounts<'a>(
+    accounts: &Accounts,
+    cfg_accounts: &'a [cfg::Account],
+) -> Vec<&'a cfg::Account> {
+    cfg_accounts
+        .iter()
+        .filter(|account| {
+            if accounts.backend_by_name(&account.backend).is_some() {
+                return true;
+            }
+            warn!(
+                "Account {} uses backend '{}' which is not available, skipping",
+                account.email, account.backend
+            );
+            false
+        })
+        .collect()
+}
+
+/// Send a one time notification for every account which polled successfully for the first time.
+fn notify_first_polls(
+    accounts: &[AccountInfo],
+    notifier: &NotifierMultiplexer,
+    state: &mut StateFile,
+) {
+    let mut changed = false;
+    for AccountInfo {
+        email, logged_in, ..
+    } in accounts
+    {
+        if *logged_in && state.record_first_poll(email) {
+            changed = true;
+            notifier.notify_event(Event::Info {
+                title: format!("Now monitoring {email}"),
+                message: Some(format!("{email} was polled successfully")),
+            });
+        }
+    }
+
+    if changed {
+        if let Err(e) = state.save() {
+            warn!("{e}");
+        }
+    }
+}
+
+fn configure_accounts(
+    accounts: &mut Accounts,
+    cfg_accounts: Vec<cfg::Account>,
+    state: &mut StateFile,
+    totp_secrets: &mut TotpSecrets,
+) -> anyhow::Result<()> {
     info!("Checking Config Accounts");
 
-    for account in &accounts {
-        let prompt = if let Some(obs_account) = observer.get_account(&account.email) {
-            if obs_account.backend().name() != account.backend {
+    for account in supported_accounts(accounts, &cfg_accounts) {
+        let prompt = if let Some(obs_account) = accounts.get(&account.email) {
+            if obs_account.backend != account.backend {
                 return Err(anyhow!("Account {} already in observer, but with different backend please remove first.", account.email));
             }
-            !obs_account.is_logged_in()
+            if state.proxy_changed(&account.email, account.proxy.as_deref()) {
+                // The proxy is set when the account logs in.
+                info!(
+                    "Proxy of account {} changed, logging in again",
+                    account.email
+                );
+                accounts.remove(&account.email)?;
+                true
+            } else {
+                !obs_account.logged_in
+            }
         } else {
             true
         };
 
+        let secret_var = account_env_var("YHM_TOTP_SECRET", &account.email);
+        let totp_secret = match std::env::var(&secret_var) {
+            Ok(secret) if !secret.trim().is_empty() => Some(TotpSecret::new(secret.trim())),
+            _ => account.totp_secret.clone(),
+        };
+        if account.totp_secret.is_some() {
+            warn!("The totp_secret of account {} is stored in plain text in the config file, it is stored encrypted once the account is configured, remove it from the config file afterwards or set {secret_var} instead", account.email);
+        }
+
         if prompt {
-            prompt_account_auth(observer, account)?;
+            let totp_secret = totp_secret
+                .as_ref()
+                .or_else(|| totp_secrets.get(&account.email));
+            prompt_account_auth(accounts, account, totp_secret)?;
+            state.forget_account(&account.email);
+            state.record_proxy(&account.email, account.proxy.as_deref());
             info!("Account {} added", account.email)
         }
+        if let Some(secret) = totp_secret {
+            // Stored even for an account which was already logged in, so the daemon can log it
+            // in again once its session expires.
+            totp_secrets.insert(&account.email, secret);
+        }
     }
 
     info!("All accounts configured");
     Ok(())
 }
 
-fn prompt_account_auth(observer: &mut Observer, cfg_account: &cfg::Account) -> anyhow::Result<()> {
-    let Some(backend) = observer.backend_by_name(&cfg_account.backend) else {
+fn prompt_account_auth(
+    accounts: &mut Accounts,
+    cfg_account: &cfg::Account,
+    totp_secret: Option<&TotpSecret>,
+) -> anyhow::Result<()> {
+    let Some(backend) = accounts.backend_by_name(&cfg_account.backend) else {
         return Err(anyhow!("Could not locate backed with name '{}'", cfg_account.backend));
     };
 
-    let password = loop {
-        let password =
-            rpassword::prompt_password(format!("Please type password for {}: ", cfg_account.email))
-                .map_err(|_| anyhow!("Failed to read password"))?;
-        if password.is_empty() {
-            eprintln!("Password can't be empty, please try again");
-            continue;
+    let password_kind = match cfg_account.auth {
+        cfg::AuthMethod::Password => "password",
+        cfg::AuthMethod::AppPassword => {
+            println!(
+                "{} requires an app-specific password, your regular account password will not work.",
+                cfg_account.email
+            );
+            if let Some(url) = &cfg_account.app_password_url {
+                println!("You can generate one at {url}");
+            }
+            "app password"
+        }
+    };
+
+    let password_var = account_env_var("YHM_PASSWORD", &cfg_account.email);
+    let password = match std::env::var(&password_var) {
+        Ok(password) if !password.is_empty() => {
+            info!(
+                "Using {password_kind} of {} from {password_var}",
+                cfg_account.email
+            );
+            Secret::new(password)
+        }
+        _ if !stdin().is_terminal() => {
+            return Err(anyhow!(
+                "No {password_kind} for {}, set {password_var} or run in a terminal",
+                cfg_account.email
+            ));
         }
-        break Secret::new(password);
+        _ => loop {
+            let password = rpassword::prompt_password(format!(
+                "Please type {password_kind} for {}: ",
+                cfg_account.email
+            ))
+            .map_err(|_| anyhow!("Failed to read password"))?;
+            if password.is_empty() {
+                eprintln!("The {password_kind} can't be empty, please try again");
+                continue;
+            }
+            break Secret::new(password);
+        },
     };
 
-    let mut account = Account::new(backend, cfg_account.email.clone(), None);
+    let proxy = cfg_account.proxy()?;
+    if proxy.is_some() {
+        info!("Account {} connects through a proxy", cfg_account.email);
+    }
+    let mut account = Account::new(backend, cfg_account.email.clone(), proxy);
 
     match account.login(&password, None) {
         Ok(()) => {}
-        Err(AccountError::Backend(BackendError::HVCaptchaRequest(_))) => {
+        Err(AccountError::Backend(BackendError::HVCaptchaRequest(challenge))) => {
+            // The challenge can't be solved in a terminal, but it usually clears once the
+            // account logged in from a browser on the same network.
+            eprintln!(
+                "{} requires human verification (captcha), which can't be completed in the CLI.",
+                cfg_account.email
+            );
+            eprintln!("Challenge: {challenge:?}");
+            eprintln!("To continue:");
+            eprintln!("  1. Log in to the account in a web browser, from the same network as this machine, and complete the verification.");
+            eprintln!("  2. Wait a few minutes and run --configure-accounts again.");
             return Err(anyhow!(
-                "Account {} requested captcha validation, this is not supported in CLI:",
+                "Account {} requires human verification, complete it in a web browser and try again",
+                cfg_account.email
+            ))?;
+        }
+        Err(e) if cfg_account.auth == cfg::AuthMethod::AppPassword => {
+            return Err(anyhow!(
+                "Failed to login account {}: {e} (make sure you used an app-specific password)",
                 cfg_account.email
             ))?;
         }
@@ -325,6 +1862,32 @@ fn prompt_account_auth(observer: &mut Observer, cfg_account: &cfg::Account) -> a
         }
     }
 
+    let totp_var = account_env_var("YHM_TOTP", &cfg_account.email);
+    if account.is_awaiting_totp() {
+        if let Ok(code) = std::env::var(&totp_var) {
+            info!(
+                "Using TOTP 2FA code of {} from {totp_var}",
+                cfg_account.email
+            );
+            account
+                .submit_totp(code.trim())
+                .map_err(|e| anyhow!("Failed to submit TOTP code from {totp_var}: {e}"))?;
+        } else if let Some(secret) = totp_secret {
+            info!("Generating TOTP 2FA code of {}", cfg_account.email);
+            let code = secret.code().map_err(|e| {
+                anyhow!("Failed to generate TOTP code of {}: {e}", cfg_account.email)
+            })?;
+            account
+                .submit_totp(&code)
+                .map_err(|e| anyhow!("Failed to submit generated TOTP code: {e}"))?;
+        } else if !stdin().is_terminal() {
+            return Err(anyhow!(
+                "{} requires a TOTP 2FA code, set {totp_var} or run in a terminal",
+                cfg_account.email
+            ));
+        }
+    }
+
     if account.is_awaiting_totp() {
         for _ in 0..5 {
             let stdin = stdin().lock();
@@ -354,35 +1917,195 @@ fn prompt_account_auth(observer: &mut Observer, cfg_account: &cfg::Account) -> a
         }
     }
 
-    observer
-        .add_account(account)
-        .map_err(|e| anyhow!("Failed to add account {}: {e}", cfg_account.email))
+    accounts.add(account)
+}
+
+/// Log the `logged_out` accounts in again without prompting, with the password from
+/// `YHM_PASSWORD_<EMAIL>` and their stored TOTP secret. Accounts without a password in the
+/// environment stay logged out until they are configured again.
+fn relogin_accounts(
+    accounts: &mut Accounts,
+    cfg_accounts: &[cfg::Account],
+    totp_secrets: Option<&TotpSecrets>,
+    logged_out: &[String],
+    notifier: &NotifierMultiplexer,
+    state: &mut StateFile,
+) {
+    for email in logged_out {
+        let Some(cfg_account) = cfg_accounts.iter().find(|a| a.email == *email) else {
+            continue;
+        };
+        let password_var = account_env_var("YHM_PASSWORD", email);
+        let password = match std::env::var(&password_var) {
+            Ok(password) if !password.is_empty() => Secret::new(password),
+            _ => continue,
+        };
+        let totp_secret = totp_secrets.and_then(|secrets| secrets.get(email));
+        let result = login_account(accounts, cfg_account, &password, totp_secret)
+            .and_then(|account| accounts.replace(account));
+        match result {
+            Ok(()) => {
+                info!("Logged in {email} again with the password from {password_var}");
+                state.record_proxy(email, cfg_account.proxy.as_deref());
+                notifier.notify_event(Event::Info {
+                    title: format!("{email} logged in again"),
+                    message: Some("The session had expired".to_string()),
+                });
+            }
+            Err(e) => {
+                warn!("Failed to log in {email} again, use --configure-accounts to log in: {e}")
+            }
+        }
+    }
+}
+
+/// The account of `cfg_account` logged in with `password` without prompting, the TOTP 2FA code
+/// is generated from `totp_secret` if required.
+fn login_account(
+    accounts: &Accounts,
+    cfg_account: &cfg::Account,
+    password: &Secret<String>,
+    totp_secret: Option<&TotpSecret>,
+) -> anyhow::Result<Account> {
+    let backend = accounts
+        .backend_by_name(&cfg_account.backend)
+        .ok_or_else(|| anyhow!("Unknown backend '{}'", cfg_account.backend))?;
+    let mut account = Account::new(backend, cfg_account.email.clone(), cfg_account.proxy()?);
+    account
+        .login(password, None)
+        .map_err(|e| anyhow!("Failed to login: {e}"))?;
+    if account.is_awaiting_totp() {
+        let secret = totp_secret
+            .ok_or_else(|| anyhow!("A TOTP 2FA code is required and no TOTP secret is stored"))?;
+        account
+            .submit_totp(&secret.code()?)
+            .map_err(|e| anyhow!("Failed to submit generated TOTP code: {e}"))?;
+    }
+    Ok(account)
+}
+
+/// Name of the environment variable `prefix_EMAIL` which provides a login value of `email`, the
+/// email is upper cased and everything but letters and digits is replaced by `_`.
+fn account_env_var(prefix: &str, email: &str) -> String {
+    let email = email
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    format!("{prefix}_{email}")
 }
 
 fn delete_accounts(
-    observer: &mut Observer,
-    accounts: Option<Vec<cfg::Account>>,
+    accounts: &mut Accounts,
+    cfg_accounts: Option<Vec<cfg::Account>>,
+    state: &mut StateFile,
+    totp_secrets: &mut TotpSecrets,
 ) -> anyhow::Result<()> {
-    let accounts_to_delete = if let Some(accounts) = accounts {
-        observer
-            .accounts()
-            .filter(|&(e, _)| accounts.iter().any(|a| a.email == *e))
-            .map(|(e, _)| e.clone())
+    let accounts_to_delete = if let Some(cfg_accounts) = cfg_accounts {
+        accounts
+            .iter()
+            .filter(|account| cfg_accounts.iter().any(|a| a.email == account.email))
+            .map(|account| account.email.clone())
             .collect::<Vec<_>>()
     } else {
-        observer
-            .accounts()
-            .map(|(e, _)| e.clone())
+        accounts
+            .iter()
+            .map(|account| account.email.clone())
             .collect::<Vec<_>>()
     };
 
     info!("Found {} account(s) to delete", accounts_to_delete.len());
     for account in accounts_to_delete {
         info!("Logging out and deleting {}", account);
-        observer
-            .remove_account(&account)
-            .map_err(|e| anyhow!("Failed to delete account {}: {e}", account))?;
+        accounts.remove(&account)?;
+        state.forget_account(&account);
+        totp_secrets.remove(&account);
+    }
+
+    Ok(())
+}
+
+/// Log out and remove the accounts with the given `emails`, or the ones picked from a list if
+/// there are none. Nothing is removed if any of them is unknown.
+fn remove_accounts(
+    accounts: &mut Accounts,
+    emails: Vec<String>,
+    state: &mut StateFile,
+    totp_secrets: &mut TotpSecrets,
+) -> anyhow::Result<()> {
+    let emails = if emails.is_empty() {
+        pick_accounts(accounts)?
+    } else {
+        emails
+    };
+
+    let unknown = emails
+        .iter()
+        .filter(|email| !accounts.contains(email))
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    if !unknown.is_empty() {
+        return Err(anyhow!("Unknown account(s): {}", unknown.join(", ")));
     }
 
+    for email in emails {
+        info!("Logging out and removing {}", email);
+        accounts.remove(&email)?;
+        state.forget_account(&email);
+        totp_secrets.remove(&email);
+        println!("Removed {email}");
+    }
     Ok(())
 }
+
+/// Prompt for the accounts to remove from a numbered list of the observer accounts.
+fn pick_accounts(accounts: &Accounts) -> anyhow::Result<Vec<String>> {
+    if !stdin().is_terminal() {
+        return Err(anyhow!(
+            "Pass the email to --remove-account or run in a terminal to pick the accounts"
+        ));
+    }
+    let accounts = accounts
+        .iter()
+        .map(|account| (account.email.clone(), account.backend.clone()))
+        .collect::<Vec<_>>();
+    if accounts.is_empty() {
+        println!("There are no accounts to remove");
+        return Ok(Vec::new());
+    }
+
+    for (index, (email, backend)) in accounts.iter().enumerate() {
+        println!("{}) {email} ({backend})", index + 1);
+    }
+    loop {
+        print!("Numbers of the accounts to remove, separated by spaces (empty to cancel): ");
+        stdout().flush().expect("Failed to flush stdout");
+        let mut line = String::new();
+        stdin()
+            .lock()
+            .read_line(&mut line)
+            .map_err(|e| anyhow!("Failed to read line: {e}"))?;
+
+        let selection = line
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|s| !s.is_empty())
+            .map(|s| match s.parse::<usize>() {
+                Ok(n) if (1..=accounts.len()).contains(&n) => Ok(accounts[n - 1].0.clone()),
+                _ => Err(s),
+            })
+            .collect::<Result<Vec<_>, _>>();
+        match selection {
+            Ok(mut emails) => {
+                emails.sort();
+                emails.dedup();
+                return Ok(emails);
+            }
+            Err(s) => eprintln!("'{s}' is not a number between 1 and {}", accounts.len()),
+        }
+    }
+}
@@ -0,0 +1,16 @@
+//! Prometheus `GET /metrics` endpoint.
+use crate::http_server::{self, Response};
+use crate::notifiers::SessionStats;
+use std::sync::Arc;
+
+/// Answer `GET /metrics` requests on `address` with the counters in `stats`.
+pub fn serve(address: &str, stats: Arc<SessionStats>) -> anyhow::Result<()> {
+    http_server::serve(address, "metrics", move |path| match path {
+        "/metrics" => Response {
+            status: "200 OK",
+            content_type: "text/plain; version=0.0.4",
+            body: stats.prometheus(),
+        },
+        _ => Response::not_found(),
+    })
+}
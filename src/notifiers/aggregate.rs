@@ -0,0 +1,178 @@
+//! Merging of the new email notifications generated by a poll.
+use crate::notifiers::{Email, Event};
+use serde::Deserialize;
+
+/// Which new email notifications are merged into a single notification.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Deserialize)]
+pub enum Aggregation {
+    /// Notifications are sent as soon as they are received.
+    #[default]
+    None,
+    /// One notification per account.
+    Account,
+    /// One notification per backend.
+    Backend,
+    /// One notification for all accounts.
+    Global,
+}
+
+impl Aggregation {
+    fn key<'a>(&self, account: &'a str, backend: &'a str) -> Option<&'a str> {
+        match self {
+            Aggregation::None | Aggregation::Account => Some(account),
+            Aggregation::Backend => Some(backend),
+            Aggregation::Global => None,
+        }
+    }
+
+    /// Merge the new email events which share the same key, other events are kept as is.
+    /// The order of the events is preserved, merged events take the place of the first one.
+    pub fn merge(&self, events: Vec<Event>) -> Vec<Event> {
+        let mut merged: Vec<Event> = Vec::with_capacity(events.len());
+        for event in events {
+            let (account, backend, emails, open_url, redacted) = match event {
+                Event::NewEmail {
+                    account,
+                    backend,
+                    emails,
+                    open_url,
+                    redacted,
+                } => (account, backend, emails, open_url, redacted),
+                event => {
+                    merged.push(event);
+                    continue;
+                }
+            };
+
+            let position = merged.iter().position(|e| {
+                matches!(e, Event::NewEmail { account: a, backend: b, .. }
+                    if self.key(a, b) == self.key(&account, &backend))
+            });
+
+            if let Some(merged_event) = position.map(|i| &mut merged[i]) {
+                if let Event::NewEmail {
+                    emails: merged_emails,
+                    open_url: merged_open_url,
+                    redacted: merged_redacted,
+                    ..
+                } = merged_event
+                {
+                    merged_emails.extend(with_source(emails, &account, &backend));
+                    if *merged_open_url != open_url {
+                        *merged_open_url = None;
+                    }
+                    *merged_redacted = *merged_redacted && redacted;
+                }
+                merged_event.update_sources();
+                continue;
+            }
+
+            let emails = with_source(emails, &account, &backend);
+            merged.push(Event::NewEmail {
+                account,
+                backend,
+                emails,
+                open_url,
+                redacted,
+            });
+        }
+        merged
+    }
+}
+
+/// `emails` with `account` and `backend` set on those recorded without them, so the merged event
+/// still knows where each email comes from.
+fn with_source(mut emails: Vec<Email>, account: &str, backend: &str) -> Vec<Email> {
+    for email in emails.iter_mut().filter(|e| e.account.is_empty()) {
+        email.account = account.to_string();
+        email.backend = backend.to_string();
+    }
+    emails
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_email(account: &str, backend: &str, subject: &str) -> Event {
+        Event::NewEmail {
+            account: account.to_string(),
+            backend: backend.to_string(),
+            emails: vec![Email {
+                sender: "sender".to_string(),
+                subject: subject.to_string(),
+                received_at: 0,
+                account: account.to_string(),
+                backend: backend.to_string(),
+                seen_hash: None,
+                priorities: Default::default(),
+            }],
+            open_url: None,
+            redacted: false,
+        }
+    }
+
+    fn sources(event: &Event) -> (&str, &str, Vec<&str>) {
+        match event {
+            Event::NewEmail {
+                account,
+                backend,
+                emails,
+                ..
+            } => (
+                account.as_str(),
+                backend.as_str(),
+                emails.iter().map(|e| e.account.as_str()).collect(),
+            ),
+            _ => panic!("not a new email event"),
+        }
+    }
+
+    #[test]
+    fn global_merge_keeps_the_account_of_each_email() {
+        let merged = Aggregation::Global.merge(vec![
+            new_email("a@proton.me", "Proton Mail", "1"),
+            new_email("b@proton.me", "Proton Mail", "2"),
+            new_email("a@proton.me", "Proton Mail", "3"),
+        ]);
+        assert_eq!(merged.len(), 1);
+        assert_eq!(
+            sources(&merged[0]),
+            (
+                "a@proton.me, b@proton.me",
+                "Proton Mail",
+                vec!["a@proton.me", "b@proton.me", "a@proton.me"]
+            )
+        );
+
+        // Merging again, as the quiet hours do with merged events, doesn't repeat the accounts.
+        let merged = Aggregation::Global.merge(vec![
+            merged[0].clone(),
+            new_email("c@proton.me", "Other", "4"),
+        ]);
+        assert_eq!(
+            sources(&merged[0]).0,
+            "a@proton.me, b@proton.me, c@proton.me"
+        );
+        assert_eq!(sources(&merged[0]).1, "Proton Mail, Other");
+    }
+
+    #[test]
+    fn emails_without_account_get_the_one_of_their_event() {
+        let mut event = new_email("a@proton.me", "Proton Mail", "1");
+        if let Event::NewEmail { emails, .. } = &mut event {
+            emails[0].account.clear();
+            emails[0].backend.clear();
+        }
+        let merged =
+            Aggregation::Global.merge(vec![event, new_email("b@proton.me", "Proton Mail", "2")]);
+        assert_eq!(
+            sources(&merged[0]),
+            (
+                "a@proton.me, b@proton.me",
+                "Proton Mail",
+                vec!["a@proton.me", "b@proton.me"]
+            )
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use crate::notifiers::stats::BreakerState;
+use crate::notifiers::{NotifierContext, SessionStats};
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Circuit breaker settings shared by the configs of the HTTP notifiers.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BreakerConfig {
+    /// Number of consecutive failures after which requests are paused, 0 never pauses them.
+    #[serde(default = "default_breaker_threshold")]
+    pub breaker_threshold: u32,
+    /// Time in seconds requests are paused before testing whether the endpoint recovered.
+    #[serde(default = "default_breaker_cooldown")]
+    pub breaker_cooldown: u64,
+}
+
+impl BreakerConfig {
+    /// Breaker of the notifier `kind` called `name`, its state is reported in `ctx.stats`.
+    pub fn circuit_breaker(&self, kind: &str, name: &str, ctx: &NotifierContext) -> CircuitBreaker {
+        CircuitBreaker::new(
+            self.breaker_threshold,
+            Duration::from_secs(self.breaker_cooldown),
+        )
+        .report_to(format!("{kind} ({name})"), ctx.stats.clone())
+    }
+}
+
+/// Default number of consecutive failures after which the breaker opens.
+const fn default_breaker_threshold() -> u32 {
+    5
+}
+
+/// Default time in seconds the breaker stays open.
+const fn default_breaker_cooldown() -> u64 {
+    300
+}
+
+/// Stop sending requests to an endpoint after too many consecutive failures.
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    failures: u32,
+    opened_at: Option<Instant>,
+    /// Name of the notifier and stats its state changes are reported to.
+    report: Option<(String, Arc<SessionStats>)>,
+}
+
+impl CircuitBreaker {
+    /// A `threshold` of 0 disables the breaker.
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            cooldown,
+            failures: 0,
+            opened_at: None,
+            report: None,
+        }
+    }
+
+    /// Report the state changes to `stats` as the breaker of `notifier`.
+    pub fn report_to(mut self, notifier: String, stats: Arc<SessionStats>) -> Self {
+        self.report = Some((notifier, stats));
+        self.report();
+        self
+    }
+
+    pub fn state(&self) -> BreakerState {
+        match self.opened_at {
+            None => BreakerState::Closed,
+            Some(t) if t.elapsed() >= self.cooldown => BreakerState::HalfOpen,
+            Some(_) => BreakerState::Open,
+        }
+    }
+
+    pub fn cooldown(&self) -> Duration {
+        self.cooldown
+    }
+
+    /// Whether a request should be attempted.
+    pub fn allow(&self) -> bool {
+        self.state() != BreakerState::Open
+    }
+
+    /// Record a successful request, returns true if the breaker was open.
+    pub fn record_success(&mut self) -> bool {
+        self.failures = 0;
+        let recovered = self.opened_at.take().is_some();
+        if recovered {
+            self.report();
+        }
+        recovered
+    }
+
+    /// Record a failed request, returns true if this failure opened the breaker.
+    pub fn record_failure(&mut self) -> bool {
+        if self.threshold == 0 {
+            return false;
+        }
+
+        self.failures = self.failures.saturating_add(1);
+        let opened = match self.state() {
+            BreakerState::HalfOpen => true,
+            BreakerState::Closed => self.failures >= self.threshold,
+            BreakerState::Open => false,
+        };
+        if opened {
+            self.opened_at = Some(Instant::now());
+            self.report();
+        }
+        opened
+    }
+
+    fn report(&self) {
+        let Some((notifier, stats)) = &self.report else {
+            return;
+        };
+        let paused_until = self.opened_at.map(|opened_at| {
+            let until = SystemTime::now() + self.cooldown.saturating_sub(opened_at.elapsed());
+            until
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default()
+        });
+        stats.record_breaker(notifier, paused_until);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LONG_COOLDOWN: Duration = Duration::from_secs(3600);
+
+    #[test]
+    fn opens_after_threshold_failures() {
+        let mut breaker = CircuitBreaker::new(3, LONG_COOLDOWN);
+        assert!(!breaker.record_failure());
+        assert!(!breaker.record_failure());
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        assert!(breaker.allow());
+        assert!(breaker.record_failure());
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert!(!breaker.allow());
+        assert!(!breaker.record_failure());
+    }
+
+    #[test]
+    fn success_resets_the_failures() {
+        let mut breaker = CircuitBreaker::new(2, LONG_COOLDOWN);
+        breaker.record_failure();
+        assert!(!breaker.record_success());
+        assert!(!breaker.record_failure());
+        assert_eq!(breaker.state(), BreakerState::Closed);
+    }
+
+    #[test]
+    fn half_open_after_the_cooldown() {
+        let mut breaker = CircuitBreaker::new(1, Duration::ZERO);
+        assert!(breaker.record_failure());
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+        assert!(breaker.allow());
+        // A failed test request opens the breaker again.
+        assert!(breaker.record_failure());
+        assert!(breaker.record_success());
+        assert_eq!(breaker.state(), BreakerState::Closed);
+    }
+
+    #[test]
+    fn threshold_zero_never_opens() {
+        let mut breaker = CircuitBreaker::new(0, LONG_COOLDOWN);
+        for _ in 0..100 {
+            assert!(!breaker.record_failure());
+        }
+        assert_eq!(breaker.state(), BreakerState::Closed);
+    }
+
+    #[test]
+    fn reports_the_state_changes() {
+        let stats = Arc::new(SessionStats::default());
+        let mut breaker = CircuitBreaker::new(1, LONG_COOLDOWN)
+            .report_to("ntfy (test)".to_string(), stats.clone());
+        assert_eq!(stats.breakers().get("ntfy (test)"), Some(&None));
+
+        breaker.record_failure();
+        let paused_until = stats.breakers()["ntfy (test)"].unwrap();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        assert!(paused_until > now && paused_until <= now + LONG_COOLDOWN.as_secs());
+
+        breaker.record_success();
+        assert_eq!(stats.breakers().get("ntfy (test)"), Some(&None));
+    }
+}
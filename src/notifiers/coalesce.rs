@@ -0,0 +1,137 @@
+//! Merging of the new email notifications an account receives within a time window.
+use crate::notifiers::{Aggregation, Event, Notifier};
+use anyhow::anyhow;
+use log::debug;
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+/// Longest time the flush thread sleeps before checking whether the notifier was dropped.
+const MAX_WAIT: Duration = Duration::from_secs(1);
+
+/// Buffers the new email notifications of each account for `window` and sends them to the
+/// notifiers as one notification. All other events are passed through immediately.
+pub struct CoalescingNotifier {
+    state: Arc<State>,
+}
+
+struct State {
+    notifiers: Vec<Box<dyn Notifier>>,
+    window: Duration,
+    /// Merged notification of each account and when it is due.
+    pending: Mutex<Vec<(Instant, Event)>>,
+    changed: Condvar,
+}
+
+impl State {
+    fn notify_all(&self, event: &Event) {
+        for notifier in &self.notifiers {
+            notifier.notify(event)
+        }
+    }
+
+    /// Send all buffered notifications, whether they are due or not.
+    fn send_pending(&self) {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        for (_, event) in pending {
+            self.notify_all(&event);
+        }
+    }
+}
+
+impl CoalescingNotifier {
+    pub fn new(notifiers: Vec<Box<dyn Notifier>>, window: Duration) -> anyhow::Result<Self> {
+        let state = Arc::new(State {
+            notifiers,
+            window,
+            pending: Mutex::default(),
+            changed: Condvar::new(),
+        });
+
+        let weak = Arc::downgrade(&state);
+        std::thread::Builder::new()
+            .name("coalesce-thread".to_string())
+            .spawn(move || send_due(weak))
+            .map_err(|e| anyhow!("Failed to spawn coalesce thread: {e}"))?;
+
+        Ok(Self { state })
+    }
+}
+
+impl Notifier for CoalescingNotifier {
+    fn notify(&self, event: &Event) {
+        let Event::NewEmail { account, .. } = event else {
+            self.state.notify_all(event);
+            return;
+        };
+
+        let mut pending = self.state.pending.lock().unwrap();
+        let existing = pending.iter().position(|(_, e)| match e {
+            Event::NewEmail { account: a, .. } => a == account,
+            _ => false,
+        });
+        match existing {
+            Some(index) => {
+                debug!("Coalescing new email notification for {account}");
+                let (due, previous) = pending.remove(index);
+                for merged in Aggregation::Account.merge(vec![previous, event.clone()]) {
+                    pending.push((due, merged));
+                }
+            }
+            None => {
+                pending.push((Instant::now() + self.state.window, event.clone()));
+                self.state.changed.notify_one();
+            }
+        }
+    }
+
+    /// Send the buffered notifications right away, e.g. on exit.
+    fn flush(&self, timeout: Duration) -> bool {
+        self.state.send_pending();
+
+        let deadline = Instant::now() + timeout;
+        let mut flushed = true;
+        for notifier in self.state.notifiers.iter() {
+            flushed &= notifier.flush(deadline.saturating_duration_since(Instant::now()));
+        }
+        flushed
+    }
+
+    fn shutdown(&self, timeout: Duration) -> bool {
+        self.state.send_pending();
+
+        let deadline = Instant::now() + timeout;
+        let mut stopped = true;
+        for notifier in self.state.notifiers.iter() {
+            stopped &= notifier.shutdown(deadline.saturating_duration_since(Instant::now()));
+        }
+        stopped
+    }
+}
+
+/// Send the notifications whose window elapsed, until the notifier is dropped.
+fn send_due(state: Weak<State>) {
+    while let Some(state) = state.upgrade() {
+        let mut pending = state.pending.lock().unwrap();
+        let now = Instant::now();
+        let wait = pending
+            .iter()
+            .map(|(due, _)| due.saturating_duration_since(now))
+            .min()
+            .unwrap_or(MAX_WAIT)
+            .min(MAX_WAIT);
+        if !wait.is_zero() {
+            pending = state.changed.wait_timeout(pending, wait).unwrap().0;
+        }
+
+        let now = Instant::now();
+        let (ready, waiting) = std::mem::take(&mut *pending)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(due, _)| *due <= now);
+        *pending = waiting;
+        drop(pending);
+
+        for (_, event) in ready {
+            state.notify_all(&event);
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use crate::notifiers::format::visible_emails;
+use crate::notifiers::template::MessageTemplates;
+use crate::notifiers::worker::{default_queue_size, WorkerNotifier};
+use crate::notifiers::{Event, Notifier, NotifierConfig, NotifierContext};
+use log::{debug, error};
+use notify_rust::Notification as DesktopNotification;
+use serde::Deserialize;
+
+/// Number of emails listed in the body of a new email notification.
+const MAX_LISTED_EMAILS: usize = 3;
+
+#[derive(Debug, Deserialize)]
+/// Configuration for native desktop notifications.
+pub struct DesktopConfig {
+    pub name: String,
+    /// Only send the notifications of these accounts, all accounts if empty.
+    #[serde(default)]
+    pub accounts: Vec<String>,
+    /// Hide the email senders and subjects, only the number of new emails is sent.
+    #[serde(default)]
+    pub redact: bool,
+    /// Number of notifications waiting to be sent after which new ones are dropped.
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
+    /// Title of the new email notifications, see the `Message Templates` section of the README.
+    pub title_template: Option<String>,
+    /// Line of each email in the body of the new email notifications.
+    pub body_template: Option<String>,
+}
+
+impl NotifierConfig for DesktopConfig {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn take_accounts(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.accounts)
+    }
+
+    fn redact(&self) -> bool {
+        self.redact
+    }
+
+    fn into_notifier(self, ctx: &NotifierContext) -> anyhow::Result<Box<dyn Notifier>> {
+        // Without a notification server the notifications would silently fail on every event.
+        #[cfg(all(unix, not(target_os = "macos")))]
+        notify_rust::get_server_information().map_err(|e| {
+            anyhow::anyhow!(
+                "No desktop notification server available (is a D-Bus session running?): {e}"
+            )
+        })?;
+
+        let state = DesktopNotifier {
+            name: self.name.clone(),
+            templates: MessageTemplates::new(
+                self.title_template,
+                self.body_template,
+                "{sender}: {subject}",
+            ),
+        };
+        let notifier =
+            WorkerNotifier::spawn("desktop", self.name, self.queue_size, ctx, move |event| {
+                state.handle(event)
+            })?;
+        Ok(Box::new(notifier))
+    }
+}
+
+/// Raise native OS notifications.
+struct DesktopNotifier {
+    name: String,
+    templates: MessageTemplates,
+}
+
+impl DesktopNotifier {
+    /// Returns true if the notification was shown.
+    fn handle(&self, event: Event) -> bool {
+        let mut critical = false;
+        let (summary, body) = match event {
+            Event::NewEmail {
+                account,
+                emails,
+                redacted,
+                ..
+            } => {
+                let visible = visible_emails(&emails, redacted);
+                let summary = self.templates.title(&account, &emails, visible);
+                let mut body = self
+                    .templates
+                    .body_lines(&account, emails.len(), visible)
+                    .take(MAX_LISTED_EMAILS)
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                if visible.len() > MAX_LISTED_EMAILS {
+                    body.push_str(&format!(
+                        "\n... and {} more",
+                        visible.len() - MAX_LISTED_EMAILS
+                    ));
+                }
+                (summary, body)
+            }
+            Event::AccountLoggedOut { account, .. } => {
+                critical = true;
+                (
+                    format!("{account} logged out or session expired"),
+                    String::new(),
+                )
+            }
+            Event::AccountError { account, error, .. } => {
+                critical = true;
+                (format!("{account} encountered an error"), error)
+            }
+            Event::ConfigError { error } => ("Server Config Error".to_string(), error),
+            Event::Error { error } => ("Server Error".to_string(), error),
+            Event::Info { title, message } => (title, message.unwrap_or_default()),
+        };
+
+        let mut notification = DesktopNotification::new();
+        notification
+            .appname("You Have Mail")
+            .summary(&summary)
+            .body(&body);
+        #[cfg(all(unix, not(target_os = "macos")))]
+        if critical {
+            notification.urgency(notify_rust::Urgency::Critical);
+        }
+        #[cfg(not(all(unix, not(target_os = "macos"))))]
+        let _ = critical;
+
+        match notification.show() {
+            Ok(_) => {
+                debug!("Desktop notification shown ({})", self.name);
+                true
+            }
+            Err(e) => {
+                error!("Failed to show desktop notification ({}): {e}", self.name);
+                false
+            }
+        }
+    }
+}
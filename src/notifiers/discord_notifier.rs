@@ -0,0 +1,180 @@
+use crate::notifiers::circuit_breaker::{BreakerConfig, CircuitBreaker};
+use crate::notifiers::format::{truncate, visible_emails};
+use crate::notifiers::http::{send_with_breaker, HttpConfig};
+use crate::notifiers::worker::{default_queue_size, WorkerNotifier};
+use crate::notifiers::{Event, Notifier, NotifierConfig, NotifierContext, Shutdown};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use you_have_mail_common::{ExposeSecret, Secret};
+
+/// Discord limits, see https://discord.com/developers/docs/resources/message#embed-object-embed-limits
+const MAX_FIELDS: usize = 25;
+const MAX_EMBED_CHARS: usize = 6000;
+const MAX_TITLE_CHARS: usize = 256;
+const MAX_SENDER_CHARS: usize = 256;
+const MAX_SUBJECT_CHARS: usize = 256;
+const MAX_DESCRIPTION_CHARS: usize = 4096;
+
+const INFO_COLOR: u32 = 0x6D4AFF;
+const ERROR_COLOR: u32 = 0xE74C3C;
+
+#[derive(Debug, Deserialize)]
+/// Configuration for a Discord webhook.
+pub struct DiscordConfig {
+    pub name: String,
+    /// Only send the notifications of these accounts, all accounts if empty.
+    #[serde(default)]
+    pub accounts: Vec<String>,
+    /// Hide the email senders and subjects, only the number of new emails is sent.
+    #[serde(default)]
+    pub redact: bool,
+    /// Number of notifications waiting to be sent after which new ones are dropped.
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
+    pub webhook_url: String,
+    /// PEM file with additional root certificates, overrides the global `ca_bundle`.
+    pub ca_bundle: Option<PathBuf>,
+    /// Timeouts and User-Agent of the requests.
+    #[serde(flatten)]
+    pub http: HttpConfig,
+    /// Pause the requests after repeated failures.
+    #[serde(flatten)]
+    pub breaker: BreakerConfig,
+}
+
+impl NotifierConfig for DiscordConfig {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn take_accounts(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.accounts)
+    }
+
+    fn redact(&self) -> bool {
+        self.redact
+    }
+
+    fn into_notifier(self, ctx: &NotifierContext) -> anyhow::Result<Box<dyn Notifier>> {
+        let ca_bundle = self.ca_bundle.or_else(|| ctx.ca_bundle.clone());
+        let agent = ctx
+            .http_agents
+            .agent(&self.http.options(ca_bundle, &self.webhook_url))?;
+        let mut state = DiscordNotifier {
+            name: self.name.clone(),
+            agent,
+            shutdown: ctx.shutdown.clone(),
+            webhook_url: Secret::new(self.webhook_url),
+            breaker: self.breaker.circuit_breaker("discord", &self.name, ctx),
+        };
+        let notifier =
+            WorkerNotifier::spawn("discord", self.name, self.queue_size, ctx, move |event| {
+                state.handle(event)
+            })?;
+        Ok(Box::new(notifier))
+    }
+}
+
+/// Post notifications as embeds to a Discord webhook.
+struct DiscordNotifier {
+    name: String,
+    agent: ureq::Agent,
+    shutdown: Shutdown,
+    /// The webhook url contains the token allowing anyone to post to the channel.
+    webhook_url: Secret<String>,
+    breaker: CircuitBreaker,
+}
+
+impl DiscordNotifier {
+    /// Returns true if the notification was posted.
+    fn handle(&mut self, event: Event) -> bool {
+        let embed = match event {
+            Event::NewEmail {
+                account,
+                emails,
+                open_url,
+                redacted,
+                ..
+            } => {
+                let title = truncate(
+                    &format!("`{account}` has {} new message(s)", emails.len()),
+                    MAX_TITLE_CHARS,
+                );
+                let mut remaining = MAX_EMBED_CHARS - title.chars().count();
+                let visible = visible_emails(&emails, redacted);
+                let mut fields = Vec::new();
+                for email in visible.iter().take(MAX_FIELDS) {
+                    let name = truncate(&email.sender, MAX_SENDER_CHARS);
+                    let value = truncate(&email.subject, MAX_SUBJECT_CHARS);
+                    let len = name.chars().count() + value.chars().count();
+                    // Keep room for the description listing the emails which didn't fit.
+                    if len + 32 > remaining {
+                        break;
+                    }
+                    remaining -= len;
+                    fields.push(json!({"name": name, "value": value}));
+                }
+
+                let hidden = visible.len() - fields.len();
+                let mut embed = json!({
+                    "title": title,
+                    "color": INFO_COLOR,
+                    "fields": fields,
+                });
+                if hidden != 0 {
+                    embed["description"] = json!(format!("... and {hidden} more"));
+                }
+                if let Some(url) = open_url {
+                    embed["url"] = json!(url);
+                }
+                embed
+            }
+            Event::AccountLoggedOut { account, .. } => json!({
+                "title": truncate(&format!("`{account}` logged out or session expired"), MAX_TITLE_CHARS),
+                "color": INFO_COLOR,
+            }),
+            Event::AccountError { account, error, .. } => {
+                error_embed(&format!("`{account}` encountered an error"), &error)
+            }
+            Event::ConfigError { error } => error_embed("Server Config Error", &error),
+            Event::Error { error } => error_embed("Server Error", &error),
+            Event::Info { title, message } => {
+                let mut embed = json!({
+                    "title": truncate(&title, MAX_TITLE_CHARS),
+                    "color": INFO_COLOR,
+                });
+                if let Some(message) = message {
+                    embed["description"] = json!(truncate(&message, MAX_DESCRIPTION_CHARS));
+                }
+                embed
+            }
+        };
+
+        self.send(embed)
+    }
+
+    fn send(&mut self, embed: Value) -> bool {
+        let payload = json!({ "embeds": [embed] });
+        let request = self
+            .agent
+            .post(self.webhook_url.expose_secret())
+            .set("Content-Type", "application/json");
+        send_with_breaker(
+            "discord",
+            &self.name,
+            &mut self.breaker,
+            &self.shutdown,
+            &request,
+            payload.to_string().as_bytes(),
+        )
+    }
+}
+
+fn error_embed(title: &str, error: &str) -> Value {
+    json!({
+        "title": truncate(title, MAX_TITLE_CHARS),
+        "description": truncate(error, MAX_DESCRIPTION_CHARS),
+        "color": ERROR_COLOR,
+    })
+}
@@ -0,0 +1,157 @@
+//! Muting of the error notifications of accounts which keep failing, e.g. after their password
+//! was changed.
+use crate::notifiers::Event;
+use log::{debug, info};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Consecutive failures of each account, shared by the notifiers created on config reloads.
+#[derive(Default)]
+pub struct AccountFailures(Mutex<HashMap<String, Failures>>);
+
+#[derive(Default)]
+struct Failures {
+    consecutive: u32,
+    /// Error notifications are muted until then.
+    muted_until: Option<Instant>,
+}
+
+impl AccountFailures {
+    /// The account polled without errors, its next error is notified again.
+    pub fn record_success(&self, account: &str) {
+        if let Some(failures) = self.0.lock().unwrap().remove(account) {
+            if failures.muted_until.is_some() {
+                info!(
+                    "{account} recovered after {} failure(s)",
+                    failures.consecutive
+                );
+            }
+        }
+    }
+}
+
+/// Sends the first `max_notifications` consecutive errors of an account, then mutes its errors
+/// for `cooldown` after which a reminder is sent. The error starting the muting and the
+/// reminders are escalated to the highest priority of the notifiers.
+#[derive(Clone, Default)]
+pub struct ErrorEscalation {
+    /// 0 notifies every error.
+    max_notifications: u32,
+    cooldown: Duration,
+    failures: Arc<AccountFailures>,
+}
+
+impl ErrorEscalation {
+    pub fn new(max_notifications: u32, cooldown: Duration, failures: Arc<AccountFailures>) -> Self {
+        Self {
+            max_notifications,
+            cooldown,
+            failures,
+        }
+    }
+
+    /// Returns false if the notification of `event` should be muted. The last error before the
+    /// errors are muted and the reminders are marked as escalated.
+    pub fn apply(&self, event: &mut Event) -> bool {
+        if self.max_notifications == 0 {
+            return true;
+        }
+        let account = match event {
+            Event::AccountError { account, .. } | Event::AccountLoggedOut { account, .. } => {
+                account.clone()
+            }
+            _ => return true,
+        };
+
+        let now = Instant::now();
+        let mut accounts = self.failures.0.lock().unwrap();
+        let failures = accounts.entry(account.clone()).or_default();
+        failures.consecutive += 1;
+        if failures.consecutive < self.max_notifications {
+            return true;
+        }
+        if matches!(failures.muted_until, Some(until) if now < until) {
+            debug!(
+                "Muting error notification of {account}, failed {} times in a row",
+                failures.consecutive
+            );
+            return false;
+        }
+
+        failures.muted_until = Some(now + self.cooldown);
+        match event {
+            Event::AccountError {
+                error, escalated, ..
+            } => {
+                *error = format!(
+                    "{error}\nFailed {} times in a row, further errors are muted for {} minutes",
+                    failures.consecutive,
+                    self.cooldown.as_secs() / 60
+                );
+                *escalated = true;
+            }
+            Event::AccountLoggedOut { escalated, .. } => *escalated = true,
+            _ => {}
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error() -> Event {
+        Event::AccountError {
+            account: "foo@bar.com".to_string(),
+            error: "Connection refused".to_string(),
+            escalated: false,
+        }
+    }
+
+    fn is_escalated(event: &Event) -> bool {
+        matches!(
+            event,
+            Event::AccountError {
+                escalated: true,
+                ..
+            }
+        )
+    }
+
+    #[test]
+    fn escalates_the_error_which_starts_the_muting() {
+        let escalation = ErrorEscalation::new(
+            2,
+            Duration::from_secs(3600),
+            Arc::new(AccountFailures::default()),
+        );
+
+        let mut first = error();
+        assert!(escalation.apply(&mut first));
+        assert!(!is_escalated(&first));
+
+        let mut second = error();
+        assert!(escalation.apply(&mut second));
+        assert!(is_escalated(&second));
+
+        let mut third = error();
+        assert!(!escalation.apply(&mut third));
+    }
+
+    #[test]
+    fn escalates_after_a_success() {
+        let failures = Arc::new(AccountFailures::default());
+        let escalation = ErrorEscalation::new(1, Duration::from_secs(3600), failures.clone());
+
+        let mut event = error();
+        assert!(escalation.apply(&mut event));
+        assert!(is_escalated(&event));
+
+        failures.record_success("foo@bar.com");
+        let mut event = error();
+        assert!(escalation.apply(&mut event));
+        assert!(is_escalated(&event));
+    }
+}
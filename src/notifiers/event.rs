@@ -0,0 +1,197 @@
+use chrono::{Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use you_have_mail_common::Notification;
+
+/// Sender and subject of a new email.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Email {
+    pub sender: String,
+    pub subject: String,
+    /// Unix time in seconds of the poll which found the email, 0 if unknown.
+    #[serde(default)]
+    pub received_at: i64,
+    /// Account which received the email, merged events list the accounts of all their emails.
+    /// Empty for emails recorded by older versions.
+    #[serde(default)]
+    pub account: String,
+    /// Backend of `account`.
+    #[serde(default)]
+    pub backend: String,
+    /// Identifies the email in the record of the notified emails, set before the sender and
+    /// subject are masked so it can be recorded once the email is actually notified.
+    #[serde(skip)]
+    pub seen_hash: Option<String>,
+    /// ntfy priority of the email by notifier name, from the notifier's `priority_rules`
+    /// evaluated before the sender and subject are masked.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub priorities: BTreeMap<String, u8>,
+}
+
+impl Email {
+    /// Local time the email was received, with the date if it wasn't today. Empty if unknown.
+    pub fn received_time(&self) -> String {
+        let received = match Local.timestamp_opt(self.received_at, 0).single() {
+            Some(received) if self.received_at != 0 => received,
+            _ => return String::new(),
+        };
+        if received.date_naive() == Local::now().date_naive() {
+            received.format("%H:%M").to_string()
+        } else {
+            received.format("%Y-%m-%d %H:%M").to_string()
+        }
+    }
+}
+
+/// Owned version of the observer's [`Notification`] which can be enriched with the information
+/// from the CLI's config and moved to notifier threads.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    NewEmail {
+        /// Accounts of the emails, comma separated if there are more than one.
+        account: String,
+        /// Backends of the emails, comma separated if there are more than one.
+        backend: String,
+        emails: Vec<Email>,
+        /// Url to open when the notification is clicked.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        open_url: Option<String>,
+        /// Email details have been masked and only the count should be displayed.
+        redacted: bool,
+    },
+    AccountLoggedOut {
+        account: String,
+        /// The account keeps failing and its further errors are muted, notified with the
+        /// highest priority.
+        #[serde(default)]
+        escalated: bool,
+    },
+    AccountError {
+        account: String,
+        error: String,
+        /// See `AccountLoggedOut::escalated`.
+        #[serde(default)]
+        escalated: bool,
+    },
+    ConfigError {
+        error: String,
+    },
+    Error {
+        error: String,
+    },
+    /// Informational message generated by the CLI.
+    Info {
+        title: String,
+        message: Option<String>,
+    },
+}
+
+impl Event {
+    /// Names of all the kinds of events.
+    pub const KINDS: [&'static str; 6] = [
+        "new_email",
+        "account_logged_out",
+        "account_error",
+        "config_error",
+        "error",
+        "info",
+    ];
+
+    /// Name of the event, matches the `event` field of the serialized event.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Event::NewEmail { .. } => "new_email",
+            Event::AccountLoggedOut { .. } => "account_logged_out",
+            Event::AccountError { .. } => "account_error",
+            Event::ConfigError { .. } => "config_error",
+            Event::Error { .. } => "error",
+            Event::Info { .. } => "info",
+        }
+    }
+
+    /// Convert an observer notification, returns `None` for notifications which are not
+    /// forwarded to the notifiers.
+    pub fn from_notification(notification: &Notification) -> Option<Self> {
+        let event = match notification {
+            Notification::NewEmail {
+                account,
+                backend,
+                emails,
+            } => Event::NewEmail {
+                account: account.to_string(),
+                backend: backend.to_string(),
+                emails: emails
+                    .iter()
+                    .map(|e| Email {
+                        sender: e.sender.to_string(),
+                        subject: e.subject.to_string(),
+                        received_at: Local::now().timestamp(),
+                        account: account.to_string(),
+                        backend: backend.to_string(),
+                        seen_hash: None,
+                        priorities: Default::default(),
+                    })
+                    .collect(),
+                open_url: None,
+                redacted: false,
+            },
+            Notification::AccountLoggedOut(email) => Event::AccountLoggedOut {
+                account: email.to_string(),
+                escalated: false,
+            },
+            Notification::AccountError(email, error) => Event::AccountError {
+                account: email.to_string(),
+                error: error.to_string(),
+                escalated: false,
+            },
+            Notification::ConfigError(e) => Event::ConfigError {
+                error: e.to_string(),
+            },
+            Notification::Error(e) => Event::Error {
+                error: e.to_string(),
+            },
+            Notification::ProxyApplied(email, proxy) => Event::Info {
+                title: format!("{email} proxy changed"),
+                message: Some(if proxy.is_some() {
+                    "Connecting through the configured proxy".to_string()
+                } else {
+                    "Connecting without proxy".to_string()
+                }),
+            },
+            _ => return None,
+        };
+
+        Some(event)
+    }
+
+    /// Set the accounts and backends of a new email event from its emails, after they changed.
+    pub fn update_sources(&mut self) {
+        let Event::NewEmail {
+            account,
+            backend,
+            emails,
+            ..
+        } = self
+        else {
+            return;
+        };
+        // Without the account of every email the event's are all there is.
+        if emails.is_empty() || emails.iter().any(|e| e.account.is_empty()) {
+            return;
+        }
+        *account = join_unique(emails.iter().map(|e| e.account.as_str()));
+        *backend = join_unique(emails.iter().map(|e| e.backend.as_str()));
+    }
+}
+
+/// The distinct `values` in order, comma separated.
+fn join_unique<'a>(values: impl Iterator<Item = &'a str>) -> String {
+    let mut unique = Vec::new();
+    for value in values {
+        if !unique.contains(&value) {
+            unique.push(value);
+        }
+    }
+    unique.join(", ")
+}
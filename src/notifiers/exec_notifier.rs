@@ -0,0 +1,135 @@
+use crate::notifiers::format::visible_emails;
+use crate::notifiers::worker::{default_queue_size, WorkerNotifier};
+use crate::notifiers::{Event, Notifier, NotifierConfig, NotifierContext};
+use log::{debug, error};
+use serde::Deserialize;
+use std::process::{Command, Stdio};
+
+#[derive(Debug, Deserialize)]
+/// Configuration for a program run for each notification.
+pub struct ExecConfig {
+    pub name: String,
+    /// Only send the notifications of these accounts, all accounts if empty.
+    #[serde(default)]
+    pub accounts: Vec<String>,
+    /// Hide the email senders and subjects, only the number of new emails is sent.
+    #[serde(default)]
+    pub redact: bool,
+    /// Number of notifications waiting to be sent after which new ones are dropped.
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+impl NotifierConfig for ExecConfig {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn take_accounts(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.accounts)
+    }
+
+    fn redact(&self) -> bool {
+        self.redact
+    }
+
+    fn into_notifier(self, ctx: &NotifierContext) -> anyhow::Result<Box<dyn Notifier>> {
+        let state = ExecNotifier {
+            name: self.name.clone(),
+            command: self.command,
+            args: self.args,
+        };
+        let notifier =
+            WorkerNotifier::spawn("exec", self.name, self.queue_size, ctx, move |event| {
+                state.handle(&event)
+            })?;
+        Ok(Box::new(notifier))
+    }
+}
+
+/// Run a program for each notification, the details are passed as `YHM_*` environment
+/// variables.
+struct ExecNotifier {
+    name: String,
+    command: String,
+    args: Vec<String>,
+}
+
+impl ExecNotifier {
+    /// Returns true if the program exited successfully.
+    fn handle(&self, event: &Event) -> bool {
+        let mut command = Command::new(&self.command);
+        command
+            .args(&self.args)
+            .envs(env_vars(event))
+            .stdin(Stdio::null());
+
+        let output = match command.output() {
+            Ok(output) => output,
+            Err(e) => {
+                error!(
+                    "Failed to run exec notifier ({}) command {:?}: {e}",
+                    self.name, self.command
+                );
+                return false;
+            }
+        };
+
+        if output.status.success() {
+            debug!("Notification successfully passed to exec {}", self.name);
+            true
+        } else {
+            error!(
+                "Exec notifier ({}) command {:?} failed with {}: {}",
+                self.name,
+                self.command,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            false
+        }
+    }
+}
+
+/// Environment variables describing `event`. Subjects are separated by newlines and omitted
+/// for redacted notifications.
+fn env_vars(event: &Event) -> Vec<(&'static str, String)> {
+    let mut vars = vec![("YHM_EVENT", event.kind().to_string())];
+    match event {
+        Event::NewEmail {
+            account,
+            backend,
+            emails,
+            redacted,
+            ..
+        } => {
+            vars.push(("YHM_ACCOUNT", account.clone()));
+            vars.push(("YHM_BACKEND", backend.clone()));
+            vars.push(("YHM_COUNT", emails.len().to_string()));
+            let subjects = visible_emails(emails, *redacted)
+                .iter()
+                .map(|email| email.subject.as_str())
+                .collect::<Vec<_>>()
+                .join("\n");
+            vars.push(("YHM_SUBJECTS", subjects));
+        }
+        Event::AccountLoggedOut { account, .. } => {
+            vars.push(("YHM_ACCOUNT", account.clone()));
+        }
+        Event::AccountError { account, error, .. } => {
+            vars.push(("YHM_ACCOUNT", account.clone()));
+            vars.push(("YHM_MESSAGE", error.clone()));
+        }
+        Event::ConfigError { error } | Event::Error { error } => {
+            vars.push(("YHM_MESSAGE", error.clone()));
+        }
+        Event::Info { title, message } => match message {
+            Some(message) => vars.push(("YHM_MESSAGE", format!("{title}\n{message}"))),
+            None => vars.push(("YHM_MESSAGE", title.clone())),
+        },
+    }
+    vars
+}
@@ -0,0 +1,189 @@
+use crate::notifiers::format::visible_emails;
+use crate::notifiers::{Email, Event, Notifier, NotifierConfig, NotifierContext};
+use crate::utils::{append_user_file, create_dir_user_only};
+use anyhow::anyhow;
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(Debug, Deserialize)]
+/// Configuration for a file notifications are appended to.
+pub struct FileConfig {
+    pub name: String,
+    /// Only send the notifications of these accounts, all accounts if empty.
+    #[serde(default)]
+    pub accounts: Vec<String>,
+    /// Hide the email senders and subjects, only the number of new emails is sent.
+    #[serde(default)]
+    pub redact: bool,
+    pub path: PathBuf,
+    #[serde(default)]
+    pub format: FileFormat,
+}
+
+#[derive(Debug, Default, Deserialize, Copy, Clone, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FileFormat {
+    /// One line of text per notification.
+    #[default]
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+impl NotifierConfig for FileConfig {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn take_accounts(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.accounts)
+    }
+
+    fn redact(&self) -> bool {
+        self.redact
+    }
+
+    fn into_notifier(self, ctx: &NotifierContext) -> anyhow::Result<Box<dyn Notifier>> {
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            create_dir_user_only(parent)
+                .map_err(|e| anyhow!("Failed to create directory {parent:?}: {e}"))?;
+        }
+
+        Ok(Box::new(FileNotifier {
+            name: self.name,
+            path: self.path,
+            format: self.format,
+            ctx: ctx.clone(),
+        }))
+    }
+}
+
+/// Append notifications to a file, as an audit trail independent of the log.
+struct FileNotifier {
+    name: String,
+    path: PathBuf,
+    format: FileFormat,
+    ctx: NotifierContext,
+}
+
+/// Stable schema of the lines written in [`FileFormat::Json`].
+#[derive(Serialize)]
+struct FileRecord<'a> {
+    ts: String,
+    event: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    account: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backend: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    emails: Option<&'a [Email]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+impl FileNotifier {
+    fn render(&self, event: &Event) -> anyhow::Result<String> {
+        let ts = chrono::Local::now()
+            .format("%Y-%m-%dT%H:%M:%S%:z")
+            .to_string();
+        let line = match self.format {
+            FileFormat::Text => format!("{ts} {}", describe(event)),
+            FileFormat::Json => {
+                let mut record = FileRecord {
+                    ts,
+                    event: event.kind(),
+                    account: None,
+                    backend: None,
+                    emails: None,
+                    message: None,
+                };
+                match event {
+                    Event::NewEmail {
+                        account,
+                        backend,
+                        emails,
+                        redacted,
+                        ..
+                    } => {
+                        record.account = Some(account);
+                        record.backend = Some(backend);
+                        record.emails = Some(visible_emails(emails, *redacted));
+                    }
+                    Event::AccountLoggedOut { account, .. } => record.account = Some(account),
+                    Event::AccountError { account, error, .. } => {
+                        record.account = Some(account);
+                        record.message = Some(error.clone());
+                    }
+                    Event::ConfigError { error } | Event::Error { error } => {
+                        record.message = Some(error.clone());
+                    }
+                    Event::Info { title, message } => {
+                        record.message = Some(match message {
+                            Some(message) => format!("{title}\n{message}"),
+                            None => title.clone(),
+                        });
+                    }
+                }
+                serde_json::to_string(&record)
+                    .map_err(|e| anyhow!("Failed to serialize notification: {e}"))?
+            }
+        };
+        Ok(line)
+    }
+}
+
+impl Notifier for FileNotifier {
+    fn notify(&self, event: &Event) {
+        let delivered = match self.render(event).and_then(|mut line| {
+            line.push('\n');
+            append_user_file(&self.path, line.as_bytes())
+                .map_err(|e| anyhow!("Failed to write {:?}: {e}", self.path))
+        }) {
+            Ok(()) => true,
+            Err(e) => {
+                error!("File notifier ({}): {e}", self.name);
+                false
+            }
+        };
+        self.ctx
+            .record_delivery(&format!("file ({})", self.name), event, delivered);
+    }
+}
+
+/// Single line description of `event` for [`FileFormat::Text`].
+fn describe(event: &Event) -> String {
+    match event {
+        Event::NewEmail {
+            account,
+            backend,
+            emails,
+            redacted,
+            ..
+        } => {
+            let mut text = format!(
+                "Account {account} ({backend}) received {} new email(s)",
+                emails.len()
+            );
+            for email in visible_emails(emails, *redacted) {
+                text.push_str(&format!(
+                    " | Sender={} Subject={}",
+                    email.sender, email.subject
+                ));
+            }
+            text
+        }
+        Event::AccountLoggedOut { account, .. } => {
+            format!("Account {account} Logged out or Session Expired")
+        }
+        Event::AccountError { account, error, .. } => {
+            format!("Account {account} ran into an error: {error}")
+        }
+        Event::ConfigError { error } => format!("Configuration error: {error}"),
+        Event::Error { error } => format!("An error occurred: {error}"),
+        Event::Info { title, message } => match message {
+            Some(message) => format!("{title}: {}", message.replace('\n', " | ")),
+            None => title.clone(),
+        },
+    }
+}
@@ -0,0 +1,105 @@
+//! Suppression of new email notifications based on the sender and subject.
+use crate::notifiers::{Email, Event};
+use anyhow::anyhow;
+use log::debug;
+use regex::Regex;
+use serde::Deserialize;
+
+#[derive(Debug, Default, Clone, Deserialize)]
+/// Emails matching a `deny` rule are not notified. If `allow` rules are set, only emails matching
+/// one of them are notified.
+pub struct FiltersConfig {
+    #[serde(default)]
+    pub allow: Vec<FilterRuleConfig>,
+    #[serde(default)]
+    pub deny: Vec<FilterRuleConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+/// Matches an email when both the `sender` and `subject` regexes match, a missing regex matches
+/// everything.
+pub struct FilterRuleConfig {
+    pub sender: Option<String>,
+    pub subject: Option<String>,
+}
+
+struct FilterRule {
+    sender: Option<Regex>,
+    subject: Option<Regex>,
+}
+
+impl FilterRule {
+    fn new(config: FilterRuleConfig) -> anyhow::Result<Self> {
+        if config.sender.is_none() && config.subject.is_none() {
+            return Err(anyhow!("filter rule needs a sender or subject pattern"));
+        }
+
+        let compile = |pattern: Option<String>| {
+            pattern
+                .map(|p| Regex::new(&p).map_err(|e| anyhow!("invalid filter regex: {e}")))
+                .transpose()
+        };
+
+        Ok(Self {
+            sender: compile(config.sender)?,
+            subject: compile(config.subject)?,
+        })
+    }
+
+    fn matches(&self, email: &Email) -> bool {
+        let is_match = |regex: &Option<Regex>, value: &str| match regex {
+            Some(regex) => regex.is_match(value),
+            None => true,
+        };
+        is_match(&self.sender, &email.sender) && is_match(&self.subject, &email.subject)
+    }
+}
+
+/// Compiled [`FiltersConfig`].
+#[derive(Default)]
+pub struct Filters {
+    allow: Vec<FilterRule>,
+    deny: Vec<FilterRule>,
+}
+
+impl Filters {
+    pub fn new(config: FiltersConfig) -> anyhow::Result<Self> {
+        let compile = |rules: Vec<FilterRuleConfig>| {
+            rules
+                .into_iter()
+                .map(FilterRule::new)
+                .collect::<anyhow::Result<Vec<_>>>()
+        };
+
+        Ok(Self {
+            allow: compile(config.allow)?,
+            deny: compile(config.deny)?,
+        })
+    }
+
+    fn is_allowed(&self, email: &Email) -> bool {
+        (self.allow.is_empty() || self.allow.iter().any(|rule| rule.matches(email)))
+            && !self.deny.iter().any(|rule| rule.matches(email))
+    }
+
+    /// Remove the filtered emails from the event. Returns false if the event should not be
+    /// sent at all because all its emails were filtered.
+    pub fn apply(&self, event: &mut Event) -> bool {
+        let Event::NewEmail {
+            account, emails, ..
+        } = event
+        else {
+            return true;
+        };
+
+        let count = emails.len();
+        emails.retain(|email| self.is_allowed(email));
+        if emails.len() != count {
+            debug!(
+                "Filtered {} of {count} new email(s) for {account}",
+                count - emails.len()
+            );
+        }
+        !emails.is_empty()
+    }
+}
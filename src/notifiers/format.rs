@@ -0,0 +1,234 @@
+//! Rendering helpers shared by the notifiers.
+use crate::notifiers::{Email, Event, Notifier};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Text used in place of the sender and subject of redacted emails.
+const REDACTED: &str = "(hidden)";
+
+/// Controls whether email senders and subjects are hidden from notifications. The global
+/// setting can be toggled at runtime, accounts listed explicitly are always redacted.
+#[derive(Clone, Default)]
+pub struct Redaction {
+    enabled: Arc<AtomicBool>,
+    accounts: Arc<HashSet<String>>,
+}
+
+impl Redaction {
+    pub fn new(enabled: bool, accounts: HashSet<String>) -> Self {
+        Self {
+            enabled: Arc::new(AtomicBool::new(enabled)),
+            accounts: Arc::new(accounts),
+        }
+    }
+
+    /// Flip the global redaction setting, returns the new value.
+    pub fn toggle(&self) -> bool {
+        !self.enabled.fetch_xor(true, Ordering::SeqCst)
+    }
+
+    pub fn is_redacted(&self, account: &str) -> bool {
+        self.enabled.load(Ordering::SeqCst) || self.accounts.contains(account)
+    }
+
+    /// Mask the email details of the event if redaction applies to its account.
+    pub fn apply(&self, event: &mut Event) {
+        if let Event::NewEmail { account, .. } = event {
+            if self.is_redacted(account) {
+                redact(event);
+            }
+        }
+    }
+}
+
+/// Mask the email details of a new email event.
+fn redact(event: &mut Event) {
+    if let Event::NewEmail {
+        emails, redacted, ..
+    } = event
+    {
+        *redacted = true;
+        for email in emails {
+            email.sender = REDACTED.to_string();
+            email.subject = REDACTED.to_string();
+        }
+    }
+}
+
+/// Notifier which only receives the number of new emails.
+struct RedactedNotifier {
+    inner: Box<dyn Notifier>,
+}
+
+impl Notifier for RedactedNotifier {
+    fn notify(&self, event: &Event) {
+        if !matches!(
+            event,
+            Event::NewEmail {
+                redacted: false,
+                ..
+            }
+        ) {
+            self.inner.notify(event);
+            return;
+        }
+
+        let mut event = event.clone();
+        redact(&mut event);
+        self.inner.notify(&event);
+    }
+
+    fn flush(&self, timeout: Duration) -> bool {
+        self.inner.flush(timeout)
+    }
+
+    fn shutdown(&self, timeout: Duration) -> bool {
+        self.inner.shutdown(timeout)
+    }
+}
+
+/// Hide the email details from `notifier` if `redact` is set.
+pub fn redacted(notifier: Box<dyn Notifier>, redact: bool) -> Box<dyn Notifier> {
+    if !redact {
+        return notifier;
+    }
+
+    Box::new(RedactedNotifier { inner: notifier })
+}
+
+/// Text displayed in place of blank email fields.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Placeholders {
+    #[serde(default = "default_sender_placeholder")]
+    pub sender: String,
+    #[serde(default = "default_subject_placeholder")]
+    pub subject: String,
+}
+
+impl Default for Placeholders {
+    fn default() -> Self {
+        Self {
+            sender: default_sender_placeholder(),
+            subject: default_subject_placeholder(),
+        }
+    }
+}
+
+fn default_sender_placeholder() -> String {
+    "(unknown sender)".to_string()
+}
+
+fn default_subject_placeholder() -> String {
+    "(no subject)".to_string()
+}
+
+impl Placeholders {
+    /// Replace empty or whitespace only senders and subjects of the event's emails.
+    pub fn apply(&self, event: &mut Event) {
+        if let Event::NewEmail { emails, .. } = event {
+            for email in emails {
+                if email.sender.trim().is_empty() {
+                    email.sender = self.sender.clone();
+                }
+                if email.subject.trim().is_empty() {
+                    email.subject = self.subject.clone();
+                }
+            }
+        }
+    }
+}
+
+/// Emails whose details can be displayed, empty if the notification is redacted and should
+/// only report the number of emails.
+pub fn visible_emails(emails: &[Email], redacted: bool) -> &[Email] {
+    if redacted {
+        &[]
+    } else {
+        emails
+    }
+}
+
+/// Shorten `text` to at most `max_chars` characters, ending with an ellipsis if it was cut.
+#[cfg(any(feature = "notifier-discord", feature = "notifier-slack"))]
+pub fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut truncated = text
+        .chars()
+        .take(max_chars.saturating_sub(1))
+        .collect::<String>();
+    truncated.push('…');
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_email(sender: &str, subject: &str) -> Event {
+        Event::NewEmail {
+            account: "foo@bar.com".to_string(),
+            backend: "Proton Mail".to_string(),
+            emails: vec![Email {
+                sender: sender.to_string(),
+                subject: subject.to_string(),
+                received_at: 0,
+                account: "foo@bar.com".to_string(),
+                backend: "Proton Mail".to_string(),
+                seen_hash: None,
+                priorities: Default::default(),
+            }],
+            open_url: None,
+            redacted: false,
+        }
+    }
+
+    fn first_email(event: &Event) -> (&str, &str) {
+        match event {
+            Event::NewEmail { emails, .. } => (&emails[0].sender, &emails[0].subject),
+            _ => panic!("not a new email event"),
+        }
+    }
+
+    #[test]
+    fn placeholders_replace_empty_fields() {
+        let mut event = new_email("", "");
+        Placeholders::default().apply(&mut event);
+        assert_eq!(first_email(&event), ("(unknown sender)", "(no subject)"));
+    }
+
+    #[test]
+    fn placeholders_replace_whitespace_fields() {
+        let mut event = new_email("  ", "\t\n");
+        let placeholders = Placeholders {
+            sender: "someone".to_string(),
+            subject: "nothing".to_string(),
+        };
+        placeholders.apply(&mut event);
+        assert_eq!(first_email(&event), ("someone", "nothing"));
+    }
+
+    #[test]
+    fn placeholders_keep_non_empty_fields() {
+        let mut event = new_email("alice@bar.com", "");
+        Placeholders::default().apply(&mut event);
+        assert_eq!(first_email(&event), ("alice@bar.com", "(no subject)"));
+
+        let mut event = new_email("", "Hello");
+        Placeholders::default().apply(&mut event);
+        assert_eq!(first_email(&event), ("(unknown sender)", "Hello"));
+    }
+
+    #[test]
+    fn placeholders_ignore_other_events() {
+        let mut event = Event::Error {
+            error: String::new(),
+        };
+        Placeholders::default().apply(&mut event);
+        assert!(matches!(event, Event::Error { error } if error.is_empty()));
+    }
+}
@@ -0,0 +1,194 @@
+use crate::cfg::deserialize_secret;
+use crate::notifiers::circuit_breaker::{BreakerConfig, CircuitBreaker};
+use crate::notifiers::format::visible_emails;
+use crate::notifiers::http::{send_with_breaker, HttpConfig};
+use crate::notifiers::template::MessageTemplates;
+use crate::notifiers::worker::{default_queue_size, WorkerNotifier};
+use crate::notifiers::{Event, Notifier, NotifierConfig, NotifierContext, Shutdown};
+use serde::Deserialize;
+use serde_json::json;
+use std::path::PathBuf;
+use you_have_mail_common::{ExposeSecret, Secret};
+
+const NEW_EMAIL_PRIORITY: u8 = 5;
+const ERROR_PRIORITY: u8 = 8;
+const INFO_PRIORITY: u8 = 4;
+/// Highest Gotify priority, of the escalated account errors.
+const ESCALATED_PRIORITY: u8 = 10;
+
+#[derive(Debug, Deserialize)]
+/// Configuration for a Gotify server.
+pub struct GotifyConfig {
+    pub name: String,
+    /// Only send the notifications of these accounts, all accounts if empty.
+    #[serde(default)]
+    pub accounts: Vec<String>,
+    /// Hide the email senders and subjects, only the number of new emails is sent.
+    #[serde(default)]
+    pub redact: bool,
+    /// Number of notifications waiting to be sent after which new ones are dropped.
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
+    pub url: String,
+    #[serde(deserialize_with = "deserialize_secret")]
+    pub app_token: Secret<String>,
+    /// PEM file with additional root certificates, overrides the global `ca_bundle`.
+    pub ca_bundle: Option<PathBuf>,
+    /// Timeouts and User-Agent of the requests.
+    #[serde(flatten)]
+    pub http: HttpConfig,
+    /// Pause the requests after repeated failures.
+    #[serde(flatten)]
+    pub breaker: BreakerConfig,
+    /// Title of the new email notifications, see the `Message Templates` section of the README.
+    pub title_template: Option<String>,
+    /// Line of each email in the body of the new email notifications.
+    pub body_template: Option<String>,
+}
+
+impl NotifierConfig for GotifyConfig {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn take_accounts(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.accounts)
+    }
+
+    fn redact(&self) -> bool {
+        self.redact
+    }
+
+    fn into_notifier(self, ctx: &NotifierContext) -> anyhow::Result<Box<dyn Notifier>> {
+        let ca_bundle = self.ca_bundle.or_else(|| ctx.ca_bundle.clone());
+        let agent = ctx
+            .http_agents
+            .agent(&self.http.options(ca_bundle, &self.url))?;
+        let mut state = GotifyNotifier {
+            name: self.name.clone(),
+            agent,
+            shutdown: ctx.shutdown.clone(),
+            url: format!("{}/message", self.url.trim_end_matches('/')),
+            app_token: self.app_token,
+            breaker: self.breaker.circuit_breaker("gotify", &self.name, ctx),
+            templates: MessageTemplates::new(
+                self.title_template,
+                self.body_template,
+                "{sender}: {subject}",
+            ),
+        };
+        let notifier =
+            WorkerNotifier::spawn("gotify", self.name, self.queue_size, ctx, move |event| {
+                state.handle(event)
+            })?;
+        Ok(Box::new(notifier))
+    }
+}
+
+/// Send notifications to a Gotify server.
+struct GotifyNotifier {
+    name: String,
+    agent: ureq::Agent,
+    shutdown: Shutdown,
+    url: String,
+    app_token: Secret<String>,
+    breaker: CircuitBreaker,
+    templates: MessageTemplates,
+}
+
+impl GotifyNotifier {
+    /// Returns true if the notification was posted.
+    fn handle(&mut self, event: Event) -> bool {
+        match event {
+            Event::NewEmail {
+                account,
+                emails,
+                open_url,
+                redacted,
+                ..
+            } => {
+                let visible = visible_emails(&emails, redacted);
+                let title = self.templates.title(&account, &emails, visible);
+                let message = self
+                    .templates
+                    .body_lines(&account, emails.len(), visible)
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                let message = if message.is_empty() {
+                    title.clone()
+                } else {
+                    message
+                };
+                self.send(title, message, NEW_EMAIL_PRIORITY, open_url)
+            }
+            Event::AccountLoggedOut { account, escalated } => {
+                let title = format!("{account} logged out or session expired");
+                let priority = if escalated {
+                    ESCALATED_PRIORITY
+                } else {
+                    INFO_PRIORITY
+                };
+                self.send(title.clone(), title, priority, None)
+            }
+            Event::AccountError {
+                account,
+                error,
+                escalated,
+            } => self.send(
+                format!("{account} encountered an error"),
+                error,
+                if escalated {
+                    ESCALATED_PRIORITY
+                } else {
+                    ERROR_PRIORITY
+                },
+                None,
+            ),
+            Event::ConfigError { error } => self.send(
+                "Server Config Error".to_string(),
+                error,
+                ERROR_PRIORITY,
+                None,
+            ),
+            Event::Error { error } => {
+                self.send("Server Error".to_string(), error, ERROR_PRIORITY, None)
+            }
+            Event::Info { title, message } => {
+                let message = message.unwrap_or_else(|| title.clone());
+                self.send(title, message, INFO_PRIORITY, None)
+            }
+        }
+    }
+
+    fn send(
+        &mut self,
+        title: String,
+        message: String,
+        priority: u8,
+        open_url: Option<String>,
+    ) -> bool {
+        let mut payload = json!({
+            "title": title,
+            "message": message,
+            "priority": priority,
+        });
+        if let Some(url) = open_url {
+            payload["extras"] = json!({"client::notification": {"click": {"url": url}}});
+        }
+
+        // The token is sent as header rather than query parameter to keep it out of logs.
+        let request = self
+            .agent
+            .post(&self.url)
+            .set("X-Gotify-Key", self.app_token.expose_secret())
+            .set("Content-Type", "application/json");
+        send_with_breaker(
+            "gotify",
+            &self.name,
+            &mut self.breaker,
+            &self.shutdown,
+            &request,
+            payload.to_string().as_bytes(),
+        )
+    }
+}
@@ -0,0 +1,494 @@
+//! Shared setup of the HTTP agents used by the notifiers.
+use crate::backoff::{Backoff, BackoffConfig};
+use crate::notifiers::circuit_breaker::CircuitBreaker;
+use crate::notifiers::Shutdown;
+use anyhow::anyhow;
+use log::{debug, error, info, warn};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Connection settings shared by the configs of the HTTP notifiers.
+#[derive(Debug, Clone, Deserialize)]
+pub struct HttpConfig {
+    /// Time in seconds to wait for the connection to the server.
+    #[serde(default = "default_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+    /// Time in seconds a request may take, including the connection.
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// User-Agent header of the requests, ureq's default if not set.
+    pub user_agent: Option<String>,
+    /// Proxy the requests are sent through, e.g. `http://proxy:3128`. Taken from the
+    /// `HTTPS_PROXY`, `HTTP_PROXY` and `NO_PROXY` environment variables if not set.
+    pub proxy: Option<String>,
+}
+
+impl HttpConfig {
+    /// Options of the agent sending requests to `url`, `ca_bundle` is the notifier's or the
+    /// global CA bundle.
+    pub fn options(&self, ca_bundle: Option<PathBuf>, url: &str) -> HttpOptions {
+        HttpOptions {
+            ca_bundle,
+            connect_timeout: Duration::from_secs(self.connect_timeout_secs),
+            timeout: Duration::from_secs(self.timeout_secs),
+            user_agent: self.user_agent.clone(),
+            proxy: self.proxy.clone().or_else(|| proxy_from_env(url)),
+        }
+    }
+}
+
+/// Proxy of `url` set in the environment with the same variables as curl.
+fn proxy_from_env(url: &str) -> Option<String> {
+    let (scheme, rest) = url.split_once("://")?;
+    let host = url_host(rest);
+    if let Some(no_proxy) = env_var("NO_PROXY") {
+        let excluded = no_proxy.split(',').map(str::trim).any(|pattern| {
+            let pattern = pattern.trim_start_matches('.');
+            pattern == "*"
+                || host.eq_ignore_ascii_case(pattern)
+                || host
+                    .to_ascii_lowercase()
+                    .ends_with(&format!(".{}", pattern.to_ascii_lowercase()))
+        });
+        if excluded {
+            return None;
+        }
+    }
+
+    let variable = match scheme {
+        "https" => "HTTPS_PROXY",
+        _ => "HTTP_PROXY",
+    };
+    env_var(variable).or_else(|| env_var("ALL_PROXY"))
+}
+
+/// Host of the url without its scheme, such as `user@host:port/path`.
+fn url_host(url: &str) -> &str {
+    let authority = url.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = authority.rsplit('@').next().unwrap_or_default();
+    match host.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    }
+}
+
+/// Value of the upper or lower case variable `name`, if not empty.
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name)
+        .or_else(|_| std::env::var(name.to_ascii_lowercase()))
+        .ok()
+        .filter(|value| !value.is_empty())
+}
+
+const fn default_connect_timeout_secs() -> u64 {
+    60
+}
+
+const fn default_timeout_secs() -> u64 {
+    120
+}
+
+/// Options for the HTTP agent a notifier uses.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HttpOptions {
+    /// PEM file with additional root certificates to trust.
+    pub ca_bundle: Option<PathBuf>,
+    pub connect_timeout: Duration,
+    pub timeout: Duration,
+    pub user_agent: Option<String>,
+    pub proxy: Option<String>,
+}
+
+/// HTTP agents shared by the notifiers, one per set of options, so notifiers don't each load
+/// the certificates and build their own TLS config.
+#[derive(Clone, Default)]
+pub struct AgentPool(Arc<Mutex<HashMap<HttpOptions, ureq::Agent>>>);
+
+impl AgentPool {
+    pub fn agent(&self, options: &HttpOptions) -> anyhow::Result<ureq::Agent> {
+        let mut agents = self.0.lock().unwrap();
+        if let Some(agent) = agents.get(options) {
+            return Ok(agent.clone());
+        }
+        let agent = new_agent(options)?;
+        agents.insert(options.clone(), agent.clone());
+        Ok(agent)
+    }
+}
+
+fn new_agent(options: &HttpOptions) -> anyhow::Result<ureq::Agent> {
+    // Connections are not kept alive, the servers usually close them long before the next poll.
+    let mut builder = ureq::builder()
+        .timeout_connect(options.connect_timeout)
+        .timeout(options.timeout)
+        .max_idle_connections(0);
+    if let Some(user_agent) = &options.user_agent {
+        builder = builder.user_agent(user_agent);
+    }
+    if let Some(proxy) = &options.proxy {
+        let proxy = ureq::Proxy::new(proxy).map_err(|e| anyhow!("Invalid proxy '{proxy}': {e}"))?;
+        builder = builder.proxy(proxy);
+    }
+
+    if let Some(ca_bundle) = &options.ca_bundle {
+        builder = builder.tls_config(Arc::new(tls_config_with_bundle(ca_bundle)?));
+    }
+
+    Ok(builder.build())
+}
+
+/// TLS config trusting the default web roots and the certificates in `path`.
+fn tls_config_with_bundle(path: &Path) -> anyhow::Result<rustls::ClientConfig> {
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+        rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+            ta.subject,
+            ta.spki,
+            ta.name_constraints,
+        )
+    }));
+
+    let file =
+        std::fs::File::open(path).map_err(|e| anyhow!("Failed to open CA bundle {path:?}: {e}"))?;
+    let certs = rustls_pemfile::certs(&mut std::io::BufReader::new(file))
+        .map_err(|e| anyhow!("Failed to parse CA bundle {path:?}: {e}"))?;
+    if certs.is_empty() {
+        return Err(anyhow!(
+            "CA bundle {path:?} does not contain any certificates"
+        ));
+    }
+
+    for cert in certs {
+        roots
+            .add(&rustls::Certificate(cert))
+            .map_err(|e| anyhow!("Invalid certificate in CA bundle {path:?}: {e}"))?;
+    }
+
+    Ok(rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth())
+}
+
+/// Describe a failed request for the logs, includes the response body of error statuses.
+pub fn describe_error(e: ureq::Error) -> String {
+    match e {
+        ureq::Error::Status(code, response) => {
+            let response_body = match response.into_string() {
+                Ok(s) => s,
+                Err(_) => "Failed to get response body".to_string(),
+            };
+            format!("HttpCode={code} Response={response_body}")
+        }
+        ureq::Error::Transport(e) => format!("Transport error={e}"),
+    }
+}
+
+/// Longest time in total a request waits for the rate limits of the server to expire.
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(5 * 60);
+
+/// Wait before retrying a rate limited request if the server doesn't say how long.
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(10);
+
+/// Send `request` with `body`. When the server answers 429 Too Many Requests, the request is
+/// sent again once the delay of its `Retry-After` header elapsed, until the total wait would
+/// exceed [`MAX_RATE_LIMIT_WAIT`] or the CLI exits.
+fn send_rate_limited(
+    kind: &str,
+    name: &str,
+    shutdown: &Shutdown,
+    request: &ureq::Request,
+    body: &[u8],
+) -> Result<ureq::Response, Box<ureq::Error>> {
+    let mut waited = Duration::ZERO;
+    loop {
+        let result = request.clone().send_bytes(body).map_err(Box::new);
+        let Some(delay) = result.as_ref().err().and_then(|e| retry_after(e)) else {
+            return result;
+        };
+        if waited + delay > MAX_RATE_LIMIT_WAIT {
+            warn!(
+                "{kind} {name} is rate limited for {} more seconds, giving up",
+                delay.as_secs()
+            );
+            return result;
+        }
+        warn!(
+            "{kind} {name} is rate limited, retrying in {} seconds",
+            delay.as_secs()
+        );
+        if !shutdown.sleep(delay) {
+            return result;
+        }
+        waited += delay;
+    }
+}
+
+/// Delay requested by a 429 Too Many Requests response, in seconds or as an HTTP date.
+fn retry_after(e: &ureq::Error) -> Option<Duration> {
+    let ureq::Error::Status(429, response) = e else {
+        return None;
+    };
+    let Some(value) = response.header("Retry-After").map(str::trim) else {
+        return Some(DEFAULT_RETRY_AFTER);
+    };
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    match chrono::DateTime::parse_from_rfc2822(value) {
+        Ok(date) => Some(
+            (date.with_timezone(&chrono::Utc) - chrono::Utc::now())
+                .to_std()
+                .unwrap_or_default(),
+        ),
+        Err(_) => Some(DEFAULT_RETRY_AFTER),
+    }
+}
+
+/// Whether a failed request may succeed when retried: transport errors and server errors.
+fn is_transient(e: &ureq::Error) -> bool {
+    match e {
+        ureq::Error::Status(code, _) => *code >= 500,
+        ureq::Error::Transport(_) => true,
+    }
+}
+
+/// Upper bound in seconds for the delay between two retries of [`send_with_retries`].
+const MAX_RETRY_DELAY: u64 = 30;
+
+/// Send `request` with `body` through [`send_rate_limited`], unless `breaker` paused the
+/// requests of the notifier after repeated failures. Returns true if the request succeeded.
+#[cfg(any(
+    feature = "notifier-telegram",
+    feature = "notifier-hass",
+    feature = "notifier-discord",
+    feature = "notifier-slack",
+    feature = "notifier-pushbullet",
+    feature = "notifier-signal",
+    feature = "notifier-gotify",
+    feature = "notifier-webhook"
+))]
+pub fn send_with_breaker(
+    kind: &str,
+    name: &str,
+    breaker: &mut CircuitBreaker,
+    shutdown: &Shutdown,
+    request: &ureq::Request,
+    body: &[u8],
+) -> bool {
+    send_with_retries(kind, name, breaker, shutdown, request, body, 0)
+}
+
+/// [`send_with_breaker`], retrying the requests failing with a transient error up to
+/// `max_retries` times, waiting 1s, 2s, 4s... up to [`MAX_RETRY_DELAY`] in between.
+pub fn send_with_retries(
+    kind: &str,
+    name: &str,
+    breaker: &mut CircuitBreaker,
+    shutdown: &Shutdown,
+    request: &ureq::Request,
+    body: &[u8],
+    max_retries: u32,
+) -> bool {
+    if !breaker.allow() {
+        warn!("Dropping notification, {kind} {name} is paused after repeated failures");
+        return false;
+    }
+
+    let mut backoff = Backoff::new(BackoffConfig {
+        initial_delay: 1,
+        max_delay: MAX_RETRY_DELAY,
+        multiplier: 2.0,
+        max_retries,
+    });
+    let mut result = send_rate_limited(kind, name, shutdown, request, body);
+    while let Err(e) = &result {
+        // A zero `max_retries` means unlimited for `Backoff`, but no retries here.
+        if max_retries == 0 || !is_transient(e) {
+            break;
+        }
+        let Some(delay) = backoff.next_delay() else {
+            break;
+        };
+        warn!(
+            "Failed to post {kind} request ({name}), retrying in {} seconds",
+            delay.as_secs()
+        );
+        if !shutdown.sleep(delay) {
+            debug!("Exiting, not retrying {kind} request ({name})");
+            break;
+        }
+        result = send_rate_limited(kind, name, shutdown, request, body);
+    }
+
+    match result {
+        Ok(_) => {
+            debug!("Notification successfully sent to {kind} {name}");
+            if breaker.record_success() {
+                info!("{kind} {name} recovered, resuming notifications");
+            }
+            true
+        }
+        Err(e) => {
+            let mut description = describe_error(*e);
+            // Transport errors include the url, the path holds the token of some services.
+            if let Ok(url) = request.request_url() {
+                if url.path() != "/" {
+                    description = description.replace(url.path(), "/<redacted>");
+                }
+            }
+            error!("Failed to post {kind} request ({name}): {description}");
+            if breaker.record_failure() {
+                warn!(
+                    "{kind} {name} failed repeatedly, pausing notifications for {} seconds",
+                    breaker.cooldown().as_secs()
+                );
+            }
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status_error(response: &str) -> ureq::Error {
+        let response = response.parse::<ureq::Response>().unwrap();
+        ureq::Error::Status(response.status(), response)
+    }
+
+    #[test]
+    fn retry_after_seconds() {
+        let e = status_error("HTTP/1.1 429 Too Many Requests\r\nRetry-After: 42\r\n\r\n");
+        assert_eq!(retry_after(&e), Some(Duration::from_secs(42)));
+    }
+
+    #[test]
+    fn retry_after_http_date() {
+        let date = (chrono::Utc::now() + chrono::Duration::seconds(120)).to_rfc2822();
+        let e = status_error(&format!(
+            "HTTP/1.1 429 Too Many Requests\r\nRetry-After: {date}\r\n\r\n"
+        ));
+        let delay = retry_after(&e).unwrap();
+        assert!(delay > Duration::from_secs(110) && delay <= Duration::from_secs(120));
+    }
+
+    #[test]
+    fn retry_after_past_http_date() {
+        let e = status_error(
+            "HTTP/1.1 429 Too Many Requests\r\nRetry-After: Wed, 21 Oct 2015 07:28:00 GMT\r\n\r\n",
+        );
+        assert_eq!(retry_after(&e), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn retry_after_missing_or_invalid() {
+        let e = status_error("HTTP/1.1 429 Too Many Requests\r\n\r\n");
+        assert_eq!(retry_after(&e), Some(DEFAULT_RETRY_AFTER));
+        let e = status_error("HTTP/1.1 429 Too Many Requests\r\nRetry-After: soon\r\n\r\n");
+        assert_eq!(retry_after(&e), Some(DEFAULT_RETRY_AFTER));
+    }
+
+    #[test]
+    fn retry_after_only_for_rate_limits() {
+        let e = status_error("HTTP/1.1 503 Service Unavailable\r\nRetry-After: 42\r\n\r\n");
+        assert_eq!(retry_after(&e), None);
+    }
+
+    #[test]
+    fn url_host_strips_credentials_port_and_path() {
+        assert_eq!(url_host("ntfy.sh/topic"), "ntfy.sh");
+        assert_eq!(url_host("user:pass@ntfy.sh:8080/topic"), "ntfy.sh");
+        assert_eq!(url_host("ntfy.sh?auth=token"), "ntfy.sh");
+        assert_eq!(url_host("ntfy.sh#fragment"), "ntfy.sh");
+        assert_eq!(url_host("[::1]:8080/topic"), "::1");
+        assert_eq!(url_host("user@[fe80::1]/topic"), "fe80::1");
+        assert_eq!(url_host(""), "");
+    }
+
+    /// Serializes the tests changing the proxy variables of the process.
+    static PROXY_ENV: Mutex<()> = Mutex::new(());
+
+    /// Run `f` with only the proxy variables in `vars` set.
+    fn with_proxy_env(vars: &[(&str, &str)], f: impl FnOnce()) {
+        let _guard = PROXY_ENV.lock().unwrap_or_else(|e| e.into_inner());
+        for name in ["HTTPS_PROXY", "HTTP_PROXY", "ALL_PROXY", "NO_PROXY"] {
+            std::env::remove_var(name);
+            std::env::remove_var(name.to_ascii_lowercase());
+        }
+        for (name, value) in vars {
+            std::env::set_var(name, value);
+        }
+        f();
+        for (name, _) in vars {
+            std::env::remove_var(name);
+        }
+    }
+
+    #[test]
+    fn proxy_from_env_by_scheme() {
+        with_proxy_env(
+            &[
+                ("HTTPS_PROXY", "http://secure:3128"),
+                ("http_proxy", "http://plain:3128"),
+            ],
+            || {
+                assert_eq!(
+                    proxy_from_env("https://ntfy.sh/topic").as_deref(),
+                    Some("http://secure:3128")
+                );
+                assert_eq!(
+                    proxy_from_env("http://ntfy.sh/topic").as_deref(),
+                    Some("http://plain:3128")
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn proxy_from_env_falls_back_to_all_proxy() {
+        with_proxy_env(&[("ALL_PROXY", "socks5://proxy:1080")], || {
+            assert_eq!(
+                proxy_from_env("https://ntfy.sh").as_deref(),
+                Some("socks5://proxy:1080")
+            );
+        });
+        with_proxy_env(&[("HTTPS_PROXY", "")], || {
+            assert_eq!(proxy_from_env("https://ntfy.sh"), None);
+        });
+    }
+
+    #[test]
+    fn proxy_from_env_honours_no_proxy() {
+        with_proxy_env(
+            &[
+                ("HTTPS_PROXY", "http://proxy:3128"),
+                ("NO_PROXY", "localhost, .Example.org"),
+            ],
+            || {
+                assert_eq!(proxy_from_env("https://localhost:8080"), None);
+                assert_eq!(proxy_from_env("https://example.org/topic"), None);
+                assert_eq!(proxy_from_env("https://ntfy.EXAMPLE.org"), None);
+                assert_eq!(
+                    proxy_from_env("https://notexample.org").as_deref(),
+                    Some("http://proxy:3128")
+                );
+            },
+        );
+        with_proxy_env(
+            &[("HTTPS_PROXY", "http://proxy:3128"), ("no_proxy", "*")],
+            || assert_eq!(proxy_from_env("https://ntfy.sh"), None),
+        );
+    }
+
+    #[test]
+    fn proxy_from_env_needs_a_scheme() {
+        with_proxy_env(&[("HTTPS_PROXY", "http://proxy:3128")], || {
+            assert_eq!(proxy_from_env("ntfy.sh"), None);
+        });
+    }
+}
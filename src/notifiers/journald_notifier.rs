@@ -0,0 +1,114 @@
+use crate::notifiers::format::visible_emails;
+use crate::notifiers::{Event, Notifier, NotifierConfig, NotifierContext};
+use anyhow::anyhow;
+use libsystemd::logging::{journal_send, Priority};
+use log::error;
+use serde::Deserialize;
+use std::path::Path;
+
+/// Socket journald listens on for native protocol messages.
+const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+
+#[derive(Debug, Deserialize)]
+/// Configuration for the systemd journal notifier.
+pub struct JournaldConfig {
+    pub name: String,
+    /// Only send the notifications of these accounts, all accounts if empty.
+    #[serde(default)]
+    pub accounts: Vec<String>,
+    /// Hide the email senders and subjects, only the number of new emails is sent.
+    #[serde(default)]
+    pub redact: bool,
+}
+
+impl NotifierConfig for JournaldConfig {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn take_accounts(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.accounts)
+    }
+
+    fn redact(&self) -> bool {
+        self.redact
+    }
+
+    fn into_notifier(self, ctx: &NotifierContext) -> anyhow::Result<Box<dyn Notifier>> {
+        if !Path::new(JOURNAL_SOCKET).exists() {
+            return Err(anyhow!(
+                "systemd journal is not available ({JOURNAL_SOCKET} not found)"
+            ));
+        }
+
+        Ok(Box::new(JournaldNotifier {
+            name: self.name,
+            ctx: ctx.clone(),
+        }))
+    }
+}
+
+/// Write structured notifications to the systemd journal, these can be queried with
+/// e.g. `journalctl EVENT=new_email`.
+struct JournaldNotifier {
+    name: String,
+    ctx: NotifierContext,
+}
+
+impl Notifier for JournaldNotifier {
+    fn notify(&self, event: &Event) {
+        let mut fields = vec![
+            ("SYSLOG_IDENTIFIER", "you-have-mail-cli".to_string()),
+            ("EVENT", event.kind().to_string()),
+        ];
+
+        let (priority, message) = match event {
+            Event::NewEmail {
+                account,
+                emails,
+                redacted,
+                ..
+            } => {
+                fields.push(("ACCOUNT", account.clone()));
+                fields.push(("COUNT", emails.len().to_string()));
+                let mut message = format!("{account} has {} new message(s)", emails.len());
+                for email in visible_emails(emails, *redacted) {
+                    message.push_str(&format!("\n{}: {}", email.sender, email.subject));
+                }
+                (Priority::Info, message)
+            }
+            Event::AccountLoggedOut { account, .. } => {
+                fields.push(("ACCOUNT", account.clone()));
+                (
+                    Priority::Warning,
+                    format!("{account} logged out or session expired"),
+                )
+            }
+            Event::AccountError { account, error, .. } => {
+                fields.push(("ACCOUNT", account.clone()));
+                (
+                    Priority::Error,
+                    format!("{account} encountered an error: {error}"),
+                )
+            }
+            Event::ConfigError { error } => {
+                (Priority::Error, format!("Configuration error: {error}"))
+            }
+            Event::Error { error } => (Priority::Error, format!("An error occurred: {error}")),
+            Event::Info { title, message } => match message {
+                Some(message) => (Priority::Info, format!("{title}\n{message}")),
+                None => (Priority::Info, title.clone()),
+            },
+        };
+
+        let delivered = match journal_send(priority, &message, fields.into_iter()) {
+            Ok(()) => true,
+            Err(e) => {
+                error!("Failed to write to journal ({}): {e}", self.name);
+                false
+            }
+        };
+        self.ctx
+            .record_delivery(&format!("journald ({})", self.name), event, delivered);
+    }
+}
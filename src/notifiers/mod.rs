@@ -1,25 +1,306 @@
 //! Collection of notifier implementations.
+use crate::cfg::Config;
+use crate::history::History;
+use anyhow::anyhow;
+use log::{error, info};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
 use you_have_mail_common::Notification;
 use you_have_mail_common::Notifier as YHMNotifier;
 
+mod aggregate;
+#[cfg(feature = "notifier-http")]
+mod circuit_breaker;
+mod coalesce;
+mod escalation;
+mod event;
+mod filter;
+mod format;
+#[cfg(feature = "notifier-http")]
+mod http;
+mod quiet;
+mod registry;
+mod reload;
+mod routing;
+mod seen;
+#[cfg(feature = "notifier-ntfy")]
+mod spool;
+mod stats;
 mod stdout_notifier;
+mod template;
+#[cfg(feature = "notifier-ntfy")]
+mod webpush;
+#[cfg(any(
+    feature = "notifier-http",
+    feature = "notifier-desktop",
+    feature = "notifier-exec",
+    feature = "notifier-smtp",
+    all(unix, feature = "notifier-pipe")
+))]
+mod worker;
+
+pub use aggregate::Aggregation;
+pub use escalation::AccountFailures;
+use escalation::ErrorEscalation;
+pub use event::{Email, Event};
+pub use filter::{Filters, FiltersConfig};
+pub use format::{visible_emails, Placeholders, Redaction};
+pub use quiet::QuietHours;
+pub use registry::{GenericNotifierConfig, NotifierFactory, NotifierRegistry};
+pub use reload::ReloadableNotifier;
+pub use seen::SeenEmails;
+pub use stats::{BreakerState, SessionStats};
 
 #[cfg(feature = "notifier-ntfy")]
 mod ntfy_notifier;
 #[cfg(feature = "notifier-ntfy")]
 pub use ntfy_notifier::NTFYConfig;
 
+#[cfg(all(unix, feature = "notifier-pipe"))]
+mod pipe_notifier;
+#[cfg(all(unix, feature = "notifier-pipe"))]
+pub use pipe_notifier::PipeConfig;
+
+#[cfg(all(target_os = "linux", feature = "notifier-journald"))]
+mod journald_notifier;
+#[cfg(all(target_os = "linux", feature = "notifier-journald"))]
+pub use journald_notifier::JournaldConfig;
+
+#[cfg(feature = "notifier-telegram")]
+mod telegram_notifier;
+#[cfg(feature = "notifier-telegram")]
+pub use telegram_notifier::TelegramConfig;
+
+#[cfg(feature = "notifier-hass")]
+mod homeassistant_notifier;
+#[cfg(feature = "notifier-hass")]
+pub use homeassistant_notifier::HomeAssistantConfig;
+
+#[cfg(feature = "notifier-discord")]
+mod discord_notifier;
+#[cfg(feature = "notifier-discord")]
+pub use discord_notifier::DiscordConfig;
+
+#[cfg(feature = "notifier-slack")]
+mod slack_notifier;
+#[cfg(feature = "notifier-slack")]
+pub use slack_notifier::SlackConfig;
+
+#[cfg(feature = "notifier-smtp")]
+mod smtp_notifier;
+#[cfg(feature = "notifier-smtp")]
+pub use smtp_notifier::SmtpConfig;
+
+#[cfg(feature = "notifier-pushbullet")]
+mod pushbullet_notifier;
+#[cfg(feature = "notifier-pushbullet")]
+pub use pushbullet_notifier::PushbulletConfig;
+
+#[cfg(feature = "notifier-signal")]
+mod signal_notifier;
+#[cfg(feature = "notifier-signal")]
+pub use signal_notifier::SignalConfig;
+
+#[cfg(feature = "notifier-syslog")]
+mod syslog_notifier;
+#[cfg(feature = "notifier-syslog")]
+pub use syslog_notifier::SyslogConfig;
+
+#[cfg(feature = "notifier-desktop")]
+mod desktop_notifier;
+#[cfg(feature = "notifier-desktop")]
+pub use desktop_notifier::DesktopConfig;
+
+#[cfg(feature = "notifier-webhook")]
+mod webhook_notifier;
+#[cfg(feature = "notifier-webhook")]
+pub use webhook_notifier::WebhookConfig;
+
+#[cfg(feature = "notifier-gotify")]
+mod gotify_notifier;
+#[cfg(feature = "notifier-gotify")]
+pub use gotify_notifier::GotifyConfig;
+
+#[cfg(feature = "notifier-exec")]
+mod exec_notifier;
+#[cfg(feature = "notifier-exec")]
+pub use exec_notifier::ExecConfig;
+
+#[cfg(feature = "notifier-file")]
+mod file_notifier;
+#[cfg(feature = "notifier-file")]
+pub use file_notifier::FileConfig;
+
 pub trait Notifier: Send + Sync {
-    fn notify(&self, notification: &Notification);
+    fn notify(&self, event: &Event);
+
+    /// Block until queued notifications have been sent or `timeout` expires.
+    /// Returns false on timeout.
+    fn flush(&self, _timeout: Duration) -> bool {
+        true
+    }
+
+    /// Send the queued notifications and stop the notifier's threads, waiting at most `timeout`.
+    /// Returns false on timeout. Notifications received afterwards are dropped.
+    fn shutdown(&self, timeout: Duration) -> bool {
+        self.flush(timeout)
+    }
+}
+
+/// Config of a notifier with its own section in the config file, e.g. `[[ntfy]]`.
+pub trait NotifierConfig: Send + 'static {
+    fn name(&self) -> &str;
+
+    /// Accounts the notifier is restricted to, all accounts if empty.
+    fn take_accounts(&mut self) -> Vec<String>;
+
+    /// Whether the notifier only receives the number of new emails.
+    fn redact(&self) -> bool;
+
+    fn into_notifier(self, ctx: &NotifierContext) -> anyhow::Result<Box<dyn Notifier>>;
+}
+
+/// State shared by the CLI with all the notifiers.
+#[derive(Clone, Default)]
+pub struct NotifierContext {
+    pub stats: Arc<SessionStats>,
+    /// Consecutive failures of each account, to mute the errors of accounts which keep failing.
+    pub account_failures: Arc<AccountFailures>,
+    /// Default CA bundle for notifiers which don't specify their own.
+    pub ca_bundle: Option<PathBuf>,
+    /// HTTP agents shared by the notifiers.
+    #[cfg(feature = "notifier-http")]
+    pub http_agents: http::AgentPool,
+    /// Record of the delivered notifications, if enabled.
+    pub history: Option<Arc<History>>,
+    /// Emails notified before the last restart, which are not notified again.
+    pub seen_emails: Option<Arc<SeenEmails>>,
+    /// Set when the CLI exits, interrupts retries.
+    pub shutdown: Shutdown,
+    /// Directory where notifiers can persist their state.
+    pub config_dir: PathBuf,
+    /// Factories of the notifiers configured with their `type`.
+    pub registry: Arc<NotifierRegistry>,
+}
+
+/// Signals the notifier threads that the CLI is exiting, so they stop waiting between retries.
+#[derive(Clone, Default)]
+pub struct Shutdown(Arc<(Mutex<bool>, Condvar)>);
+
+impl Shutdown {
+    pub fn request(&self) {
+        let (requested, condvar) = &*self.0;
+        *requested.lock().unwrap() = true;
+        condvar.notify_all();
+    }
+
+    /// Sleep for `duration`, returns false if shutdown was requested before it elapsed.
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let (requested, condvar) = &*self.0;
+        let guard = requested.lock().unwrap();
+        let (guard, _) = condvar
+            .wait_timeout_while(guard, duration, |requested| !*requested)
+            .unwrap();
+        !*guard
+    }
+}
+
+impl NotifierContext {
+    /// Record whether the notifier called `name` delivered `event`.
+    pub fn record_delivery(&self, name: &str, event: &Event, delivered: bool) {
+        self.stats
+            .record_notification(name, event.kind(), delivered);
+        if delivered {
+            self.stats.record_delivered();
+            if let Some(history) = &self.history {
+                history.append(name, event);
+            }
+        } else {
+            self.stats.record_dropped();
+        }
+    }
 }
 
 pub struct NotifierMultiplexer {
     notifiers: Vec<Box<dyn Notifier>>,
+    aggregation: Aggregation,
+    dry_run: bool,
+    /// New email events of the current poll waiting to be aggregated.
+    pending: Mutex<Vec<Event>>,
+    open_urls: HashMap<String, String>,
+    placeholders: Placeholders,
+    filters: Filters,
+    redaction: Redaction,
+    escalation: ErrorEscalation,
+    stats: Arc<SessionStats>,
+    seen_emails: Option<Arc<SeenEmails>>,
+    /// Priorities of the emails by notifier name.
+    email_priorities: Vec<(String, EmailPriority)>,
+}
+
+/// Priority of an email for a notifier, evaluated before the email is masked.
+pub type EmailPriority = Box<dyn Fn(&Email) -> Option<u8> + Send + Sync>;
+
+/// Deferred construction of a notifier, so they can be initialized concurrently.
+type NotifierInit = Box<dyn FnOnce() -> anyhow::Result<Box<dyn Notifier>> + Send>;
+
+enum PendingNotifier {
+    Ready(Box<dyn Notifier>),
+    Factory(String, NotifierInit),
+}
+
+/// What happens when a notifier can't be created.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotifierFailure {
+    /// Fail, so that no notification is silently lost.
+    #[default]
+    Abort,
+    /// Log the error and continue with the other notifiers, as long as one of them works.
+    Skip,
 }
 
-#[derive(Default)]
 pub struct NotifierMultiplexerBuilder {
-    notifiers: Vec<Box<dyn Notifier>>,
+    notifiers: Vec<PendingNotifier>,
+    failure: NotifierFailure,
+    aggregation: Aggregation,
+    coalesce_window: Duration,
+    quiet_hours: Option<QuietHours>,
+    dry_run: bool,
+    open_urls: HashMap<String, String>,
+    init_concurrency: usize,
+    placeholders: Placeholders,
+    filters: Filters,
+    redaction: Redaction,
+    escalation: ErrorEscalation,
+    stats: Arc<SessionStats>,
+    seen_emails: Option<Arc<SeenEmails>>,
+    email_priorities: Vec<(String, EmailPriority)>,
+}
+
+impl Default for NotifierMultiplexerBuilder {
+    fn default() -> Self {
+        Self {
+            notifiers: Vec::new(),
+            failure: NotifierFailure::default(),
+            aggregation: Aggregation::default(),
+            coalesce_window: Duration::ZERO,
+            quiet_hours: None,
+            dry_run: false,
+            open_urls: HashMap::new(),
+            init_concurrency: 1,
+            placeholders: Placeholders::default(),
+            filters: Filters::default(),
+            redaction: Redaction::default(),
+            escalation: ErrorEscalation::default(),
+            stats: Arc::default(),
+            seen_emails: None,
+            email_priorities: Vec::new(),
+        }
+    }
 }
 
 impl NotifierMultiplexerBuilder {
@@ -28,25 +309,492 @@ impl NotifierMultiplexerBuilder {
     }
 
     pub fn with_notifier(mut self, notifier: Box<dyn Notifier>) -> Self {
-        self.notifiers.push(notifier);
+        self.notifiers.push(PendingNotifier::Ready(notifier));
+        self
+    }
+
+    /// Add a notifier which will be created when [`NotifierMultiplexerBuilder::build`] is
+    /// called. `name` is used to identify the notifier should it fail to initialize.
+    pub fn with_notifier_factory(
+        mut self,
+        name: String,
+        factory: impl FnOnce() -> anyhow::Result<Box<dyn Notifier>> + Send + 'static,
+    ) -> Self {
+        self.notifiers
+            .push(PendingNotifier::Factory(name, Box::new(factory)));
+        self
+    }
+
+    /// Whether [`NotifierMultiplexerBuilder::build`] fails when a notifier can't be created.
+    pub fn with_failure_policy(mut self, failure: NotifierFailure) -> Self {
+        self.failure = failure;
+        self
+    }
+
+    /// Hide email details for the accounts covered by `redaction`.
+    pub fn with_redaction(mut self, redaction: Redaction) -> Self {
+        self.redaction = redaction;
+        self
+    }
+
+    /// Merge the new email notifications of a poll according to `aggregation`.
+    pub fn with_aggregation(mut self, aggregation: Aggregation) -> Self {
+        self.aggregation = aggregation;
+        self
+    }
+
+    /// Combine the new email notifications an account receives within `window`, zero to send
+    /// them right away.
+    pub fn with_coalesce_window(mut self, window: Duration) -> Self {
+        self.coalesce_window = window;
+        self
+    }
+
+    /// Hold back the new email notifications during `quiet_hours`.
+    pub fn with_quiet_hours(mut self, quiet_hours: Option<QuietHours>) -> Self {
+        self.quiet_hours = quiet_hours;
+        self
+    }
+
+    /// Only log the notifications instead of dispatching them to the notifiers.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Text used for blank email senders and subjects.
+    pub fn with_placeholders(mut self, placeholders: Placeholders) -> Self {
+        self.placeholders = placeholders;
+        self
+    }
+
+    /// Suppress the new emails matching `filters`.
+    pub fn with_filters(mut self, filters: Filters) -> Self {
+        self.filters = filters;
+        self
+    }
+
+    /// Mute the errors of accounts which keep failing.
+    pub fn with_error_escalation(mut self, escalation: ErrorEscalation) -> Self {
+        self.escalation = escalation;
+        self
+    }
+
+    /// Record the session's counters in `stats`.
+    pub fn with_stats(mut self, stats: Arc<SessionStats>) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    /// Skip the emails which were already notified before the last restart.
+    pub fn with_seen_emails(mut self, seen_emails: Option<Arc<SeenEmails>>) -> Self {
+        self.seen_emails = seen_emails;
+        self
+    }
+
+    /// Set the priority of the emails for the notifier `name`, carried on the emails since the
+    /// notifier may only receive them masked.
+    #[cfg(feature = "notifier-ntfy")]
+    pub fn with_email_priority(mut self, name: impl Into<String>, priority: EmailPriority) -> Self {
+        self.email_priorities.push((name.into(), priority));
+        self
+    }
+
+    /// Maximum number of notifiers that are initialized at the same time.
+    pub fn with_init_concurrency(mut self, concurrency: usize) -> Self {
+        self.init_concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Url to open when a notification for `email` is clicked.
+    pub fn with_open_url(mut self, email: String, url: String) -> Self {
+        self.open_urls.insert(email, url);
         self
     }
 
-    pub fn build(self) -> NotifierMultiplexer {
-        NotifierMultiplexer {
-            notifiers: self.notifiers,
+    /// Initialize all notifiers, the order in which they were added is preserved. Fails if
+    /// any of the notifiers could not be created, unless the failure policy is
+    /// [`NotifierFailure::Skip`] and at least one of them could. The skipped notifiers are then
+    /// reported to the others as a config error.
+    pub fn build(self) -> anyhow::Result<NotifierMultiplexer> {
+        let count = self.notifiers.len();
+        let pending = Mutex::new(self.notifiers.into_iter().enumerate());
+        let results = Mutex::new((0..count).map(|_| None).collect::<Vec<_>>());
+
+        std::thread::scope(|s| {
+            for _ in 0..self.init_concurrency.min(count) {
+                s.spawn(|| loop {
+                    let Some((index, notifier)) = pending.lock().unwrap().next() else {
+                        break;
+                    };
+
+                    let result = match notifier {
+                        PendingNotifier::Ready(notifier) => Ok(notifier),
+                        PendingNotifier::Factory(name, factory) => {
+                            factory().map_err(|e| anyhow!("{name}: {e}"))
+                        }
+                    };
+
+                    results.lock().unwrap()[index] = Some(result);
+                });
+            }
+        });
+
+        let mut notifiers = Vec::with_capacity(count);
+        let mut errors = Vec::new();
+        for result in results.into_inner().unwrap().into_iter().flatten() {
+            match result {
+                Ok(notifier) => notifiers.push(notifier),
+                Err(e) => errors.push(e.to_string()),
+            }
         }
+
+        let mut skipped = None;
+        if !errors.is_empty() {
+            let error = format!(
+                "Failed to create {} notifier(s): {}",
+                errors.len(),
+                errors.join(", ")
+            );
+            if self.failure == NotifierFailure::Abort || notifiers.is_empty() {
+                return Err(anyhow!(error));
+            }
+            error!(
+                "{error}, continuing with the other {} notifier(s)",
+                notifiers.len()
+            );
+            skipped = Some(error);
+        }
+
+        // Records the emails once they leave the quiet hours and the coalescing, the dry runs
+        // don't dispatch to it.
+        if let Some(seen) = &self.seen_emails {
+            notifiers.push(Box::new(seen::SeenRecorder(seen.clone())));
+        }
+        if let Some(quiet_hours) = self.quiet_hours {
+            let quiet = quiet::QuietNotifier::new(notifiers, quiet_hours)?;
+            notifiers = vec![Box::new(quiet)];
+        }
+        if !self.coalesce_window.is_zero() {
+            let coalescing = coalesce::CoalescingNotifier::new(notifiers, self.coalesce_window)?;
+            notifiers = vec![Box::new(coalescing)];
+        }
+
+        let multiplexer = NotifierMultiplexer {
+            notifiers,
+            aggregation: self.aggregation,
+            dry_run: self.dry_run,
+            pending: Mutex::default(),
+            open_urls: self.open_urls,
+            placeholders: self.placeholders,
+            filters: self.filters,
+            redaction: self.redaction,
+            escalation: self.escalation,
+            stats: self.stats,
+            seen_emails: self.seen_emails,
+            email_priorities: self.email_priorities,
+        };
+        if let Some(error) = skipped {
+            multiplexer.notify_event(Event::ConfigError { error });
+        }
+        Ok(multiplexer)
+    }
+}
+
+impl NotifierMultiplexer {
+    /// Dispatch `event` to all notifiers.
+    pub fn notify_event(&self, mut event: Event) {
+        self.stats.record_event(event.kind());
+        if let Event::AccountError { account, error, .. } = &event {
+            self.stats.record_account_error(account, error);
+        }
+        if !self.escalation.apply(&mut event) {
+            return;
+        }
+        if let Event::NewEmail {
+            account,
+            emails,
+            open_url,
+            ..
+        } = &mut event
+        {
+            if let Some(seen) = &self.seen_emails {
+                let count = emails.len();
+                seen.retain_unseen(account, emails);
+                if emails.is_empty() {
+                    info!("Skipping {count} email(s) of {account} notified before the restart");
+                    return;
+                }
+            }
+            self.stats.record_new_emails(account, emails.len());
+            *open_url = self.open_urls.get(account).cloned();
+        }
+        self.placeholders.apply(&mut event);
+        if !self.filters.apply(&mut event) {
+            return;
+        }
+        if let Event::NewEmail { emails, .. } = &mut event {
+            for email in emails.iter_mut() {
+                for (name, priority) in &self.email_priorities {
+                    if let Some(priority) = priority(email) {
+                        email.priorities.insert(name.clone(), priority);
+                    }
+                }
+            }
+        }
+        self.redaction.apply(&mut event);
+
+        if self.aggregation != Aggregation::None && matches!(event, Event::NewEmail { .. }) {
+            self.pending.lock().unwrap().push(event);
+            return;
+        }
+
+        self.dispatch(&event);
+    }
+
+    /// Send the aggregated new email notifications of the poll which just completed.
+    pub fn end_poll(&self) {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        for event in self.aggregation.merge(pending) {
+            self.dispatch(&event);
+        }
+    }
+
+    fn dispatch(&self, event: &Event) {
+        if self.dry_run {
+            info!("Dry run, not sending notification: {event:?}");
+            return;
+        }
+
+        for notifier in &self.notifiers {
+            notifier.notify(event)
+        }
+    }
+
+    /// Wait for all notifiers to send their queued notifications, giving up after `timeout`.
+    pub fn flush(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut flushed = true;
+        for notifier in self.notifiers.iter() {
+            flushed &= notifier.flush(deadline.saturating_duration_since(Instant::now()));
+        }
+        flushed
+    }
+
+    /// Send the queued notifications and stop the notifiers before exiting, giving up after
+    /// `timeout`.
+    pub fn shutdown(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut stopped = true;
+        for notifier in self.notifiers.iter() {
+            stopped &= notifier.shutdown(deadline.saturating_duration_since(Instant::now()));
+        }
+        stopped
     }
 }
 
 impl YHMNotifier for NotifierMultiplexer {
     fn notify(&self, notification: Notification) {
-        for notifier in &self.notifiers {
-            notifier.notify(&notification)
+        if let Some(event) = Event::from_notification(&notification) {
+            self.notify_event(event);
+        }
+    }
+}
+
+/// Create the notifiers enabled in `config`. The notifier configurations are moved out of
+/// `config`.
+pub fn new_notifier(
+    config: &mut Config,
+    ctx: &NotifierContext,
+    redaction: Redaction,
+    dry_run: bool,
+) -> anyhow::Result<NotifierMultiplexer> {
+    let mut builder = NotifierMultiplexerBuilder::new()
+        .with_redaction(redaction)
+        .with_dry_run(dry_run)
+        .with_failure_policy(config.notifier_failure)
+        .with_placeholders(config.placeholders.clone())
+        .with_filters(
+            Filters::new(config.filters.clone()).map_err(|e| anyhow!("Invalid filters: {e}"))?,
+        )
+        .with_aggregation(config.aggregation)
+        .with_error_escalation(ErrorEscalation::new(
+            config.max_error_notifications,
+            Duration::from_secs(config.error_notification_cooldown_secs),
+            ctx.account_failures.clone(),
+        ))
+        .with_stats(ctx.stats.clone())
+        .with_seen_emails(ctx.seen_emails.clone());
+    // The stdout notifier has no section to list its accounts or ask for redaction, it prints
+    // the notifications of every account as the global and account `redact` settings leave them.
+    if config.stdout_notifier {
+        info!("Adding stdout Notifier");
+        builder = builder.with_notifier(new_stdout_notifier(ctx));
+    }
+
+    if let Some(accounts) = &config.account {
+        for account in accounts {
+            if let Some(url) = account.open_url() {
+                builder = builder.with_open_url(account.email.clone(), url);
+            }
+        }
+    }
+
+    #[cfg(feature = "notifier-ntfy")]
+    {
+        for ntfy in config.ntfy.iter_mut().flatten() {
+            let rules = std::mem::take(&mut ntfy.priority_rules);
+            let priority = ntfy_notifier::email_priority(rules)
+                .map_err(|e| anyhow!("ntfy ({}): {e}", ntfy.name))?;
+            builder = builder.with_email_priority(ntfy.name.clone(), priority);
         }
+        builder = add_notifiers(builder, "ntfy", config.ntfy.take(), ctx);
+    }
+
+    #[cfg(all(unix, feature = "notifier-pipe"))]
+    {
+        builder = add_notifiers(builder, "pipe", config.pipe.take(), ctx);
+    }
+
+    #[cfg(all(target_os = "linux", feature = "notifier-journald"))]
+    {
+        builder = add_notifiers(builder, "journald", config.journald.take(), ctx);
+    }
+
+    #[cfg(feature = "notifier-syslog")]
+    {
+        builder = add_notifiers(builder, "syslog", config.syslog.take(), ctx);
+    }
+
+    #[cfg(feature = "notifier-telegram")]
+    {
+        builder = add_notifiers(builder, "telegram", config.telegram.take(), ctx);
+    }
+
+    #[cfg(feature = "notifier-hass")]
+    {
+        builder = add_notifiers(builder, "hass", config.homeassistant.take(), ctx);
+    }
+
+    #[cfg(feature = "notifier-discord")]
+    {
+        builder = add_notifiers(builder, "discord", config.discord.take(), ctx);
+    }
+
+    #[cfg(feature = "notifier-slack")]
+    {
+        builder = add_notifiers(builder, "slack", config.slack.take(), ctx);
+    }
+
+    #[cfg(feature = "notifier-smtp")]
+    {
+        builder = add_notifiers(builder, "smtp", config.smtp.take(), ctx);
+    }
+
+    #[cfg(feature = "notifier-pushbullet")]
+    {
+        builder = add_notifiers(builder, "pushbullet", config.pushbullet.take(), ctx);
+    }
+
+    #[cfg(feature = "notifier-signal")]
+    {
+        builder = add_notifiers(builder, "signal", config.signal.take(), ctx);
+    }
+
+    #[cfg(feature = "notifier-desktop")]
+    {
+        builder = add_notifiers(builder, "desktop", config.desktop.take(), ctx);
+    }
+
+    #[cfg(feature = "notifier-webhook")]
+    {
+        builder = add_notifiers(builder, "webhook", config.webhook.take(), ctx);
+    }
+
+    #[cfg(feature = "notifier-gotify")]
+    {
+        builder = add_notifiers(builder, "gotify", config.gotify.take(), ctx);
+    }
+
+    #[cfg(feature = "notifier-exec")]
+    {
+        builder = add_notifiers(builder, "exec", config.exec.take(), ctx);
+    }
+
+    #[cfg(feature = "notifier-file")]
+    {
+        builder = add_notifiers(builder, "file", config.file.take(), ctx);
+    }
+
+    if let Some(notifiers) = config.notifier.take() {
+        for mut cfg in notifiers {
+            if !ctx.registry.contains(&cfg.kind) {
+                return Err(anyhow!(
+                    "Notifier {} has unknown type '{}', it may not be enabled in this build",
+                    cfg.name,
+                    cfg.kind
+                ));
+            }
+            #[cfg(feature = "notifier-ntfy")]
+            if cfg.kind == "ntfy" {
+                if let Some(rules) = cfg.settings.remove("priority_rules") {
+                    let priority = serde_json::from_value(rules)
+                        .map_err(anyhow::Error::from)
+                        .and_then(ntfy_notifier::email_priority)
+                        .map_err(|e| anyhow!("ntfy ({}): {e}", cfg.name))?;
+                    builder = builder.with_email_priority(cfg.name.clone(), priority);
+                }
+            }
+            info!("Adding {} Notifier: name={}", cfg.kind, cfg.name);
+            builder = builder.with_notifier_factory(format!("{} ({})", cfg.kind, cfg.name), {
+                let ctx = ctx.clone();
+                let accounts = std::mem::take(&mut cfg.accounts);
+                let redact = cfg.redact;
+                move || {
+                    ctx.registry
+                        .create(cfg, &ctx)
+                        .map(|n| routing::routed(format::redacted(n, redact), accounts))
+                }
+            });
+        }
+    }
+
+    builder
+        .with_init_concurrency(config.notifier_init_concurrency)
+        .with_coalesce_window(Duration::from_secs(config.coalesce_window_secs))
+        .with_quiet_hours(
+            config
+                .quiet_hours
+                .as_deref()
+                .map(|range| QuietHours::parse(range, config.quiet_hours_timezone.as_deref()))
+                .transpose()?,
+        )
+        .build()
+}
+
+/// Add a notifier for each of `configs`, restricted to its accounts and redacted if it asks for
+/// it. `kind` identifies the type of notifier in the logs.
+fn add_notifiers<C: NotifierConfig>(
+    mut builder: NotifierMultiplexerBuilder,
+    kind: &str,
+    configs: Option<Vec<C>>,
+    ctx: &NotifierContext,
+) -> NotifierMultiplexerBuilder {
+    for mut cfg in configs.into_iter().flatten() {
+        info!("Adding {kind} Notifier: name={}", cfg.name());
+        builder = builder.with_notifier_factory(format!("{kind} ({})", cfg.name()), {
+            let ctx = ctx.clone();
+            let accounts = cfg.take_accounts();
+            let redact = cfg.redact();
+            move || {
+                cfg.into_notifier(&ctx)
+                    .map(|n| routing::routed(format::redacted(n, redact), accounts))
+            }
+        });
     }
+    builder
 }
 
-pub fn new_stdout_notifier() -> Box<dyn Notifier> {
-    Box::new(stdout_notifier::StdOutNotifier {})
+pub fn new_stdout_notifier(ctx: &NotifierContext) -> Box<dyn Notifier> {
+    Box::new(stdout_notifier::StdOutNotifier { ctx: ctx.clone() })
 }
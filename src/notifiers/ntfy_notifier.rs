@@ -1,190 +1,627 @@
-use crate::notifiers::Notifier;
+use crate::notifiers::circuit_breaker::{BreakerConfig, CircuitBreaker};
+use crate::notifiers::format::visible_emails;
+use crate::notifiers::http::{send_with_retries, HttpConfig};
+use crate::notifiers::spool::FailedNotificationQueue;
+use crate::notifiers::template::MessageTemplates;
+use crate::notifiers::webpush::WebPushKeys;
+use crate::notifiers::worker::{default_queue_size, WorkerNotifier};
+use crate::notifiers::{
+    Email, EmailPriority, Event, Notifier, NotifierConfig, NotifierContext, Shutdown,
+};
 use anyhow::anyhow;
-use crossbeam_channel::Receiver;
-use crossbeam_channel::Sender;
-use log::{debug, error};
+use log::{error, info};
+use regex::Regex;
 use serde::Deserialize;
-use std::time::Duration;
-use ureq::Error;
-use you_have_mail_common::backend::EmailInfo;
-use you_have_mail_common::{ExposeSecret, Notification, Secret};
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use you_have_mail_common::{ExposeSecret, Secret};
+
+/// ntfy priority of the escalated account errors.
+const MAX_PRIORITY: u8 = 5;
 
 #[derive(Debug, Deserialize)]
 /// Configuration for ntfy endpoint
 pub struct NTFYConfig {
     pub name: String,
+    /// Only send the notifications of these accounts, all accounts if empty.
+    #[serde(default)]
+    pub accounts: Vec<String>,
+    /// Hide the email senders and subjects, only the number of new emails is sent.
+    #[serde(default)]
+    pub redact: bool,
+    /// Number of notifications waiting to be sent after which new ones are dropped.
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
     pub url: String,
     pub auth_token: Option<String>,
+    /// PEM file with additional root certificates, overrides the global `ca_bundle`.
+    pub ca_bundle: Option<PathBuf>,
+    /// Timeouts and User-Agent of the requests.
+    #[serde(flatten)]
+    pub http: HttpConfig,
+    /// Pause the requests after repeated failures.
+    #[serde(flatten)]
+    pub breaker: BreakerConfig,
+    /// Number of times a request failing with a transport or server error is retried.
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// Keep undelivered notifications on disk and send them once the server is reachable again.
+    #[serde(default)]
+    pub spool_failed: bool,
+    /// Raise the priority of new email notifications matching these rules. They are taken out
+    /// of the config and evaluated by the multiplexer, before the emails are masked.
+    #[serde(default)]
+    pub priority_rules: Vec<PriorityRuleConfig>,
+    /// Format the new email notifications as markdown, plain text if false.
+    #[serde(default = "default_markdown")]
+    pub markdown: bool,
+    /// Url opened when a notification is tapped, new email notifications open the account's
+    /// webmail instead if it is known.
+    pub click_url: Option<String>,
+    /// Url of the icon displayed with the notifications.
+    pub icon_url: Option<String>,
+    /// Priority and tags of each kind of notification, keyed by the event name.
+    #[serde(default)]
+    pub events: HashMap<String, EventStyleConfig>,
+    /// Title of the new email notifications, see the `Message Templates` section of the README.
+    pub title_template: Option<String>,
+    /// Line of each email in the body of the new email notifications.
+    pub body_template: Option<String>,
+    /// Public key of the UnifiedPush app's WebPush subscription, base64url encoded. The
+    /// notifications are encrypted for the app if set together with `webpush_auth`.
+    pub webpush_p256dh: Option<String>,
+    /// Authentication secret of the WebPush subscription, base64url encoded.
+    pub webpush_auth: Option<String>,
+    /// Whether the notifications are sent, or only a wakeup telling the app to sync.
+    #[serde(default)]
+    pub mode: NtfyMode,
 }
 
-impl NTFYConfig {
-    pub fn into_notifier(self) -> anyhow::Result<Box<dyn Notifier>> {
-        let notifier = NTFYNotifier::new(self)?;
-        Ok(Box::new(notifier))
-    }
+/// What the ntfy notifier sends.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NtfyMode {
+    /// The title and text of the notifications.
+    #[default]
+    Content,
+    /// A single digit identifying the kind of event, the position of the event in
+    /// `Event::KINDS`, so the app fetches the details on its own.
+    Wakeup,
 }
 
-/// Send notifications to a ntfy instances.
-struct NTFYNotifier {
-    name: String,
-    sender: Sender<NTFYNotification>,
+#[derive(Debug, Deserialize)]
+/// Sets the ntfy `priority` (1-5) when an email matches the `sender` and `subject` regexes.
+pub struct PriorityRuleConfig {
+    pub sender: Option<String>,
+    pub subject: Option<String>,
+    pub priority: u8,
+}
+
+#[derive(Debug, Deserialize)]
+/// ntfy `priority` (1-5) and `tags` of a kind of notification.
+pub struct EventStyleConfig {
+    pub priority: Option<u8>,
+    pub tags: Option<Vec<String>>,
 }
 
-enum NTFYNotification {
-    NewEmail(String, String, Vec<EmailInfo>),
-    LoggedOut(String),
-    AccountError(String, String),
-    ConfigError(String),
-    Error(String),
+/// Headers set on the requests of a kind of notification.
+struct EventStyle {
+    priority: Option<u8>,
+    tags: Option<String>,
 }
 
-impl Notifier for NTFYNotifier {
-    fn notify(&self, notification: &Notification) {
-        let up_notification = match notification {
-            Notification::NewEmail {
-                account,
-                backend,
-                emails,
-            } => NTFYNotification::NewEmail(
-                account.to_string(),
-                backend.to_string(),
-                emails.to_vec(),
-            ),
-            Notification::AccountLoggedOut(email) => NTFYNotification::LoggedOut(email.to_string()),
-            Notification::AccountError(email, error) => {
-                NTFYNotification::AccountError(email.to_string(), error.to_string())
-            }
-            Notification::ConfigError(e) => NTFYNotification::ConfigError(e.to_string()),
-            Notification::Error(e) => NTFYNotification::Error(e.clone()),
-            _ => {
-                return;
+/// Priority and tags of every kind of notification. The error notifications are tagged with
+/// `exclamation` unless configured otherwise.
+fn event_styles(
+    mut config: HashMap<String, EventStyleConfig>,
+) -> anyhow::Result<HashMap<&'static str, EventStyle>> {
+    if let Some(kind) = config.keys().find(|k| !Event::KINDS.contains(&k.as_str())) {
+        return Err(anyhow!(
+            "Unknown event '{kind}', expected one of {}",
+            Event::KINDS.join(", ")
+        ));
+    }
+
+    let mut styles = HashMap::new();
+    for kind in Event::KINDS {
+        let default_tags = matches!(kind, "account_error" | "config_error" | "error")
+            .then(|| "exclamation".to_string());
+        let style = match config.remove(kind) {
+            Some(EventStyleConfig {
+                priority: Some(priority),
+                ..
+            }) if !(1..=5).contains(&priority) => {
+                return Err(anyhow!(
+                    "{kind} priority must be between 1 and 5, got {priority}"
+                ));
             }
+            Some(style) => EventStyle {
+                priority: style.priority,
+                tags: style.tags.map(|tags| tags.join(",")).or(default_tags),
+            },
+            None => EventStyle {
+                priority: None,
+                tags: default_tags,
+            },
         };
-
-        if let Err(e) = self.sender.send(up_notification) {
-            error!("Failed to sent notification to thread ({}): {e}", self.name);
-        }
+        styles.insert(kind, style);
     }
+    Ok(styles)
 }
 
-impl NTFYNotifier {
-    pub fn new(config: NTFYConfig) -> anyhow::Result<Self> {
-        let agent = ureq::builder()
-            .timeout_connect(Duration::from_secs(60))
-            .timeout(Duration::from_secs(120))
-            .max_idle_connections(0)
-            .build();
-        let (sender, receiver) = crossbeam_channel::bounded(20);
-        let thread_state = ThreadState {
-            agent,
-            receiver,
-            server_url: config.url,
-            name: config.name.clone(),
-            auth_token: config.auth_token.map(Secret::new),
+/// Evaluate `rules` on the emails before they are masked, the multiplexer carries the highest
+/// priority of the matching rules on each email.
+pub fn email_priority(rules: Vec<PriorityRuleConfig>) -> anyhow::Result<EmailPriority> {
+    let rules = rules
+        .into_iter()
+        .map(PriorityRule::new)
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(Box::new(move |email| {
+        rules
+            .iter()
+            .filter(|rule| rule.matches(email))
+            .map(|rule| rule.priority)
+            .max()
+    }))
+}
+
+struct PriorityRule {
+    sender: Option<Regex>,
+    subject: Option<Regex>,
+    priority: u8,
+}
+
+impl PriorityRule {
+    fn new(config: PriorityRuleConfig) -> anyhow::Result<Self> {
+        if !(1..=5).contains(&config.priority) {
+            return Err(anyhow!(
+                "priority rule priority must be between 1 and 5, got {}",
+                config.priority
+            ));
+        }
+        if config.sender.is_none() && config.subject.is_none() {
+            return Err(anyhow!("priority rule needs a sender or subject pattern"));
+        }
+
+        let compile = |pattern: Option<String>| {
+            pattern
+                .map(|p| Regex::new(&p).map_err(|e| anyhow!("invalid priority rule regex: {e}")))
+                .transpose()
         };
-        std::thread::Builder::new()
-            .name("ntfy-thread".to_string())
-            .spawn(move || ThreadState::thread_loop(thread_state))
-            .map_err(|e| anyhow!("Failed to spawn ntfy ({}) thread: {e}", config.name))?;
 
         Ok(Self {
-            sender,
-            name: config.name,
+            sender: compile(config.sender)?,
+            subject: compile(config.subject)?,
+            priority: config.priority,
         })
     }
+
+    fn matches(&self, email: &Email) -> bool {
+        let is_match = |regex: &Option<Regex>, value: &str| match regex {
+            Some(regex) => regex.is_match(value),
+            None => true,
+        };
+        is_match(&self.sender, &email.sender) && is_match(&self.subject, &email.subject)
+    }
+}
+
+const fn default_max_retries() -> u32 {
+    3
 }
 
-struct ThreadState {
+const fn default_markdown() -> bool {
+    true
+}
+
+impl NotifierConfig for NTFYConfig {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn take_accounts(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.accounts)
+    }
+
+    fn redact(&self) -> bool {
+        self.redact
+    }
+
+    fn into_notifier(self, ctx: &NotifierContext) -> anyhow::Result<Box<dyn Notifier>> {
+        let ca_bundle = self.ca_bundle.or_else(|| ctx.ca_bundle.clone());
+        let agent = ctx
+            .http_agents
+            .agent(&self.http.options(ca_bundle, &self.url))?;
+        let styles = event_styles(self.events)?;
+        let webpush = match (&self.webpush_p256dh, &self.webpush_auth) {
+            (Some(p256dh), Some(auth)) => Some(WebPushKeys::new(p256dh, auth)?),
+            (None, None) => None,
+            _ => {
+                return Err(anyhow!(
+                    "Both webpush_p256dh and webpush_auth need to be set"
+                ))
+            }
+        };
+        if webpush.is_some() && self.mode == NtfyMode::Wakeup {
+            return Err(anyhow!(
+                "The wakeups are not encrypted, remove the webpush keys or use the content mode"
+            ));
+        }
+        let mut state = NTFYNotifier {
+            agent,
+            server_url: self.url,
+            name: self.name.clone(),
+            auth_token: self.auth_token.map(Secret::new),
+            breaker: self.breaker.circuit_breaker("ntfy", &self.name, ctx),
+            click_url: self.click_url,
+            icon_url: self.icon_url,
+            styles,
+            templates: MessageTemplates::new(
+                self.title_template,
+                self.body_template,
+                if self.markdown {
+                    "{time} **{sender}**: {subject}"
+                } else {
+                    "{time} {sender}: {subject}"
+                },
+            ),
+            markdown: self.markdown,
+            webpush,
+            mode: self.mode,
+            max_retries: self.max_retries,
+            shutdown: ctx.shutdown.clone(),
+            spool: self
+                .spool_failed
+                .then(|| FailedNotificationQueue::open(&ctx.config_dir, "ntfy", &self.name)),
+        };
+        let notifier =
+            WorkerNotifier::spawn_with_setup("ntfy", self.name, self.queue_size, ctx, move || {
+                // Send the notifications left over from the previous run first.
+                state.replay_spool();
+                move |event| state.process(event)
+            })?;
+        Ok(Box::new(notifier))
+    }
+}
+
+/// Send notifications to a ntfy instances.
+struct NTFYNotifier {
     name: String,
     agent: ureq::Agent,
-    receiver: Receiver<NTFYNotification>,
     server_url: String,
     auth_token: Option<Secret<String>>,
+    breaker: CircuitBreaker,
+    click_url: Option<String>,
+    icon_url: Option<String>,
+    styles: HashMap<&'static str, EventStyle>,
+    templates: MessageTemplates,
+    markdown: bool,
+    /// Encrypts the notifications, the title and the other details are then not sent as
+    /// headers.
+    webpush: Option<WebPushKeys>,
+    mode: NtfyMode,
+    max_retries: u32,
+    shutdown: Shutdown,
+    spool: Option<FailedNotificationQueue>,
 }
 
-impl ThreadState {
-    fn thread_loop(state: ThreadState) {
-        debug!("Starting ntfy {} thread", state.name);
-        while let Ok(notification) = state.receiver.recv() {
-            match notification {
-                NTFYNotification::NewEmail(account, _backend, emails) => {
-                    let title = format!("{account} has {} new message(s))", emails.len());
-                    let mut body = String::new();
-                    for email in emails {
-                        body.push_str(&format!("**{}**: {}\n", email.sender, email.subject))
-                    }
-                    state.info_notification(title, Some(body));
-                }
-                NTFYNotification::LoggedOut(email) => {
-                    state.info_notification(format!("{email} logged out or session expired"), None);
-                }
-                NTFYNotification::AccountError(email, e) => {
-                    let title = format!("{email} encountered an error");
-                    state.error_notification(title, Some(e));
-                }
-                NTFYNotification::ConfigError(e) => {
-                    state.error_notification("Server Config Error".to_string(), Some(e));
+impl NTFYNotifier {
+    /// Send `event` after the spooled notifications, spooling it if it can't be delivered or
+    /// older notifications are still waiting. Returns true if it was posted.
+    fn process(&mut self, event: Event) -> bool {
+        if self.spool.is_none() {
+            return self.handle(event);
+        }
+
+        let delivered = self.replay_spool() && self.handle(event.clone());
+        if !delivered {
+            if let Some(spool) = &mut self.spool {
+                spool.push(event);
+            }
+        }
+        delivered
+    }
+
+    /// Send the spooled notifications in order, stops at the first failure. Returns true if no
+    /// notification is left in the spool.
+    fn replay_spool(&mut self) -> bool {
+        let Some(mut spool) = self.spool.take() else {
+            return true;
+        };
+        if !spool.is_empty() {
+            info!(
+                "Sending {} spooled notification(s) to ntfy {}",
+                spool.len(),
+                self.name
+            );
+        }
+        while let Some(event) = spool.front().cloned() {
+            if !self.handle(event) {
+                break;
+            }
+            spool.pop();
+        }
+        let empty = spool.is_empty();
+        self.spool = Some(spool);
+        empty
+    }
+
+    /// Returns true if the notification was posted.
+    fn handle(&mut self, event: Event) -> bool {
+        let kind = event.kind();
+        if self.mode == NtfyMode::Wakeup {
+            let index = Event::KINDS
+                .iter()
+                .position(|k| *k == kind)
+                .unwrap_or_default();
+            let request = self.new_request(kind, None, None);
+            return self.post(request, vec![b'0' + index as u8]);
+        }
+
+        match event {
+            Event::NewEmail {
+                account,
+                emails,
+                open_url,
+                redacted,
+                ..
+            } => {
+                let visible = visible_emails(&emails, redacted);
+                let title = self.templates.title(&account, &emails, visible);
+                let mut body = String::new();
+                for line in self.templates.body_lines(&account, emails.len(), visible) {
+                    body.push_str(&line);
+                    body.push('\n');
                 }
-                NTFYNotification::Error(e) => {
-                    state.error_notification("Server Error".to_string(), Some(e));
+                let mut request =
+                    self.new_request(kind, self.priority(&emails), open_url.as_deref());
+                if self.markdown && self.webpush.is_none() {
+                    request = request.set("X-Markdown", "yes");
                 }
+                let body = if body.is_empty() { None } else { Some(body) };
+                self.send(request, title, body)
             }
+            Event::AccountLoggedOut { account, escalated } => self.notification(
+                kind,
+                escalated.then_some(MAX_PRIORITY),
+                format!("{account} logged out or session expired"),
+                None,
+            ),
+            Event::AccountError {
+                account,
+                error,
+                escalated,
+            } => self.notification(
+                kind,
+                escalated.then_some(MAX_PRIORITY),
+                format!("{account} encountered an error"),
+                Some(error),
+            ),
+            Event::ConfigError { error } => {
+                self.notification(kind, None, "Server Config Error".to_string(), Some(error))
+            }
+            Event::Error { error } => {
+                self.notification(kind, None, "Server Error".to_string(), Some(error))
+            }
+            Event::Info { title, message } => self.notification(kind, None, title, message),
         }
-        debug!("Exiting ntfy {} thread", state.name)
     }
 
-    fn new_request(&self) -> ureq::Request {
-        let request = self
+    /// Highest priority of the rules matching any of `emails`, evaluated by the multiplexer.
+    fn priority(&self, emails: &[Email]) -> Option<u8> {
+        emails
+            .iter()
+            .filter_map(|email| email.priorities.get(&self.name))
+            .max()
+            .copied()
+    }
+
+    /// Request with the priority and tags of `kind`, or `priority` if it is higher. Tapping the
+    /// notification opens `open_url`, or the configured `click_url`.
+    fn new_request(
+        &self,
+        kind: &str,
+        priority: Option<u8>,
+        open_url: Option<&str>,
+    ) -> ureq::Request {
+        let mut request = self
             .agent
             .request("POST", &self.server_url)
             .set("X-UnifiedPush", "1");
         if let Some(token) = &self.auth_token {
-            request.set(
+            request = request.set(
                 "authorization",
                 &format!("Bearer {}", token.expose_secret()),
-            )
-        } else {
-            request
+            );
         }
+        if self.mode == NtfyMode::Wakeup {
+            return request;
+        }
+        if self.webpush.is_some() {
+            return request.set("Content-Encoding", "aes128gcm");
+        }
+        if let Some(url) = open_url.or(self.click_url.as_deref()) {
+            request = request.set("X-Click", url);
+        }
+        if let Some(url) = &self.icon_url {
+            request = request.set("X-Icon", url);
+        }
+        let style = self.styles.get(kind);
+        if let Some(priority) = priority.max(style.and_then(|s| s.priority)) {
+            request = request.set("X-Priority", &priority.to_string());
+        }
+        if let Some(tags) = style.and_then(|s| s.tags.as_deref()) {
+            request = request.set("X-Tags", tags);
+        }
+        request
     }
 
-    fn info_notification(&self, title: String, body: Option<String>) {
-        let request = self.new_request();
-        self.send(request, title, body)
-    }
-
-    fn error_notification(&self, title: String, body: Option<String>) {
-        let request = self.new_request().set("X-Tags", "exclamation");
+    fn notification(
+        &mut self,
+        kind: &str,
+        priority: Option<u8>,
+        title: String,
+        body: Option<String>,
+    ) -> bool {
+        let request = self.new_request(kind, priority, None);
         self.send(request, title, body)
     }
 
-    fn send(&self, request: ureq::Request, title: String, body: Option<String>) {
-        match if let Some(body) = body {
-            request.set("X-Title", &title).send_string(&body)
-        } else {
-            request.send_string(&title)
-        } {
-            Ok(_) => {
-                debug!("Notification successfully posted to ntfy {}", self.name)
-            }
-            Err(e) => match e {
-                Error::Status(code, response) => {
-                    let response_body = match response.into_string() {
-                        Ok(s) => s,
-                        Err(_) => "Failed to get response body".to_string(),
-                    };
-                    error!(
-                        "Failed to post ntfy request ({}): HttpCode={} Response={}",
-                        self.name, code, response_body
-                    );
-                }
-                Error::Transport(e) => {
-                    error!(
-                        "Failed to post ntfy request ({}): Transport error={e}",
-                        self.name,
-                    );
+    fn send(&mut self, request: ureq::Request, title: String, body: Option<String>) -> bool {
+        let (request, payload) = match &self.webpush {
+            Some(keys) => {
+                let message = json!({"title": title, "message": body});
+                match keys.encrypt(message.to_string().as_bytes()) {
+                    Ok(payload) => (request, payload),
+                    Err(e) => {
+                        error!("Failed to encrypt ntfy notification ({}): {e}", self.name);
+                        return false;
+                    }
                 }
+            }
+            None => match &body {
+                Some(body) => (request.set("X-Title", &title), body.clone().into_bytes()),
+                None => (request, title.into_bytes()),
             },
         };
+        self.post(request, payload)
+    }
+
+    /// Post `payload`, retrying transient failures.
+    fn post(&mut self, request: ureq::Request, payload: Vec<u8>) -> bool {
+        send_with_retries(
+            "ntfy",
+            &self.name,
+            &mut self.breaker,
+            &self.shutdown,
+            &request,
+            &payload,
+            self.max_retries,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifiers::{NotifierMultiplexerBuilder, Redaction};
+    use std::collections::HashSet;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<Event>>>);
+
+    impl Notifier for Recorder {
+        fn notify(&self, event: &Event) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    fn email(sender: &str) -> Email {
+        Email {
+            sender: sender.to_string(),
+            subject: "Hello".to_string(),
+            received_at: 0,
+            account: "foo@bar.com".to_string(),
+            backend: "Proton Mail".to_string(),
+            seen_hash: None,
+            priorities: Default::default(),
+        }
+    }
+
+    fn notifier(click_url: Option<&str>, icon_url: Option<&str>) -> NTFYNotifier {
+        NTFYNotifier {
+            name: "test".to_string(),
+            agent: ureq::agent(),
+            server_url: "https://ntfy.sh/topic".to_string(),
+            auth_token: None,
+            breaker: CircuitBreaker::new(0, Duration::ZERO),
+            click_url: click_url.map(str::to_string),
+            icon_url: icon_url.map(str::to_string),
+            styles: HashMap::new(),
+            templates: MessageTemplates::new(None, None, "{sender}: {subject}"),
+            markdown: false,
+            webpush: None,
+            mode: NtfyMode::Content,
+            max_retries: 0,
+            shutdown: Shutdown::default(),
+            spool: None,
+        }
+    }
+
+    #[test]
+    fn no_click_or_icon_headers_by_default() {
+        let request = notifier(None, None).new_request("info", None, None);
+        assert_eq!(request.header("X-Click"), None);
+        assert_eq!(request.header("X-Icon"), None);
+    }
+
+    #[test]
+    fn configured_click_and_icon_urls() {
+        let request = notifier(
+            Some("https://mail.proton.me"),
+            Some("https://x.org/icon.png"),
+        )
+        .new_request("info", None, None);
+        assert_eq!(request.header("X-Click"), Some("https://mail.proton.me"));
+        assert_eq!(request.header("X-Icon"), Some("https://x.org/icon.png"));
+    }
+
+    #[test]
+    fn open_url_overrides_click_url() {
+        let request = notifier(Some("https://mail.proton.me"), None).new_request(
+            "new_email",
+            None,
+            Some("https://webmail.org"),
+        );
+        assert_eq!(request.header("X-Click"), Some("https://webmail.org"));
+
+        let request =
+            notifier(None, None).new_request("new_email", None, Some("https://webmail.org"));
+        assert_eq!(request.header("X-Click"), Some("https://webmail.org"));
+    }
+
+    #[test]
+    fn no_click_or_icon_headers_for_wakeups() {
+        let mut notifier = notifier(Some("https://mail.proton.me"), Some("https://x.org/i.png"));
+        notifier.mode = NtfyMode::Wakeup;
+        let request = notifier.new_request("new_email", None, Some("https://webmail.org"));
+        assert_eq!(request.header("X-Click"), None);
+        assert_eq!(request.header("X-Icon"), None);
+    }
+
+    #[test]
+    fn priority_rules_match_the_emails_before_redaction() {
+        let rules = vec![PriorityRuleConfig {
+            sender: Some("boss@".to_string()),
+            subject: None,
+            priority: 5,
+        }];
+        let recorder = Recorder::default();
+        let multiplexer = NotifierMultiplexerBuilder::new()
+            .with_redaction(Redaction::new(true, HashSet::new()))
+            .with_email_priority("test", email_priority(rules).unwrap())
+            .with_notifier(Box::new(recorder.clone()))
+            .build()
+            .unwrap();
+        multiplexer.notify_event(Event::NewEmail {
+            account: "foo@bar.com".to_string(),
+            backend: "Proton Mail".to_string(),
+            emails: vec![email("boss@bar.com"), email("other@bar.com")],
+            open_url: None,
+            redacted: false,
+        });
+
+        let events = recorder.0.lock().unwrap();
+        let Some(Event::NewEmail {
+            emails, redacted, ..
+        }) = events.first()
+        else {
+            panic!("new email event not notified");
+        };
+        assert!(redacted);
+        assert_eq!(notifier(None, None).priority(emails), Some(5));
+        assert_eq!(notifier(None, None).priority(&emails[1..]), None);
     }
 }
@@ -0,0 +1,113 @@
+use crate::notifiers::worker::{default_queue_size, WorkerNotifier};
+use crate::notifiers::{Event, Notifier, NotifierConfig, NotifierContext};
+use anyhow::anyhow;
+use log::{debug, error};
+use serde::Deserialize;
+use std::io::Write;
+use std::os::unix::fs::{FileTypeExt, OpenOptionsExt};
+use std::path::PathBuf;
+
+#[derive(Debug, Deserialize)]
+/// Configuration for a named pipe (FIFO) endpoint.
+pub struct PipeConfig {
+    pub name: String,
+    /// Only send the notifications of these accounts, all accounts if empty.
+    #[serde(default)]
+    pub accounts: Vec<String>,
+    /// Hide the email senders and subjects, only the number of new emails is sent.
+    #[serde(default)]
+    pub redact: bool,
+    /// Number of notifications waiting to be sent after which new ones are dropped.
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
+    pub path: PathBuf,
+}
+
+impl NotifierConfig for PipeConfig {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn take_accounts(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.accounts)
+    }
+
+    fn redact(&self) -> bool {
+        self.redact
+    }
+
+    fn into_notifier(self, ctx: &NotifierContext) -> anyhow::Result<Box<dyn Notifier>> {
+        let notifier = PipeNotifier::spawn(self, ctx)?;
+        Ok(Box::new(notifier))
+    }
+}
+
+/// Write notifications as JSON lines to a named pipe. Notifications are dropped if no process
+/// is reading from the pipe.
+struct PipeNotifier {
+    name: String,
+    path: PathBuf,
+}
+
+impl PipeNotifier {
+    fn spawn(config: PipeConfig, ctx: &NotifierContext) -> anyhow::Result<WorkerNotifier> {
+        let metadata = std::fs::metadata(&config.path)
+            .map_err(|e| anyhow!("Failed to access pipe {:?}: {e}", config.path))?;
+        if !metadata.file_type().is_fifo() {
+            return Err(anyhow!("{:?} is not a named pipe", config.path));
+        }
+
+        let state = PipeNotifier {
+            name: config.name.clone(),
+            path: config.path,
+        };
+        WorkerNotifier::spawn("pipe", config.name, config.queue_size, ctx, move |event| {
+            state.write(&event)
+        })
+    }
+
+    /// Returns true if the event was written to the pipe.
+    fn write(&self, event: &Event) -> bool {
+        let mut line = match serde_json::to_string(event) {
+            Ok(line) => line,
+            Err(e) => {
+                error!("Failed to serialize notification ({}): {e}", self.name);
+                return false;
+            }
+        };
+        line.push('\n');
+
+        // Opening the pipe in non-blocking mode fails with ENXIO rather than blocking when
+        // there is no reader.
+        let mut file = match std::fs::OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&self.path)
+        {
+            Ok(f) => f,
+            Err(e) if e.raw_os_error() == Some(libc::ENXIO) => {
+                debug!("No reader on pipe {}, dropping notification", self.name);
+                return false;
+            }
+            Err(e) => {
+                error!("Failed to open pipe ({}): {e}", self.name);
+                return false;
+            }
+        };
+
+        match file.write_all(line.as_bytes()) {
+            Ok(()) => {
+                debug!("Notification successfully written to pipe {}", self.name);
+                true
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                error!("Pipe ({}) is full, dropping notification", self.name);
+                false
+            }
+            Err(e) => {
+                error!("Failed to write to pipe ({}): {e}", self.name);
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use crate::cfg::deserialize_secret;
+use crate::notifiers::circuit_breaker::{BreakerConfig, CircuitBreaker};
+use crate::notifiers::format::visible_emails;
+use crate::notifiers::http::{send_with_breaker, HttpConfig};
+use crate::notifiers::template::MessageTemplates;
+use crate::notifiers::worker::{default_queue_size, WorkerNotifier};
+use crate::notifiers::{Event, Notifier, NotifierConfig, NotifierContext, Shutdown};
+use serde::Deserialize;
+use serde_json::json;
+use std::path::PathBuf;
+use you_have_mail_common::{ExposeSecret, Secret};
+
+const PUSHES_URL: &str = "https://api.pushbullet.com/v2/pushes";
+
+#[derive(Debug, Deserialize)]
+/// Configuration for a Pushbullet account.
+pub struct PushbulletConfig {
+    pub name: String,
+    /// Only send the notifications of these accounts, all accounts if empty.
+    #[serde(default)]
+    pub accounts: Vec<String>,
+    /// Hide the email senders and subjects, only the number of new emails is sent.
+    #[serde(default)]
+    pub redact: bool,
+    /// Number of notifications waiting to be sent after which new ones are dropped.
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
+    #[serde(deserialize_with = "deserialize_secret")]
+    pub access_token: Secret<String>,
+    /// PEM file with additional root certificates, overrides the global `ca_bundle`.
+    pub ca_bundle: Option<PathBuf>,
+    /// Timeouts and User-Agent of the requests.
+    #[serde(flatten)]
+    pub http: HttpConfig,
+    /// Pause the requests after repeated failures.
+    #[serde(flatten)]
+    pub breaker: BreakerConfig,
+    /// Title of the new email notifications, see the `Message Templates` section of the README.
+    pub title_template: Option<String>,
+    /// Line of each email in the body of the new email notifications.
+    pub body_template: Option<String>,
+}
+
+impl NotifierConfig for PushbulletConfig {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn take_accounts(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.accounts)
+    }
+
+    fn redact(&self) -> bool {
+        self.redact
+    }
+
+    fn into_notifier(self, ctx: &NotifierContext) -> anyhow::Result<Box<dyn Notifier>> {
+        let ca_bundle = self.ca_bundle.or_else(|| ctx.ca_bundle.clone());
+        let agent = ctx
+            .http_agents
+            .agent(&self.http.options(ca_bundle, PUSHES_URL))?;
+        let mut state = PushbulletNotifier {
+            name: self.name.clone(),
+            agent,
+            shutdown: ctx.shutdown.clone(),
+            access_token: self.access_token,
+            breaker: self.breaker.circuit_breaker("pushbullet", &self.name, ctx),
+            templates: MessageTemplates::new(
+                self.title_template,
+                self.body_template,
+                "{sender}: {subject}",
+            ),
+        };
+        let notifier = WorkerNotifier::spawn(
+            "pushbullet",
+            self.name,
+            self.queue_size,
+            ctx,
+            move |event| state.handle(event),
+        )?;
+        Ok(Box::new(notifier))
+    }
+}
+
+/// Send notifications as Pushbullet note pushes.
+struct PushbulletNotifier {
+    name: String,
+    agent: ureq::Agent,
+    shutdown: Shutdown,
+    access_token: Secret<String>,
+    breaker: CircuitBreaker,
+    templates: MessageTemplates,
+}
+
+impl PushbulletNotifier {
+    /// Returns true if the push was created.
+    fn handle(&mut self, event: Event) -> bool {
+        let (title, body) = match event {
+            Event::NewEmail {
+                account,
+                emails,
+                redacted,
+                ..
+            } => {
+                let visible = visible_emails(&emails, redacted);
+                let title = self.templates.title(&account, &emails, visible);
+                let body = self
+                    .templates
+                    .body_lines(&account, emails.len(), visible)
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                (title, body)
+            }
+            Event::AccountLoggedOut { account, .. } => (
+                format!("{account} logged out or session expired"),
+                String::new(),
+            ),
+            Event::AccountError { account, error, .. } => {
+                (format!("{account} encountered an error"), error)
+            }
+            Event::ConfigError { error } => ("Server Config Error".to_string(), error),
+            Event::Error { error } => ("Server Error".to_string(), error),
+            Event::Info { title, message } => (title, message.unwrap_or_default()),
+        };
+
+        self.send(title, body)
+    }
+
+    fn send(&mut self, title: String, body: String) -> bool {
+        let payload = json!({
+            "type": "note",
+            "title": title,
+            "body": body,
+        });
+        let request = self
+            .agent
+            .post(PUSHES_URL)
+            .set("Access-Token", self.access_token.expose_secret())
+            .set("Content-Type", "application/json");
+        send_with_breaker(
+            "pushbullet",
+            &self.name,
+            &mut self.breaker,
+            &self.shutdown,
+            &request,
+            payload.to_string().as_bytes(),
+        )
+    }
+}
@@ -0,0 +1,248 @@
+//! Holding back new email notifications during quiet hours.
+use crate::notifiers::{Aggregation, Event, Notifier};
+use anyhow::anyhow;
+use chrono::{FixedOffset, NaiveTime, Timelike, Utc};
+use log::{debug, info};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+/// How often the release thread checks whether the quiet hours ended.
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Daily time range, which may cross midnight, during which new email notifications are held
+/// back.
+#[derive(Debug, Clone, Copy)]
+pub struct QuietHours {
+    start: NaiveTime,
+    end: NaiveTime,
+    /// Timezone of the range, the system's local timezone if `None`.
+    offset: Option<FixedOffset>,
+}
+
+impl QuietHours {
+    /// Parse a `"HH:MM-HH:MM"` range. `timezone` is either `"local"` or an offset from UTC such
+    /// as `"UTC"` or `"+02:00"`.
+    pub fn parse(range: &str, timezone: Option<&str>) -> anyhow::Result<Self> {
+        let (start, end) = range
+            .split_once('-')
+            .ok_or_else(|| anyhow!("quiet hours '{range}' are not in the HH:MM-HH:MM format"))?;
+        let parse_time = |time: &str| {
+            NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                .map_err(|e| anyhow!("invalid quiet hours time '{time}': {e}"))
+        };
+        let (start, end) = (parse_time(start)?, parse_time(end)?);
+        if start == end {
+            return Err(anyhow!(
+                "quiet hours '{range}' start and end at the same time"
+            ));
+        }
+
+        Ok(Self {
+            start,
+            end,
+            offset: parse_offset(timezone.unwrap_or("local"))?,
+        })
+    }
+
+    fn now(&self) -> NaiveTime {
+        match self.offset {
+            Some(offset) => Utc::now().with_timezone(&offset).time(),
+            None => chrono::Local::now().time(),
+        }
+    }
+
+    fn contains(&self, time: NaiveTime) -> bool {
+        // Sub-minute precision is not needed and would make `end` inclusive for a minute.
+        let time = time.with_second(0).unwrap_or(time);
+        if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+
+    pub fn is_quiet(&self) -> bool {
+        self.contains(self.now())
+    }
+}
+
+fn parse_offset(timezone: &str) -> anyhow::Result<Option<FixedOffset>> {
+    match timezone.trim() {
+        tz if tz.eq_ignore_ascii_case("local") => Ok(None),
+        tz if tz.eq_ignore_ascii_case("utc") => Ok(FixedOffset::east_opt(0)),
+        tz => {
+            let (sign, rest) = if let Some(rest) = tz.strip_prefix('+') {
+                (1, rest)
+            } else if let Some(rest) = tz.strip_prefix('-') {
+                (-1, rest)
+            } else {
+                return Err(anyhow!("invalid quiet hours timezone '{tz}'"));
+            };
+            let time = NaiveTime::parse_from_str(rest, "%H:%M")
+                .map_err(|_| anyhow!("invalid quiet hours timezone '{tz}'"))?;
+            let seconds = sign * (time.num_seconds_from_midnight() as i32);
+            FixedOffset::east_opt(seconds)
+                .map(Some)
+                .ok_or_else(|| anyhow!("invalid quiet hours timezone '{tz}'"))
+        }
+    }
+}
+
+/// Holds back new email notifications during the quiet hours and sends them as a single
+/// notification once they end. All other events are passed through immediately.
+pub struct QuietNotifier {
+    state: Arc<State>,
+}
+
+struct State {
+    notifiers: Vec<Box<dyn Notifier>>,
+    quiet_hours: QuietHours,
+    pending: Mutex<Vec<Event>>,
+}
+
+impl State {
+    fn notify_all(&self, event: &Event) {
+        for notifier in &self.notifiers {
+            notifier.notify(event)
+        }
+    }
+
+    /// Send the held back notifications merged into one.
+    fn release(&self) {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        if pending.is_empty() {
+            return;
+        }
+
+        info!(
+            "Sending {} notification(s) held back during quiet hours",
+            pending.len()
+        );
+        for event in Aggregation::Global.merge(pending) {
+            self.notify_all(&event);
+        }
+    }
+}
+
+impl QuietNotifier {
+    pub fn new(notifiers: Vec<Box<dyn Notifier>>, quiet_hours: QuietHours) -> anyhow::Result<Self> {
+        let state = Arc::new(State {
+            notifiers,
+            quiet_hours,
+            pending: Mutex::default(),
+        });
+
+        let weak = Arc::downgrade(&state);
+        std::thread::Builder::new()
+            .name("quiet-hours-thread".to_string())
+            .spawn(move || release_after_quiet_hours(weak))
+            .map_err(|e| anyhow!("Failed to spawn quiet hours thread: {e}"))?;
+
+        Ok(Self { state })
+    }
+}
+
+impl Notifier for QuietNotifier {
+    fn notify(&self, event: &Event) {
+        if matches!(event, Event::NewEmail { .. }) && self.state.quiet_hours.is_quiet() {
+            debug!("Quiet hours, holding back new email notification");
+            self.state.pending.lock().unwrap().push(event.clone());
+            return;
+        }
+
+        self.state.notify_all(event);
+    }
+
+    /// Held back notifications are sent right away, so they are not lost on exit.
+    fn flush(&self, timeout: Duration) -> bool {
+        self.state.release();
+
+        let deadline = Instant::now() + timeout;
+        let mut flushed = true;
+        for notifier in self.state.notifiers.iter() {
+            flushed &= notifier.flush(deadline.saturating_duration_since(Instant::now()));
+        }
+        flushed
+    }
+
+    fn shutdown(&self, timeout: Duration) -> bool {
+        self.state.release();
+
+        let deadline = Instant::now() + timeout;
+        let mut stopped = true;
+        for notifier in self.state.notifiers.iter() {
+            stopped &= notifier.shutdown(deadline.saturating_duration_since(Instant::now()));
+        }
+        stopped
+    }
+}
+
+/// Release the held back notifications once the quiet hours end, until the notifier is
+/// dropped.
+fn release_after_quiet_hours(state: Weak<State>) {
+    loop {
+        std::thread::sleep(CHECK_INTERVAL);
+        let Some(state) = state.upgrade() else {
+            return;
+        };
+        if !state.quiet_hours.is_quiet() {
+            state.release();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(hour: u32, min: u32, sec: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, min, sec).unwrap()
+    }
+
+    #[test]
+    fn range_within_a_day() {
+        let quiet = QuietHours::parse("09:00-17:30", None).unwrap();
+        assert!(!quiet.contains(time(8, 59, 59)));
+        assert!(quiet.contains(time(9, 0, 0)));
+        assert!(quiet.contains(time(17, 29, 59)));
+        assert!(!quiet.contains(time(17, 30, 0)));
+        assert!(!quiet.contains(time(0, 0, 0)));
+    }
+
+    #[test]
+    fn range_across_midnight() {
+        let quiet = QuietHours::parse("22:00 - 07:00", Some("UTC")).unwrap();
+        assert!(!quiet.contains(time(21, 59, 0)));
+        assert!(quiet.contains(time(22, 0, 0)));
+        assert!(quiet.contains(time(23, 59, 59)));
+        assert!(quiet.contains(time(0, 0, 0)));
+        assert!(quiet.contains(time(6, 59, 30)));
+        assert!(!quiet.contains(time(7, 0, 0)));
+        assert!(!quiet.contains(time(12, 0, 0)));
+    }
+
+    #[test]
+    fn invalid_ranges() {
+        assert!(QuietHours::parse("22:00", None).is_err());
+        assert!(QuietHours::parse("22:00-25:00", None).is_err());
+        assert!(QuietHours::parse("10pm-7am", None).is_err());
+        assert!(QuietHours::parse("07:00-07:00", None).is_err());
+    }
+
+    #[test]
+    fn timezones() {
+        assert_eq!(parse_offset("local").unwrap(), None);
+        assert_eq!(parse_offset("Local").unwrap(), None);
+        assert_eq!(parse_offset("utc").unwrap(), FixedOffset::east_opt(0));
+        assert_eq!(
+            parse_offset("+02:00").unwrap(),
+            FixedOffset::east_opt(2 * 3600)
+        );
+        assert_eq!(
+            parse_offset("-05:30").unwrap(),
+            FixedOffset::west_opt(5 * 3600 + 30 * 60)
+        );
+        assert!(parse_offset("CET").is_err());
+        assert!(parse_offset("+2").is_err());
+    }
+}
@@ -0,0 +1,150 @@
+//! Construction of the notifiers configured with a generic `[[notifier]]` entry, selected by
+//! their `type`.
+use crate::notifiers::{Notifier, NotifierConfig, NotifierContext};
+use anyhow::anyhow;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+#[derive(Debug, Deserialize)]
+/// Configuration of a notifier of any registered type.
+pub struct GenericNotifierConfig {
+    /// Type of the notifier, e.g. "ntfy".
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub name: String,
+    /// Only send the notifications of these accounts, all accounts if empty.
+    #[serde(default)]
+    pub accounts: Vec<String>,
+    /// Hide the email senders and subjects, only the number of new emails is sent.
+    #[serde(default)]
+    pub redact: bool,
+    /// The remaining settings, specific to the type of notifier.
+    #[serde(flatten)]
+    pub settings: Map<String, Value>,
+}
+
+/// Creates the notifiers of a type from their settings.
+pub trait NotifierFactory: Send + Sync {
+    /// `settings` are the settings of the notifier including its name.
+    fn create(&self, settings: Value, ctx: &NotifierContext) -> anyhow::Result<Box<dyn Notifier>>;
+}
+
+impl<F> NotifierFactory for F
+where
+    F: Fn(Value, &NotifierContext) -> anyhow::Result<Box<dyn Notifier>> + Send + Sync,
+{
+    fn create(&self, settings: Value, ctx: &NotifierContext) -> anyhow::Result<Box<dyn Notifier>> {
+        self(settings, ctx)
+    }
+}
+
+/// Factory deserializing the settings into the config `C` of a notifier.
+pub fn config_factory<C: NotifierConfig + DeserializeOwned>() -> impl NotifierFactory {
+    move |settings: Value, ctx: &NotifierContext| {
+        let config: C =
+            serde_json::from_value(settings).map_err(|e| anyhow!("Invalid settings: {e}"))?;
+        config.into_notifier(ctx)
+    }
+}
+
+/// Factories of the notifier types, by type name.
+pub struct NotifierRegistry {
+    factories: HashMap<String, Box<dyn NotifierFactory>>,
+}
+
+impl NotifierRegistry {
+    /// Registry without any notifier type.
+    pub fn empty() -> Self {
+        Self {
+            factories: HashMap::new(),
+        }
+    }
+
+    /// Create the notifiers of type `kind` with `factory`, replacing a previous factory.
+    pub fn register(&mut self, kind: impl Into<String>, factory: impl NotifierFactory + 'static) {
+        self.factories.insert(kind.into(), Box::new(factory));
+    }
+
+    pub fn contains(&self, kind: &str) -> bool {
+        self.factories.contains_key(kind)
+    }
+
+    /// Create the notifier described by `config`. It is not restricted to `config.accounts`.
+    pub fn create(
+        &self,
+        config: GenericNotifierConfig,
+        ctx: &NotifierContext,
+    ) -> anyhow::Result<Box<dyn Notifier>> {
+        let factory = self
+            .factories
+            .get(&config.kind)
+            .ok_or_else(|| anyhow!("Unknown notifier type '{}'", config.kind))?;
+        let mut settings = config.settings;
+        settings.insert("name".to_string(), Value::String(config.name));
+        factory.create(Value::Object(settings), ctx)
+    }
+}
+
+/// Registry of the notifier types enabled at build time.
+impl Default for NotifierRegistry {
+    fn default() -> Self {
+        #[allow(unused_mut)]
+        let mut registry = Self::empty();
+        #[cfg(feature = "notifier-ntfy")]
+        registry.register("ntfy", config_factory::<crate::notifiers::NTFYConfig>());
+        #[cfg(all(unix, feature = "notifier-pipe"))]
+        registry.register("pipe", config_factory::<crate::notifiers::PipeConfig>());
+        #[cfg(all(target_os = "linux", feature = "notifier-journald"))]
+        registry.register(
+            "journald",
+            config_factory::<crate::notifiers::JournaldConfig>(),
+        );
+        #[cfg(feature = "notifier-syslog")]
+        registry.register("syslog", config_factory::<crate::notifiers::SyslogConfig>());
+        #[cfg(feature = "notifier-telegram")]
+        registry.register(
+            "telegram",
+            config_factory::<crate::notifiers::TelegramConfig>(),
+        );
+        #[cfg(feature = "notifier-hass")]
+        registry.register(
+            "homeassistant",
+            config_factory::<crate::notifiers::HomeAssistantConfig>(),
+        );
+        #[cfg(feature = "notifier-discord")]
+        registry.register(
+            "discord",
+            config_factory::<crate::notifiers::DiscordConfig>(),
+        );
+        #[cfg(feature = "notifier-slack")]
+        registry.register("slack", config_factory::<crate::notifiers::SlackConfig>());
+        #[cfg(feature = "notifier-smtp")]
+        registry.register("smtp", config_factory::<crate::notifiers::SmtpConfig>());
+        #[cfg(feature = "notifier-pushbullet")]
+        registry.register(
+            "pushbullet",
+            config_factory::<crate::notifiers::PushbulletConfig>(),
+        );
+        #[cfg(feature = "notifier-signal")]
+        registry.register("signal", config_factory::<crate::notifiers::SignalConfig>());
+        #[cfg(feature = "notifier-desktop")]
+        registry.register(
+            "desktop",
+            config_factory::<crate::notifiers::DesktopConfig>(),
+        );
+        #[cfg(feature = "notifier-webhook")]
+        registry.register(
+            "webhook",
+            config_factory::<crate::notifiers::WebhookConfig>(),
+        );
+        #[cfg(feature = "notifier-gotify")]
+        registry.register("gotify", config_factory::<crate::notifiers::GotifyConfig>());
+        #[cfg(feature = "notifier-exec")]
+        registry.register("exec", config_factory::<crate::notifiers::ExecConfig>());
+        #[cfg(feature = "notifier-file")]
+        registry.register("file", config_factory::<crate::notifiers::FileConfig>());
+        registry
+    }
+}
@@ -0,0 +1,35 @@
+//! Replacement of the notifiers while the observer is running.
+use crate::notifiers::NotifierMultiplexer;
+use std::sync::{Arc, RwLock};
+use you_have_mail_common::Notification;
+use you_have_mail_common::Notifier as YHMNotifier;
+
+/// Notifier handed to the observer, forwards the notifications to the current multiplexer
+/// which can be swapped when the config file is reloaded.
+pub struct ReloadableNotifier {
+    current: RwLock<Arc<NotifierMultiplexer>>,
+}
+
+impl ReloadableNotifier {
+    pub fn new(notifier: NotifierMultiplexer) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(notifier)),
+        }
+    }
+
+    pub fn current(&self) -> Arc<NotifierMultiplexer> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Install `notifier` and return the previous multiplexer, which should be flushed so its
+    /// queued notifications are not lost.
+    pub fn replace(&self, notifier: NotifierMultiplexer) -> Arc<NotifierMultiplexer> {
+        std::mem::replace(&mut *self.current.write().unwrap(), Arc::new(notifier))
+    }
+}
+
+impl YHMNotifier for ReloadableNotifier {
+    fn notify(&self, notification: Notification) {
+        self.current().notify(notification)
+    }
+}
@@ -0,0 +1,141 @@
+//! Restriction of notifiers to the notifications of some accounts.
+use crate::notifiers::{Email, Event, Notifier};
+use std::collections::HashSet;
+use std::time::Duration;
+
+/// Only passes the events of `accounts` to `inner`. Events which don't belong to an account,
+/// such as server errors, are always passed through.
+struct RoutedNotifier {
+    inner: Box<dyn Notifier>,
+    accounts: HashSet<String>,
+}
+
+impl Notifier for RoutedNotifier {
+    fn notify(&self, event: &Event) {
+        let account = match event {
+            Event::NewEmail {
+                account, emails, ..
+            } => {
+                // Aggregated notifications are narrowed down to the emails of the routed
+                // accounts, the others must not leak to this notifier.
+                let routed = |email: &Email| {
+                    let email_account = if email.account.is_empty() {
+                        account
+                    } else {
+                        &email.account
+                    };
+                    self.accounts.contains(email_account)
+                };
+                if emails.iter().all(routed) {
+                    self.inner.notify(event);
+                } else if emails.iter().any(routed) {
+                    let mut event = event.clone();
+                    if let Event::NewEmail { emails, .. } = &mut event {
+                        emails.retain(routed);
+                    }
+                    event.update_sources();
+                    self.inner.notify(&event);
+                }
+                return;
+            }
+            Event::AccountLoggedOut { account, .. } | Event::AccountError { account, .. } => {
+                account
+            }
+            Event::ConfigError { .. } | Event::Error { .. } | Event::Info { .. } => {
+                self.inner.notify(event);
+                return;
+            }
+        };
+
+        if self.accounts.contains(account) {
+            self.inner.notify(event);
+        }
+    }
+
+    fn flush(&self, timeout: Duration) -> bool {
+        self.inner.flush(timeout)
+    }
+
+    fn shutdown(&self, timeout: Duration) -> bool {
+        self.inner.shutdown(timeout)
+    }
+}
+
+/// Restrict `notifier` to the events of `accounts`, all events are sent if it is empty.
+pub fn routed(notifier: Box<dyn Notifier>, accounts: Vec<String>) -> Box<dyn Notifier> {
+    if accounts.is_empty() {
+        return notifier;
+    }
+
+    Box::new(RoutedNotifier {
+        inner: notifier,
+        accounts: accounts.into_iter().collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<Event>>>);
+
+    impl Notifier for Recorder {
+        fn notify(&self, event: &Event) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    fn email(account: &str, subject: &str) -> Email {
+        Email {
+            sender: "sender".to_string(),
+            subject: subject.to_string(),
+            received_at: 0,
+            account: account.to_string(),
+            backend: "Proton Mail".to_string(),
+            seen_hash: None,
+            priorities: Default::default(),
+        }
+    }
+
+    fn new_email(account: &str, emails: Vec<Email>) -> Event {
+        Event::NewEmail {
+            account: account.to_string(),
+            backend: "Proton Mail".to_string(),
+            emails,
+            open_url: None,
+            redacted: false,
+        }
+    }
+
+    #[test]
+    fn merged_events_only_carry_the_emails_of_the_routed_accounts() {
+        let recorder = Recorder::default();
+        let notifier = routed(Box::new(recorder.clone()), vec!["a@proton.me".to_string()]);
+        notifier.notify(&new_email(
+            "a@proton.me, b@proton.me",
+            vec![
+                email("a@proton.me", "1"),
+                email("b@proton.me", "2"),
+                email("a@proton.me", "3"),
+            ],
+        ));
+        notifier.notify(&new_email("b@proton.me", vec![email("b@proton.me", "4")]));
+
+        let events = recorder.0.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        let Event::NewEmail {
+            account, emails, ..
+        } = &events[0]
+        else {
+            panic!("not a new email event");
+        };
+        assert_eq!(account, "a@proton.me");
+        let subjects = emails
+            .iter()
+            .map(|e| e.subject.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(subjects, ["1", "3"]);
+    }
+}
@@ -0,0 +1,177 @@
+//! Persistent record of the notified emails, so a restart doesn't notify them again.
+use crate::notifiers::{Email, Event, Notifier};
+use crate::utils::write_user_file;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const FILENAME: &str = "notified_emails.json";
+
+/// Entries older than this are forgotten.
+const MAX_AGE_SECS: u64 = 24 * 60 * 60;
+
+#[derive(Serialize, Deserialize)]
+struct SeenEntry {
+    /// Hash of the account, sender and subject of the email.
+    hash: String,
+    /// Unix timestamp in seconds of the notification.
+    ts: u64,
+}
+
+/// Emails notified during the last day, stored in the config directory. Only the emails of
+/// previous sessions are suppressed, two emails with the same sender and subject received while
+/// running are both notified.
+pub struct SeenEmails {
+    path: PathBuf,
+    previous: HashSet<String>,
+    entries: Mutex<Vec<SeenEntry>>,
+}
+
+impl SeenEmails {
+    /// Load and prune the notified emails in `dir`.
+    pub fn open(dir: impl AsRef<Path>) -> Self {
+        let path = dir.as_ref().join(FILENAME);
+        let mut entries = load(&path);
+        let cutoff = unix_now().saturating_sub(MAX_AGE_SECS);
+        entries.retain(|e| e.ts >= cutoff);
+        Self {
+            path,
+            previous: entries.iter().map(|e| e.hash.clone()).collect(),
+            entries: Mutex::new(entries),
+        }
+    }
+
+    /// Remove the emails of `account` which were notified before the restart, and mark the
+    /// others to be recorded by [`SeenRecorder`] when they are notified.
+    pub fn retain_unseen(&self, account: &str, emails: &mut Vec<Email>) {
+        emails.retain_mut(|email| {
+            let hash = hash(account, email);
+            let unseen = !self.previous.contains(&hash);
+            email.seen_hash = Some(hash);
+            unseen
+        });
+    }
+
+    /// Remember that the marked `emails` were notified.
+    fn record(&self, emails: &[Email]) {
+        let hashes = emails
+            .iter()
+            .filter_map(|email| email.seen_hash.clone())
+            .collect::<Vec<_>>();
+        if hashes.is_empty() {
+            return;
+        }
+
+        let now = unix_now();
+        let mut entries = self.entries.lock().unwrap();
+        let cutoff = now.saturating_sub(MAX_AGE_SECS);
+        entries.retain(|e| e.ts >= cutoff);
+        entries.extend(hashes.into_iter().map(|hash| SeenEntry { hash, ts: now }));
+
+        match serde_json::to_vec(&*entries) {
+            Ok(contents) => {
+                if let Err(e) = write_user_file(&self.path, &contents) {
+                    warn!("Failed to write notified emails to {:?}: {e}", self.path);
+                }
+            }
+            Err(e) => warn!("Failed to serialize notified emails: {e}"),
+        }
+    }
+}
+
+/// Records the new emails it receives as notified. Added next to the other notifiers, it only
+/// receives the emails which passed the filters, after the quiet hours and the coalescing
+/// released them.
+pub struct SeenRecorder(pub Arc<SeenEmails>);
+
+impl Notifier for SeenRecorder {
+    fn notify(&self, event: &Event) {
+        if let Event::NewEmail { emails, .. } = event {
+            self.0.record(emails);
+        }
+    }
+}
+
+fn hash(account: &str, email: &Email) -> String {
+    let mut hasher = Sha1::new();
+    for part in [account, email.sender.as_str(), email.subject.as_str()] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+fn load(path: &Path) -> Vec<SeenEntry> {
+    let contents = match std::fs::read(path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!("Failed to read notified emails {path:?}: {e}");
+            }
+            return Vec::new();
+        }
+    };
+
+    serde_json::from_slice(&contents).unwrap_or_else(|e| {
+        warn!("Ignoring invalid notified emails {path:?}: {e}");
+        Vec::new()
+    })
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email(subject: &str) -> Email {
+        Email {
+            sender: "Bar".to_string(),
+            subject: subject.to_string(),
+            received_at: 0,
+            account: "foo@bar.com".to_string(),
+            backend: "Proton Mail".to_string(),
+            seen_hash: None,
+            priorities: Default::default(),
+        }
+    }
+
+    #[test]
+    fn only_records_the_notified_emails() {
+        let dir = std::env::temp_dir().join(format!("yhm-seen-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let seen = Arc::new(SeenEmails::open(&dir));
+        let mut emails = vec![email("Notified"), email("Held back")];
+        seen.retain_unseen("foo@bar.com", &mut emails);
+        assert_eq!(emails.len(), 2);
+        let notified = Event::NewEmail {
+            account: "foo@bar.com".to_string(),
+            backend: "Proton Mail".to_string(),
+            emails: emails[..1].to_vec(),
+            open_url: None,
+            redacted: false,
+        };
+        SeenRecorder(seen).notify(&notified);
+
+        let seen = SeenEmails::open(&dir);
+        let mut emails = vec![email("Notified"), email("Held back")];
+        seen.retain_unseen("foo@bar.com", &mut emails);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(emails.len(), 1);
+        assert_eq!(emails[0].subject, "Held back");
+    }
+}
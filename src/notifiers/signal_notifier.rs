@@ -0,0 +1,153 @@
+use crate::notifiers::circuit_breaker::{BreakerConfig, CircuitBreaker};
+use crate::notifiers::format::visible_emails;
+use crate::notifiers::http::{send_with_breaker, HttpConfig};
+use crate::notifiers::template::MessageTemplates;
+use crate::notifiers::worker::{default_queue_size, WorkerNotifier};
+use crate::notifiers::{Event, Notifier, NotifierConfig, NotifierContext, Shutdown};
+use anyhow::anyhow;
+use serde::Deserialize;
+use serde_json::json;
+use std::path::PathBuf;
+
+#[derive(Debug, Deserialize)]
+/// Configuration for a signal-cli REST API instance.
+pub struct SignalConfig {
+    pub name: String,
+    /// Only send the notifications of these accounts, all accounts if empty.
+    #[serde(default)]
+    pub accounts: Vec<String>,
+    /// Hide the email senders and subjects, only the number of new emails is sent.
+    #[serde(default)]
+    pub redact: bool,
+    /// Number of notifications waiting to be sent after which new ones are dropped.
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
+    /// Url of the signal-cli-rest-api server.
+    pub api_url: String,
+    /// Number registered with signal-cli the messages are sent from.
+    pub number: String,
+    /// Numbers or group ids the messages are sent to.
+    pub recipients: Vec<String>,
+    /// PEM file with additional root certificates, overrides the global `ca_bundle`.
+    pub ca_bundle: Option<PathBuf>,
+    /// Timeouts and User-Agent of the requests.
+    #[serde(flatten)]
+    pub http: HttpConfig,
+    /// Pause the requests after repeated failures.
+    #[serde(flatten)]
+    pub breaker: BreakerConfig,
+    /// Title of the new email notifications, see the `Message Templates` section of the README.
+    pub title_template: Option<String>,
+    /// Line of each email in the body of the new email notifications.
+    pub body_template: Option<String>,
+}
+
+impl NotifierConfig for SignalConfig {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn take_accounts(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.accounts)
+    }
+
+    fn redact(&self) -> bool {
+        self.redact
+    }
+
+    fn into_notifier(self, ctx: &NotifierContext) -> anyhow::Result<Box<dyn Notifier>> {
+        if self.recipients.is_empty() {
+            return Err(anyhow!("Signal notifier {} has no recipients", self.name));
+        }
+        let ca_bundle = self.ca_bundle.or_else(|| ctx.ca_bundle.clone());
+        let agent = ctx
+            .http_agents
+            .agent(&self.http.options(ca_bundle, &self.api_url))?;
+        let mut state = SignalNotifier {
+            name: self.name.clone(),
+            agent,
+            shutdown: ctx.shutdown.clone(),
+            url: format!("{}/v2/send", self.api_url.trim_end_matches('/')),
+            number: self.number,
+            recipients: self.recipients,
+            breaker: self.breaker.circuit_breaker("signal", &self.name, ctx),
+            templates: MessageTemplates::new(
+                self.title_template,
+                self.body_template,
+                "{sender}: {subject}",
+            ),
+        };
+        let notifier =
+            WorkerNotifier::spawn("signal", self.name, self.queue_size, ctx, move |event| {
+                state.handle(event)
+            })?;
+        Ok(Box::new(notifier))
+    }
+}
+
+/// Send notifications as plain text Signal messages through signal-cli-rest-api.
+struct SignalNotifier {
+    name: String,
+    agent: ureq::Agent,
+    shutdown: Shutdown,
+    url: String,
+    number: String,
+    recipients: Vec<String>,
+    breaker: CircuitBreaker,
+    templates: MessageTemplates,
+}
+
+impl SignalNotifier {
+    /// Returns true if the message was sent.
+    fn handle(&mut self, event: Event) -> bool {
+        let message = match event {
+            Event::NewEmail {
+                account,
+                emails,
+                open_url,
+                redacted,
+                ..
+            } => {
+                let visible = visible_emails(&emails, redacted);
+                let mut lines = vec![self.templates.title(&account, &emails, visible)];
+                lines.extend(self.templates.body_lines(&account, emails.len(), visible));
+                lines.extend(open_url);
+                lines.join("\n")
+            }
+            Event::AccountLoggedOut { account, .. } => {
+                format!("{account} logged out or session expired")
+            }
+            Event::AccountError { account, error, .. } => {
+                format!("{account} encountered an error\n{error}")
+            }
+            Event::ConfigError { error } => format!("Server Config Error\n{error}"),
+            Event::Error { error } => format!("Server Error\n{error}"),
+            Event::Info { title, message } => match message {
+                Some(message) => format!("{title}\n{message}"),
+                None => title,
+            },
+        };
+
+        self.send(message)
+    }
+
+    fn send(&mut self, message: String) -> bool {
+        let payload = json!({
+            "message": message,
+            "number": self.number,
+            "recipients": self.recipients,
+        });
+        let request = self
+            .agent
+            .post(&self.url)
+            .set("Content-Type", "application/json");
+        send_with_breaker(
+            "signal",
+            &self.name,
+            &mut self.breaker,
+            &self.shutdown,
+            &request,
+            payload.to_string().as_bytes(),
+        )
+    }
+}
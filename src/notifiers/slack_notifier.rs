@@ -0,0 +1,192 @@
+use crate::notifiers::circuit_breaker::{BreakerConfig, CircuitBreaker};
+use crate::notifiers::format::{truncate, visible_emails};
+use crate::notifiers::http::{send_with_breaker, HttpConfig};
+use crate::notifiers::worker::{default_queue_size, WorkerNotifier};
+use crate::notifiers::{Event, Notifier, NotifierConfig, NotifierContext, Shutdown};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use you_have_mail_common::{ExposeSecret, Secret};
+
+/// Slack limits, see https://api.slack.com/reference/block-kit/blocks
+const MAX_HEADER_CHARS: usize = 150;
+const MAX_SECTION_CHARS: usize = 3000;
+
+const ERROR_COLOR: &str = "#E74C3C";
+
+#[derive(Debug, Deserialize)]
+/// Configuration for a Slack incoming webhook.
+pub struct SlackConfig {
+    pub name: String,
+    /// Only send the notifications of these accounts, all accounts if empty.
+    #[serde(default)]
+    pub accounts: Vec<String>,
+    /// Hide the email senders and subjects, only the number of new emails is sent.
+    #[serde(default)]
+    pub redact: bool,
+    /// Number of notifications waiting to be sent after which new ones are dropped.
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
+    pub webhook_url: String,
+    /// PEM file with additional root certificates, overrides the global `ca_bundle`.
+    pub ca_bundle: Option<PathBuf>,
+    /// Timeouts and User-Agent of the requests.
+    #[serde(flatten)]
+    pub http: HttpConfig,
+    /// Pause the requests after repeated failures.
+    #[serde(flatten)]
+    pub breaker: BreakerConfig,
+}
+
+impl NotifierConfig for SlackConfig {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn take_accounts(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.accounts)
+    }
+
+    fn redact(&self) -> bool {
+        self.redact
+    }
+
+    fn into_notifier(self, ctx: &NotifierContext) -> anyhow::Result<Box<dyn Notifier>> {
+        let ca_bundle = self.ca_bundle.or_else(|| ctx.ca_bundle.clone());
+        let agent = ctx
+            .http_agents
+            .agent(&self.http.options(ca_bundle, &self.webhook_url))?;
+        let mut state = SlackNotifier {
+            name: self.name.clone(),
+            agent,
+            shutdown: ctx.shutdown.clone(),
+            webhook_url: Secret::new(self.webhook_url),
+            breaker: self.breaker.circuit_breaker("slack", &self.name, ctx),
+        };
+        let notifier =
+            WorkerNotifier::spawn("slack", self.name, self.queue_size, ctx, move |event| {
+                state.handle(event)
+            })?;
+        Ok(Box::new(notifier))
+    }
+}
+
+/// Post notifications as Block Kit messages to a Slack incoming webhook.
+struct SlackNotifier {
+    name: String,
+    agent: ureq::Agent,
+    shutdown: Shutdown,
+    /// The webhook url allows anyone to post to the channel.
+    webhook_url: Secret<String>,
+    breaker: CircuitBreaker,
+}
+
+impl SlackNotifier {
+    /// Returns true if the notification was posted.
+    fn handle(&mut self, event: Event) -> bool {
+        let payload = match event {
+            Event::NewEmail {
+                account,
+                emails,
+                open_url,
+                redacted,
+                ..
+            } => {
+                let title = format!("{account} has {} new message(s)", emails.len());
+                let mut blocks = vec![header_block(&title)];
+                let list = visible_emails(&emails, redacted)
+                    .iter()
+                    .map(|email| {
+                        format!(
+                            "• *{}*: {}",
+                            escape_mrkdwn(&email.sender),
+                            escape_mrkdwn(&email.subject)
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                if !list.is_empty() {
+                    blocks.push(section_block(&list));
+                }
+                if let Some(url) = open_url {
+                    blocks.push(json!({
+                        "type": "context",
+                        "elements": [{
+                            "type": "mrkdwn",
+                            "text": format!("<{}|Open webmail>", escape_mrkdwn(&url)),
+                        }],
+                    }));
+                }
+                json!({ "text": title, "blocks": blocks })
+            }
+            Event::AccountLoggedOut { account, .. } => {
+                let title = format!("{account} logged out or session expired");
+                json!({ "text": title, "blocks": [header_block(&title)] })
+            }
+            Event::AccountError { account, error, .. } => {
+                error_payload(&format!("{account} encountered an error"), &error)
+            }
+            Event::ConfigError { error } => error_payload("Server Config Error", &error),
+            Event::Error { error } => error_payload("Server Error", &error),
+            Event::Info { title, message } => {
+                let mut blocks = vec![header_block(&title)];
+                if let Some(message) = message {
+                    blocks.push(section_block(&escape_mrkdwn(&message)));
+                }
+                json!({ "text": title, "blocks": blocks })
+            }
+        };
+
+        self.send(payload)
+    }
+
+    fn send(&mut self, payload: Value) -> bool {
+        let request = self
+            .agent
+            .post(self.webhook_url.expose_secret())
+            .set("Content-Type", "application/json");
+        send_with_breaker(
+            "slack",
+            &self.name,
+            &mut self.breaker,
+            &self.shutdown,
+            &request,
+            payload.to_string().as_bytes(),
+        )
+    }
+}
+
+fn header_block(text: &str) -> Value {
+    json!({
+        "type": "header",
+        "text": {"type": "plain_text", "text": truncate(text, MAX_HEADER_CHARS)},
+    })
+}
+
+fn section_block(mrkdwn: &str) -> Value {
+    json!({
+        "type": "section",
+        "text": {"type": "mrkdwn", "text": truncate(mrkdwn, MAX_SECTION_CHARS)},
+    })
+}
+
+/// Errors are sent as an attachment, which Slack displays with a red bar.
+fn error_payload(title: &str, error: &str) -> Value {
+    json!({
+        "text": title,
+        "attachments": [{
+            "color": ERROR_COLOR,
+            "blocks": [
+                header_block(title),
+                section_block(&escape_mrkdwn(error)),
+            ],
+        }],
+    })
+}
+
+/// Escape the characters Slack interprets as control sequences in mrkdwn text.
+fn escape_mrkdwn(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
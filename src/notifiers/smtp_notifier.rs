@@ -0,0 +1,222 @@
+use crate::cfg::deserialize_optional_secret;
+use crate::notifiers::format::visible_emails;
+use crate::notifiers::template::MessageTemplates;
+use crate::notifiers::worker::{default_queue_size, WorkerNotifier};
+use crate::notifiers::{Event, Notifier, NotifierConfig, NotifierContext};
+use anyhow::anyhow;
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use log::{debug, error};
+use serde::Deserialize;
+use std::time::Duration;
+use you_have_mail_common::{ExposeSecret, Secret};
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How the connection to the SMTP server is secured.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Deserialize)]
+pub enum SmtpTls {
+    /// Upgrade the connection with STARTTLS, port 587 by default.
+    #[default]
+    StartTls,
+    /// Connect with TLS, port 465 by default.
+    Tls,
+    /// Plain text connection, port 25 by default. Only for servers on the local machine.
+    None,
+}
+
+#[derive(Debug, Deserialize)]
+/// Configuration for forwarding the notifications by email.
+pub struct SmtpConfig {
+    pub name: String,
+    /// Only send the notifications of these accounts, all accounts if empty.
+    #[serde(default)]
+    pub accounts: Vec<String>,
+    /// Hide the email senders and subjects, only the number of new emails is sent.
+    #[serde(default)]
+    pub redact: bool,
+    /// Number of notifications waiting to be sent after which new ones are dropped.
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
+    pub host: String,
+    /// Port of the server, depends on `tls` if not set.
+    pub port: Option<u16>,
+    pub username: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_optional_secret")]
+    pub password: Option<Secret<String>>,
+    /// Sender address of the emails.
+    pub from: String,
+    /// Address the emails are sent to.
+    pub to: String,
+    #[serde(default)]
+    pub tls: SmtpTls,
+    /// Subject of the new email notifications, see the `Message Templates` section of the README.
+    pub title_template: Option<String>,
+    /// Line of each email in the body of the new email notifications.
+    pub body_template: Option<String>,
+}
+
+impl NotifierConfig for SmtpConfig {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn take_accounts(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.accounts)
+    }
+
+    fn redact(&self) -> bool {
+        self.redact
+    }
+
+    fn into_notifier(self, ctx: &NotifierContext) -> anyhow::Result<Box<dyn Notifier>> {
+        let from = self
+            .from
+            .parse::<Mailbox>()
+            .map_err(|e| anyhow!("Invalid from address '{}': {e}", self.from))?;
+        let to = self
+            .to
+            .parse::<Mailbox>()
+            .map_err(|e| anyhow!("Invalid to address '{}': {e}", self.to))?;
+
+        let mut builder = match self.tls {
+            SmtpTls::StartTls => SmtpTransport::starttls_relay(&self.host),
+            SmtpTls::Tls => SmtpTransport::relay(&self.host),
+            SmtpTls::None => Ok(SmtpTransport::builder_dangerous(&self.host)),
+        }
+        .map_err(|e| anyhow!("Failed to set up TLS for {}: {e}", self.host))?
+        .timeout(Some(TIMEOUT));
+        if let Some(port) = self.port {
+            builder = builder.port(port);
+        }
+        match (self.username, self.password) {
+            (Some(username), Some(password)) => {
+                let password = password.expose_secret().clone();
+                builder = builder.credentials(Credentials::new(username, password));
+            }
+            (None, None) => {}
+            _ => return Err(anyhow!("Both username and password need to be set")),
+        }
+        let transport = builder.build();
+
+        // Report wrong credentials or TLS settings right away rather than on the first email.
+        match transport.test_connection() {
+            Ok(true) => {}
+            Ok(false) => return Err(anyhow!("Could not connect to {}", self.host)),
+            Err(e) => return Err(anyhow!(describe_error(&self.host, &e))),
+        }
+
+        let mut state = SmtpNotifier {
+            name: self.name.clone(),
+            host: self.host,
+            transport,
+            from,
+            to,
+            templates: MessageTemplates::new(
+                self.title_template,
+                self.body_template,
+                "{time} {sender}: {subject}",
+            ),
+        };
+        let notifier =
+            WorkerNotifier::spawn("smtp", self.name, self.queue_size, ctx, move |event| {
+                state.handle(event)
+            })?;
+        Ok(Box::new(notifier))
+    }
+}
+
+fn describe_error(host: &str, e: &smtp::Error) -> String {
+    if e.is_tls() {
+        format!("TLS connection to {host} failed, check the tls and port settings: {e}")
+    } else if e.is_permanent() {
+        format!("{host} refused the request, check the username and password: {e}")
+    } else {
+        format!("Failed to connect to {host}: {e}")
+    }
+}
+
+/// Send the notifications as plain text emails.
+struct SmtpNotifier {
+    name: String,
+    host: String,
+    transport: SmtpTransport,
+    from: Mailbox,
+    to: Mailbox,
+    templates: MessageTemplates,
+}
+
+impl SmtpNotifier {
+    /// Returns true if the server accepted the email.
+    fn handle(&mut self, event: Event) -> bool {
+        let (subject, body) = match event {
+            Event::NewEmail {
+                account,
+                emails,
+                open_url,
+                redacted,
+                ..
+            } => {
+                let visible = visible_emails(&emails, redacted);
+                let subject = self.templates.title(&account, &emails, visible);
+                let mut lines = self
+                    .templates
+                    .body_lines(&account, emails.len(), visible)
+                    .collect::<Vec<_>>();
+                if lines.is_empty() {
+                    lines.push(subject.clone());
+                }
+                if let Some(url) = open_url {
+                    lines.push(String::new());
+                    lines.push(url);
+                }
+                (subject, lines.join("\n"))
+            }
+            Event::AccountLoggedOut { account, .. } => {
+                let subject = format!("{account} logged out or session expired");
+                (subject.clone(), subject)
+            }
+            Event::AccountError { account, error, .. } => {
+                (format!("{account} encountered an error"), error)
+            }
+            Event::ConfigError { error } => ("Server Config Error".to_string(), error),
+            Event::Error { error } => ("Server Error".to_string(), error),
+            Event::Info { title, message } => {
+                let body = message.unwrap_or_else(|| title.clone());
+                (title, body)
+            }
+        };
+
+        let message = match Message::builder()
+            .from(self.from.clone())
+            .to(self.to.clone())
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(body)
+        {
+            Ok(message) => message,
+            Err(e) => {
+                error!("Failed to build email ({}): {e}", self.name);
+                return false;
+            }
+        };
+
+        match self.transport.send(&message) {
+            Ok(_) => {
+                debug!("Notification successfully emailed by smtp {}", self.name);
+                true
+            }
+            Err(e) => {
+                error!(
+                    "Failed to send email ({}): {}",
+                    self.name,
+                    describe_error(&self.host, &e)
+                );
+                false
+            }
+        }
+    }
+}
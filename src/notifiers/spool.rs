@@ -0,0 +1,107 @@
+//! On-disk queue of the notifications a notifier failed to deliver.
+use crate::notifiers::Event;
+use crate::utils::write_user_file;
+use log::warn;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+
+/// Maximum number of notifications kept, the oldest are dropped first.
+const MAX_SPOOLED: usize = 100;
+
+/// Undelivered notifications of one notifier, stored as JSON lines so they survive a restart.
+pub struct FailedNotificationQueue {
+    path: PathBuf,
+    events: VecDeque<Event>,
+}
+
+impl FailedNotificationQueue {
+    /// Load the queue of the notifier `kind` called `name` from `dir`.
+    pub fn open(dir: &Path, kind: &str, name: &str) -> Self {
+        let file_name = format!("spool_{kind}_{}.jsonl", sanitize(name));
+        let path = dir.join(file_name);
+        let events = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .filter_map(|line| match serde_json::from_str(line) {
+                    Ok(event) => Some(event),
+                    Err(e) => {
+                        warn!("Skipping invalid spooled notification in {path:?}: {e}");
+                        None
+                    }
+                })
+                .collect(),
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Failed to read spooled notifications {path:?}: {e}");
+                }
+                VecDeque::new()
+            }
+        };
+
+        Self { path, events }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn push(&mut self, event: Event) {
+        if self.events.len() >= MAX_SPOOLED {
+            warn!(
+                "Spool {:?} is full, dropping the oldest notification",
+                self.path
+            );
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+        self.save();
+    }
+
+    /// Oldest spooled notification.
+    pub fn front(&self) -> Option<&Event> {
+        self.events.front()
+    }
+
+    /// Remove the oldest notification after it was delivered.
+    pub fn pop(&mut self) {
+        if self.events.pop_front().is_some() {
+            self.save();
+        }
+    }
+
+    fn save(&self) {
+        if self.events.is_empty() {
+            if let Err(e) = std::fs::remove_file(&self.path) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Failed to remove spool {:?}: {e}", self.path);
+                }
+            }
+            return;
+        }
+
+        let mut contents = Vec::new();
+        for event in &self.events {
+            if let Err(e) = serde_json::to_writer(&mut contents, event) {
+                warn!("Failed to serialize spooled notification: {e}");
+                continue;
+            }
+            contents.push(b'\n');
+        }
+
+        if let Err(e) = write_user_file(&self.path, &contents) {
+            warn!("Failed to write spool {:?}: {e}", self.path);
+        }
+    }
+}
+
+/// Keep notifier names usable as part of a file name.
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
@@ -0,0 +1,275 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// State of the circuit breaker of an HTTP notifier.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    /// Requests are attempted.
+    Closed,
+    /// Too many consecutive failures, requests are not attempted until the cooldown expires.
+    Open,
+    /// Cooldown expired, the next request tests whether the endpoint recovered.
+    HalfOpen,
+}
+
+impl BreakerState {
+    #[cfg(feature = "metrics")]
+    pub const ALL: [BreakerState; 3] = [Self::Closed, Self::Open, Self::HalfOpen];
+
+    /// State of a breaker pausing the requests until the unix time `paused_until` in seconds.
+    pub fn paused_until(paused_until: Option<u64>) -> Self {
+        match paused_until {
+            None => Self::Closed,
+            Some(until) if until > unix_now() => Self::Open,
+            Some(_) => Self::HalfOpen,
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Closed => "closed",
+            Self::Open => "open",
+            Self::HalfOpen => "half_open",
+        }
+    }
+}
+
+/// Counters accumulated while the observer is running.
+#[derive(Default)]
+pub struct SessionStats {
+    new_emails: Mutex<BTreeMap<String, u64>>,
+    delivered: AtomicU64,
+    dropped: AtomicU64,
+    /// Events received from the observer by kind.
+    events: Mutex<BTreeMap<&'static str, u64>>,
+    /// Notifications handled by each notifier, by event kind and whether they were delivered.
+    notifications: Mutex<BTreeMap<(String, &'static str, bool), u64>>,
+    successful_polls: AtomicU64,
+    failed_polls: AtomicU64,
+    /// Unix time in seconds of the last poll, 0 before the first one.
+    last_poll: AtomicU64,
+    logged_in: Mutex<BTreeMap<String, bool>>,
+    /// Errors reported for each account since they were last taken.
+    account_errors: Mutex<BTreeMap<String, String>>,
+    /// Unix time in seconds until which the circuit breaker of each HTTP notifier pauses its
+    /// requests, `None` if it doesn't.
+    breakers: Mutex<BTreeMap<String, Option<u64>>>,
+}
+
+impl SessionStats {
+    pub fn record_new_emails(&self, account: &str, count: usize) {
+        let mut new_emails = self.new_emails.lock().unwrap();
+        *new_emails.entry(account.to_string()).or_default() += count as u64;
+    }
+
+    /// A notifier successfully delivered a notification.
+    pub fn record_delivered(&self) {
+        self.delivered.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A notifier failed to deliver a notification.
+    pub fn record_dropped(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The observer reported an event of `kind`.
+    pub fn record_event(&self, kind: &'static str) {
+        *self.events.lock().unwrap().entry(kind).or_default() += 1;
+    }
+
+    /// The notifier called `notifier` handled an event of `kind`.
+    pub fn record_notification(&self, notifier: &str, kind: &'static str, delivered: bool) {
+        let mut notifications = self.notifications.lock().unwrap();
+        *notifications
+            .entry((notifier.to_string(), kind, delivered))
+            .or_default() += 1;
+    }
+
+    pub fn record_poll(&self, success: bool) {
+        if success {
+            self.successful_polls.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.failed_polls.fetch_add(1, Ordering::Relaxed);
+        }
+        self.last_poll.store(unix_now(), Ordering::Relaxed);
+    }
+
+    pub fn record_login_state(&self, account: &str, logged_in: bool) {
+        self.logged_in
+            .lock()
+            .unwrap()
+            .insert(account.to_string(), logged_in);
+    }
+
+    pub fn record_account_error(&self, account: &str, error: &str) {
+        self.account_errors
+            .lock()
+            .unwrap()
+            .insert(account.to_string(), error.to_string());
+    }
+
+    /// The circuit breaker of `notifier` pauses its requests until the unix time `paused_until`,
+    /// or not at all if `None`.
+    pub fn record_breaker(&self, notifier: &str, paused_until: Option<u64>) {
+        self.breakers
+            .lock()
+            .unwrap()
+            .insert(notifier.to_string(), paused_until);
+    }
+
+    /// Time until which the circuit breaker of each HTTP notifier pauses its requests.
+    pub fn breakers(&self) -> BTreeMap<String, Option<u64>> {
+        self.breakers.lock().unwrap().clone()
+    }
+
+    /// The latest error of each account reported since the previous call.
+    pub fn take_account_errors(&self) -> BTreeMap<String, String> {
+        std::mem::take(&mut *self.account_errors.lock().unwrap())
+    }
+
+    /// Number of notifications delivered so far.
+    pub fn delivered(&self) -> u64 {
+        self.delivered.load(Ordering::Relaxed)
+    }
+
+    /// Human readable summary of the session, one line per entry.
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines = self
+            .new_emails
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(account, count)| format!("{account}: {count} new email(s)"))
+            .collect::<Vec<_>>();
+        if lines.is_empty() {
+            lines.push("No new emails".to_string());
+        }
+        lines.push(format!(
+            "Notifications: {} delivered, {} dropped",
+            self.delivered.load(Ordering::Relaxed),
+            self.dropped.load(Ordering::Relaxed)
+        ));
+        lines
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl SessionStats {
+    /// Counters in the Prometheus text exposition format.
+    pub fn prometheus(&self) -> String {
+        let mut out = String::new();
+
+        metric_header(
+            &mut out,
+            "yhm_events_total",
+            "Events reported by the observer.",
+            "counter",
+        );
+        for (kind, count) in self.events.lock().unwrap().iter() {
+            out.push_str(&format!("yhm_events_total{{event=\"{kind}\"}} {count}\n"));
+        }
+
+        metric_header(
+            &mut out,
+            "yhm_notifications_total",
+            "Notifications handled by each notifier.",
+            "counter",
+        );
+        for ((notifier, kind, delivered), count) in self.notifications.lock().unwrap().iter() {
+            let result = if *delivered { "delivered" } else { "dropped" };
+            out.push_str(&format!(
+                "yhm_notifications_total{{notifier=\"{}\",event=\"{kind}\",result=\"{result}\"}} {count}\n",
+                escape_label(notifier)
+            ));
+        }
+
+        metric_header(
+            &mut out,
+            "yhm_new_emails_total",
+            "New emails received by each account.",
+            "counter",
+        );
+        for (account, count) in self.new_emails.lock().unwrap().iter() {
+            out.push_str(&format!(
+                "yhm_new_emails_total{{account=\"{}\"}} {count}\n",
+                escape_label(account)
+            ));
+        }
+
+        metric_header(&mut out, "yhm_polls_total", "Polls by result.", "counter");
+        out.push_str(&format!(
+            "yhm_polls_total{{result=\"success\"}} {}\nyhm_polls_total{{result=\"failure\"}} {}\n",
+            self.successful_polls.load(Ordering::Relaxed),
+            self.failed_polls.load(Ordering::Relaxed)
+        ));
+
+        metric_header(
+            &mut out,
+            "yhm_last_poll_timestamp_seconds",
+            "Unix time of the last poll.",
+            "gauge",
+        );
+        out.push_str(&format!(
+            "yhm_last_poll_timestamp_seconds {}\n",
+            self.last_poll.load(Ordering::Relaxed)
+        ));
+
+        metric_header(
+            &mut out,
+            "yhm_account_logged_in",
+            "Whether each account is logged in.",
+            "gauge",
+        );
+        for (account, logged_in) in self.logged_in.lock().unwrap().iter() {
+            out.push_str(&format!(
+                "yhm_account_logged_in{{account=\"{}\"}} {}\n",
+                escape_label(account),
+                u8::from(*logged_in)
+            ));
+        }
+
+        metric_header(
+            &mut out,
+            "yhm_notifier_breaker_state",
+            "State of the circuit breaker of each HTTP notifier.",
+            "gauge",
+        );
+        for (notifier, paused_until) in self.breakers.lock().unwrap().iter() {
+            let current = BreakerState::paused_until(*paused_until);
+            for state in BreakerState::ALL {
+                out.push_str(&format!(
+                    "yhm_notifier_breaker_state{{notifier=\"{}\",state=\"{}\"}} {}\n",
+                    escape_label(notifier),
+                    state.as_str(),
+                    u8::from(state == current)
+                ));
+            }
+        }
+        out
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(feature = "metrics")]
+fn metric_header(out: &mut String, name: &str, help: &str, kind: &str) {
+    out.push_str(&format!("# HELP {name} {help}\n# TYPE {name} {kind}\n"));
+}
+
+#[cfg(feature = "metrics")]
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -1,40 +1,71 @@
-use crate::notifiers::Notifier;
-use you_have_mail_common::Notification;
+use crate::notifiers::format::visible_emails;
+use crate::notifiers::{Event, Notifier, NotifierContext};
+use log::error;
+use std::fmt::Write as FmtWrite;
+use std::io::Write;
 
 /// Write notifications to stdout.
-#[derive(Debug, Copy, Clone)]
-pub struct StdOutNotifier {}
+pub struct StdOutNotifier {
+    pub ctx: NotifierContext,
+}
 
 impl Notifier for StdOutNotifier {
-    fn notify(&self, notification: &Notification) {
-        match notification {
-            Notification::NewEmail {
+    fn notify(&self, event: &Event) {
+        let mut output = String::new();
+        match event {
+            Event::NewEmail {
                 account,
                 backend,
                 emails,
+                redacted,
+                ..
             } => {
-                println!(
+                let _ = writeln!(
+                    output,
                     "Account {account} ({backend}) received {} new email(s)",
                     emails.len()
                 );
-                for e in emails.iter() {
-                    println!("    Sender={} Subject={}", e.sender, e.subject);
+                for e in visible_emails(emails, *redacted) {
+                    let _ = writeln!(
+                        output,
+                        "    Sender={} Subject={} Received={}",
+                        e.sender,
+                        e.subject,
+                        e.received_time()
+                    );
                 }
             }
-            Notification::AccountLoggedOut(account) => {
-                println!("Account {account} Logged out or Session Expired");
+            Event::AccountLoggedOut { account, .. } => {
+                let _ = writeln!(output, "Account {account} Logged out or Session Expired");
+            }
+            Event::AccountError { account, error, .. } => {
+                let _ = writeln!(output, "Account {account} ran into an error: {}", error);
             }
-            Notification::AccountError(account, error) => {
-                println!("Account {account} ran into an error: {}", error)
+            Event::ConfigError { error } => {
+                let _ = writeln!(output, "Configuration error: {}", error);
             }
-            Notification::ProxyApplied(_, _) => {}
-            Notification::ConfigError(error) => {
-                println!("Configuration error: {}", error);
+            Event::Error { error } => {
+                let _ = writeln!(output, "An error occurred: {}", error);
             }
-            Notification::Error(error) => {
-                println!("An error occurred: {}", error);
+            Event::Info { title, message } => {
+                let _ = writeln!(output, "{title}");
+                if let Some(message) = message {
+                    for line in message.lines() {
+                        let _ = writeln!(output, "    {line}");
+                    }
+                }
             }
-            _ => {}
         }
+
+        // Unlike `println!` this does not panic when stdout can't be written to
+        // (e.g. redirected to a file on a full disk).
+        let delivered = match std::io::stdout().lock().write_all(output.as_bytes()) {
+            Ok(()) => true,
+            Err(e) => {
+                error!("Failed to write notification to stdout: {e}");
+                false
+            }
+        };
+        self.ctx.record_delivery("stdout", event, delivered);
     }
 }
@@ -0,0 +1,196 @@
+use crate::notifiers::format::visible_emails;
+use crate::notifiers::{Event, Notifier, NotifierConfig, NotifierContext};
+use anyhow::anyhow;
+use log::error;
+use serde::Deserialize;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::str::FromStr;
+use std::sync::Mutex;
+use syslog::{Facility, Formatter3164, Formatter5424, LogFormat, Logger, LoggerBackend, Severity};
+
+const PROCESS: &str = "you-have-mail-cli";
+const DEFAULT_PORT: u16 = 514;
+
+#[derive(Debug, Deserialize)]
+/// Configuration for the syslog notifier.
+pub struct SyslogConfig {
+    pub name: String,
+    /// Only send the notifications of these accounts, all accounts if empty.
+    #[serde(default)]
+    pub accounts: Vec<String>,
+    /// Hide the email senders and subjects, only the number of new emails is sent.
+    #[serde(default)]
+    pub redact: bool,
+    /// Syslog facility such as "user", "mail" or "local0".
+    #[serde(default = "default_facility")]
+    pub facility: String,
+    /// `host[:port]` of a remote syslog server receiving RFC 5424 messages over UDP, the local
+    /// syslog is used if not set.
+    pub remote: Option<String>,
+}
+
+fn default_facility() -> String {
+    "user".to_string()
+}
+
+impl NotifierConfig for SyslogConfig {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn take_accounts(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.accounts)
+    }
+
+    fn redact(&self) -> bool {
+        self.redact
+    }
+
+    fn into_notifier(self, ctx: &NotifierContext) -> anyhow::Result<Box<dyn Notifier>> {
+        let facility = Facility::from_str(&self.facility)
+            .map_err(|_| anyhow!("Unknown syslog facility '{}'", self.facility))?;
+        let logger = match &self.remote {
+            Some(remote) => SyslogLogger::Remote(connect_remote(remote, facility)?),
+            None => SyslogLogger::Local(connect_local(facility)?),
+        };
+
+        Ok(Box::new(SyslogNotifier {
+            name: self.name,
+            ctx: ctx.clone(),
+            logger: Mutex::new(logger),
+        }))
+    }
+}
+
+#[cfg(unix)]
+fn connect_local(facility: Facility) -> anyhow::Result<Logger<LoggerBackend, Formatter3164>> {
+    syslog::unix(Formatter3164 {
+        facility,
+        hostname: None,
+        process: PROCESS.to_string(),
+        pid: std::process::id(),
+    })
+    .map_err(|e| anyhow!("Failed to connect to the local syslog: {e}"))
+}
+
+#[cfg(not(unix))]
+fn connect_local(_: Facility) -> anyhow::Result<Logger<LoggerBackend, Formatter3164>> {
+    Err(anyhow!(
+        "The local syslog is only available on Unix, set `remote` to a syslog server"
+    ))
+}
+
+fn connect_remote(
+    remote: &str,
+    facility: Facility,
+) -> anyhow::Result<Logger<LoggerBackend, Formatter5424>> {
+    let addrs = match remote.to_socket_addrs() {
+        Ok(addrs) => Ok(addrs),
+        // The port is optional.
+        Err(_) => (remote, DEFAULT_PORT).to_socket_addrs(),
+    };
+    let server = addrs
+        .map_err(|e| anyhow!("Invalid syslog server '{remote}': {e}"))?
+        .next()
+        .ok_or_else(|| anyhow!("Syslog server '{remote}' has no address"))?;
+    let local: SocketAddr = if server.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    }
+    .parse()?;
+
+    let hostname = std::env::var("HOSTNAME").ok();
+    syslog::udp(
+        Formatter5424 {
+            facility,
+            hostname,
+            process: PROCESS.to_string(),
+            pid: std::process::id(),
+        },
+        local,
+        server,
+    )
+    .map_err(|e| anyhow!("Failed to connect to syslog server '{remote}': {e}"))
+}
+
+enum SyslogLogger {
+    Local(Logger<LoggerBackend, Formatter3164>),
+    Remote(Logger<LoggerBackend, Formatter5424>),
+}
+
+impl SyslogLogger {
+    fn send(&mut self, severity: Severity, message: String) -> Result<(), String> {
+        match self {
+            SyslogLogger::Local(logger) => send_with(logger, severity, message),
+            SyslogLogger::Remote(logger) => {
+                send_with(logger, severity, (0, Default::default(), message))
+            }
+        }
+    }
+}
+
+fn send_with<F: LogFormat<T>, T>(
+    logger: &mut Logger<LoggerBackend, F>,
+    severity: Severity,
+    message: T,
+) -> Result<(), String> {
+    let result = match severity {
+        Severity::LOG_ERR => logger.err(message),
+        Severity::LOG_WARNING => logger.warning(message),
+        _ => logger.info(message),
+    };
+    result.map_err(|e| e.to_string())
+}
+
+/// Write the notifications to syslog, one line per notification.
+struct SyslogNotifier {
+    name: String,
+    ctx: NotifierContext,
+    logger: Mutex<SyslogLogger>,
+}
+
+impl Notifier for SyslogNotifier {
+    fn notify(&self, event: &Event) {
+        let (severity, message) = match event {
+            Event::NewEmail {
+                account,
+                emails,
+                redacted,
+                ..
+            } => {
+                let mut message = format!("{account} has {} new message(s)", emails.len());
+                for email in visible_emails(emails, *redacted) {
+                    message.push_str(&format!("; {}: {}", email.sender, email.subject));
+                }
+                (Severity::LOG_INFO, message)
+            }
+            Event::AccountLoggedOut { account, .. } => (
+                Severity::LOG_WARNING,
+                format!("{account} logged out or session expired"),
+            ),
+            Event::AccountError { account, error, .. } => (
+                Severity::LOG_ERR,
+                format!("{account} encountered an error: {error}"),
+            ),
+            Event::ConfigError { error } => {
+                (Severity::LOG_ERR, format!("Configuration error: {error}"))
+            }
+            Event::Error { error } => (Severity::LOG_ERR, format!("An error occurred: {error}")),
+            Event::Info { title, message } => match message {
+                Some(message) => (Severity::LOG_INFO, format!("{title}: {message}")),
+                None => (Severity::LOG_INFO, title.clone()),
+            },
+        };
+
+        let delivered = match self.logger.lock().unwrap().send(severity, message) {
+            Ok(()) => true,
+            Err(e) => {
+                error!("Failed to write to syslog ({}): {e}", self.name);
+                false
+            }
+        };
+        self.ctx
+            .record_delivery(&format!("syslog ({})", self.name), event, delivered);
+    }
+}
@@ -0,0 +1,187 @@
+use crate::cfg::deserialize_secret;
+use crate::notifiers::circuit_breaker::{BreakerConfig, CircuitBreaker};
+use crate::notifiers::format::visible_emails;
+use crate::notifiers::http::{send_with_breaker, HttpConfig};
+use crate::notifiers::worker::{default_queue_size, WorkerNotifier};
+use crate::notifiers::{Event, Notifier, NotifierConfig, NotifierContext, Shutdown};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::PathBuf;
+use you_have_mail_common::{ExposeSecret, Secret};
+
+const TELEGRAM_API_URL: &str = "https://api.telegram.org";
+
+#[derive(Debug, Deserialize)]
+/// Configuration for a Telegram bot.
+pub struct TelegramConfig {
+    pub name: String,
+    /// Only send the notifications of these accounts, all accounts if empty.
+    #[serde(default)]
+    pub accounts: Vec<String>,
+    /// Hide the email senders and subjects, only the number of new emails is sent.
+    #[serde(default)]
+    pub redact: bool,
+    /// Number of notifications waiting to be sent after which new ones are dropped.
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
+    #[serde(deserialize_with = "deserialize_secret")]
+    pub bot_token: Secret<String>,
+    /// Numeric id of the chat or `@channelname`.
+    pub chat_id: ChatId,
+    /// PEM file with additional root certificates, overrides the global `ca_bundle`.
+    pub ca_bundle: Option<PathBuf>,
+    /// Timeouts and User-Agent of the requests.
+    #[serde(flatten)]
+    pub http: HttpConfig,
+    /// Pause the requests after repeated failures.
+    #[serde(flatten)]
+    pub breaker: BreakerConfig,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum ChatId {
+    Id(i64),
+    Username(String),
+}
+
+impl NotifierConfig for TelegramConfig {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn take_accounts(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.accounts)
+    }
+
+    fn redact(&self) -> bool {
+        self.redact
+    }
+
+    fn into_notifier(self, ctx: &NotifierContext) -> anyhow::Result<Box<dyn Notifier>> {
+        let ca_bundle = self.ca_bundle.or_else(|| ctx.ca_bundle.clone());
+        let agent = ctx
+            .http_agents
+            .agent(&self.http.options(ca_bundle, TELEGRAM_API_URL))?;
+        let mut state = TelegramNotifier {
+            name: self.name.clone(),
+            agent,
+            shutdown: ctx.shutdown.clone(),
+            bot_token: self.bot_token,
+            chat_id: self.chat_id,
+            breaker: self.breaker.circuit_breaker("telegram", &self.name, ctx),
+        };
+        let notifier =
+            WorkerNotifier::spawn("telegram", self.name, self.queue_size, ctx, move |event| {
+                state.handle(event)
+            })?;
+        Ok(Box::new(notifier))
+    }
+}
+
+/// Send notifications as messages of a Telegram bot.
+struct TelegramNotifier {
+    name: String,
+    agent: ureq::Agent,
+    shutdown: Shutdown,
+    bot_token: Secret<String>,
+    chat_id: ChatId,
+    breaker: CircuitBreaker,
+}
+
+impl TelegramNotifier {
+    /// Returns true if the message was posted.
+    fn handle(&mut self, event: Event) -> bool {
+        match event {
+            Event::NewEmail {
+                account,
+                emails,
+                open_url,
+                redacted,
+                ..
+            } => {
+                let mut text = format!(
+                    "*{}*",
+                    escape_markdown(&format!("{account} has {} new message(s)", emails.len()))
+                );
+                for email in visible_emails(&emails, redacted) {
+                    text.push_str(&format!(
+                        "\n*{}*: {}",
+                        escape_markdown(&email.sender),
+                        escape_markdown(&email.subject)
+                    ));
+                }
+                self.send(text, open_url)
+            }
+            Event::AccountLoggedOut { account, .. } => self.send(
+                escape_markdown(&format!("{account} logged out or session expired")),
+                None,
+            ),
+            Event::AccountError { account, error, .. } => {
+                self.error_message(&format!("{account} encountered an error"), &error)
+            }
+            Event::ConfigError { error } => self.error_message("Server Config Error", &error),
+            Event::Error { error } => self.error_message("Server Error", &error),
+            Event::Info { title, message } => {
+                let mut text = format!("*{}*", escape_markdown(&title));
+                if let Some(message) = message {
+                    text.push('\n');
+                    text.push_str(&escape_markdown(&message));
+                }
+                self.send(text, None)
+            }
+        }
+    }
+
+    fn error_message(&mut self, title: &str, error: &str) -> bool {
+        let text = format!(
+            "❗ *{}*\n{}",
+            escape_markdown(title),
+            escape_markdown(error)
+        );
+        self.send(text, None)
+    }
+
+    /// Post `text` formatted as MarkdownV2, with a button opening `open_url` if set.
+    fn send(&mut self, text: String, open_url: Option<String>) -> bool {
+        let mut message = json!({
+            "chat_id": self.chat_id,
+            "text": text,
+            "parse_mode": "MarkdownV2",
+        });
+        if let Some(url) = open_url {
+            message["reply_markup"] = json!({
+                "inline_keyboard": [[{"text": "Open", "url": url}]],
+            });
+        }
+
+        let url = format!(
+            "{TELEGRAM_API_URL}/bot{}/sendMessage",
+            self.bot_token.expose_secret()
+        );
+        let request = self
+            .agent
+            .post(&url)
+            .set("Content-Type", "application/json");
+        send_with_breaker(
+            "telegram",
+            &self.name,
+            &mut self.breaker,
+            &self.shutdown,
+            &request,
+            message.to_string().as_bytes(),
+        )
+    }
+}
+
+/// Escape the characters which have a special meaning in Telegram's MarkdownV2.
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "_*[]()~`>#+-=|{}.!\\".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
@@ -0,0 +1,225 @@
+//! User defined title and body of the new email notifications.
+use crate::notifiers::Email;
+
+/// Title used when the notifier has no `title_template`.
+const DEFAULT_TITLE: &str = "{account} has {count} new message(s)";
+
+/// Title used when the notifier has no `title_template` and the emails of several accounts were
+/// merged into one notification.
+const DEFAULT_MERGED_TITLE: &str = "{account_count} accounts have {count} new message(s)";
+
+/// Title and per email body line of the new email notifications. The templates support the
+/// `{account}`, `{account_count}` and `{count}` placeholders, and `{sender}`, `{subject}` and
+/// `{time}` of the email. The title uses the first email, or empty values if the email details
+/// are hidden.
+pub struct MessageTemplates {
+    /// `None` for the default titles.
+    title: Option<String>,
+    body: String,
+}
+
+impl MessageTemplates {
+    /// `default_body` is the notifier's line for each email when no `body_template` is set.
+    pub fn new(title: Option<String>, body: Option<String>, default_body: &str) -> Self {
+        Self {
+            title,
+            body: body.unwrap_or_else(|| default_body.to_string()),
+        }
+    }
+
+    /// Title of a notification of the new `emails`, of which `visible` can be displayed.
+    pub fn title(&self, account: &str, emails: &[Email], visible: &[Email]) -> String {
+        let account_count = account_count(emails);
+        let title = match &self.title {
+            Some(title) => title,
+            None if account_count > 1 => DEFAULT_MERGED_TITLE,
+            None => DEFAULT_TITLE,
+        };
+        let values = Values {
+            account,
+            account_count,
+            count: emails.len(),
+        };
+        render(title, &values, visible.first())
+    }
+
+    /// Body line of each of the `visible` emails.
+    pub fn body_lines<'a>(
+        &'a self,
+        account: &'a str,
+        count: usize,
+        visible: &'a [Email],
+    ) -> impl Iterator<Item = String> + 'a {
+        let values = Values {
+            account,
+            account_count: account_count(visible),
+            count,
+        };
+        visible
+            .iter()
+            .map(move |email| render(&self.body, &values, Some(email)))
+    }
+}
+
+/// Values of the placeholders which don't depend on the email.
+struct Values<'a> {
+    account: &'a str,
+    account_count: usize,
+    count: usize,
+}
+
+/// Number of accounts the `emails` were received by, 1 if they don't know theirs.
+fn account_count(emails: &[Email]) -> usize {
+    let mut accounts = emails
+        .iter()
+        .map(|e| e.account.as_str())
+        .filter(|a| !a.is_empty())
+        .collect::<Vec<_>>();
+    accounts.sort_unstable();
+    accounts.dedup();
+    accounts.len().max(1)
+}
+
+/// Substitute the placeholders in a single pass, so placeholders in the values are kept as is.
+fn render(template: &str, values: &Values, email: Option<&Email>) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let placeholder = rest[start..]
+            .find('}')
+            .map(|end| &rest[start..=start + end]);
+        let value = match placeholder {
+            Some("{account}") => values.account.to_string(),
+            Some("{account_count}") => values.account_count.to_string(),
+            Some("{count}") => values.count.to_string(),
+            Some("{sender}") => email.map(|e| e.sender.clone()).unwrap_or_default(),
+            Some("{subject}") => email.map(|e| e.subject.clone()).unwrap_or_default(),
+            Some("{time}") => email.map(Email::received_time).unwrap_or_default(),
+            _ => {
+                rendered.push('{');
+                rest = &rest[start + 1..];
+                continue;
+            }
+        };
+        rendered.push_str(&value);
+        rest = &rest[start + placeholder.map_or(1, str::len)..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(account: &str, count: usize) -> Values<'_> {
+        Values {
+            account,
+            account_count: 1,
+            count,
+        }
+    }
+
+    fn email(sender: &str, subject: &str) -> Email {
+        Email {
+            sender: sender.to_string(),
+            subject: subject.to_string(),
+            received_at: 0,
+            account: String::new(),
+            backend: String::new(),
+            seen_hash: None,
+            priorities: Default::default(),
+        }
+    }
+
+    #[test]
+    fn render_substitutes_placeholders() {
+        let email = email("alice@bar.com", "Hello");
+        assert_eq!(
+            render(
+                "{account} ({count}): {sender} - {subject}",
+                &values("foo@bar.com", 2),
+                Some(&email)
+            ),
+            "foo@bar.com (2): alice@bar.com - Hello"
+        );
+    }
+
+    #[test]
+    fn render_keeps_unknown_and_unclosed_placeholders() {
+        assert_eq!(
+            render("{foo} {count} {", &values("foo@bar.com", 1), None),
+            "{foo} 1 {"
+        );
+    }
+
+    #[test]
+    fn render_keeps_placeholders_in_values() {
+        let email = email("{account}", "{subject}");
+        assert_eq!(
+            render(
+                "{sender} {subject}",
+                &values("foo@bar.com", 1),
+                Some(&email)
+            ),
+            "{account} {subject}"
+        );
+    }
+
+    #[test]
+    fn render_without_email_leaves_email_fields_empty() {
+        assert_eq!(
+            render(
+                "[{time}] {sender}: {subject}",
+                &values("foo@bar.com", 1),
+                None
+            ),
+            "[] : "
+        );
+    }
+
+    #[test]
+    fn default_title_uses_the_first_visible_email() {
+        let templates = MessageTemplates::new(None, Some("{sender}".to_string()), "");
+        let emails = [email("alice@bar.com", "Hello"), email("bob@bar.com", "Hi")];
+        assert_eq!(
+            templates.title("foo@bar.com", &emails, &emails),
+            "foo@bar.com has 2 new message(s)"
+        );
+        assert_eq!(
+            templates
+                .body_lines("foo@bar.com", 2, &emails)
+                .collect::<Vec<_>>(),
+            ["alice@bar.com", "bob@bar.com"]
+        );
+        assert_eq!(templates.body_lines("foo@bar.com", 2, &[]).count(), 0);
+    }
+
+    #[test]
+    fn default_title_of_merged_accounts_counts_the_accounts() {
+        let templates = MessageTemplates::new(None, None, "");
+        let mut emails = [
+            email("alice@bar.com", "Hello"),
+            email("bob@bar.com", "Hi"),
+            email("carol@bar.com", "Hey"),
+        ];
+        for (email, account) in emails.iter_mut().zip(["a@x.com", "b@y.com", "a@x.com"]) {
+            email.account = account.to_string();
+        }
+        assert_eq!(
+            templates.title("a@x.com, b@y.com", &emails, &[]),
+            "2 accounts have 3 new message(s)"
+        );
+        assert_eq!(
+            templates.title("a@x.com", &emails[..1], &[]),
+            "a@x.com has 1 new message(s)"
+        );
+
+        let templates = MessageTemplates::new(Some("{account}: {count}".to_string()), None, "");
+        assert_eq!(
+            templates.title("a@x.com, b@y.com", &emails, &[]),
+            "a@x.com, b@y.com: 3"
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use crate::notifiers::circuit_breaker::{BreakerConfig, CircuitBreaker};
+use crate::notifiers::format::visible_emails;
+use crate::notifiers::http::{send_with_breaker, HttpConfig};
+use crate::notifiers::worker::{default_queue_size, WorkerNotifier};
+use crate::notifiers::{Event, Notifier, NotifierConfig, NotifierContext, Shutdown};
+use log::error;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+#[derive(Debug, Deserialize)]
+/// Configuration for a generic JSON webhook.
+pub struct WebhookConfig {
+    pub name: String,
+    /// Only send the notifications of these accounts, all accounts if empty.
+    #[serde(default)]
+    pub accounts: Vec<String>,
+    /// Hide the email senders and subjects, only the number of new emails is sent.
+    #[serde(default)]
+    pub redact: bool,
+    /// Number of notifications waiting to be sent after which new ones are dropped.
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
+    pub url: String,
+    /// JSON body with `{placeholder}`s, defaults to the JSON serialized event.
+    pub template: Option<String>,
+    /// Extra headers added to every request.
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// PEM file with additional root certificates, overrides the global `ca_bundle`.
+    pub ca_bundle: Option<PathBuf>,
+    /// Timeouts and User-Agent of the requests.
+    #[serde(flatten)]
+    pub http: HttpConfig,
+    /// Pause the requests after repeated failures.
+    #[serde(flatten)]
+    pub breaker: BreakerConfig,
+}
+
+impl NotifierConfig for WebhookConfig {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn take_accounts(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.accounts)
+    }
+
+    fn redact(&self) -> bool {
+        self.redact
+    }
+
+    fn into_notifier(self, ctx: &NotifierContext) -> anyhow::Result<Box<dyn Notifier>> {
+        let ca_bundle = self.ca_bundle.or_else(|| ctx.ca_bundle.clone());
+        let agent = ctx
+            .http_agents
+            .agent(&self.http.options(ca_bundle, &self.url))?;
+        let mut state = WebhookNotifier {
+            name: self.name.clone(),
+            agent,
+            shutdown: ctx.shutdown.clone(),
+            url: self.url,
+            template: self.template,
+            headers: self.headers,
+            breaker: self.breaker.circuit_breaker("webhook", &self.name, ctx),
+        };
+        let notifier =
+            WorkerNotifier::spawn("webhook", self.name, self.queue_size, ctx, move |event| {
+                state.handle(event)
+            })?;
+        Ok(Box::new(notifier))
+    }
+}
+
+/// POST a JSON body to an arbitrary url for every notification.
+struct WebhookNotifier {
+    name: String,
+    agent: ureq::Agent,
+    shutdown: Shutdown,
+    url: String,
+    template: Option<String>,
+    headers: HashMap<String, String>,
+    breaker: CircuitBreaker,
+}
+
+impl WebhookNotifier {
+    /// Returns true if the notification was posted.
+    fn handle(&mut self, event: Event) -> bool {
+        let body = match &self.template {
+            Some(template) => render(template, &event),
+            None => match serde_json::to_string(&event) {
+                Ok(body) => body,
+                Err(e) => {
+                    error!("Failed to serialize notification ({}): {e}", self.name);
+                    return false;
+                }
+            },
+        };
+        self.send(body)
+    }
+
+    fn send(&mut self, body: String) -> bool {
+        let mut request = self
+            .agent
+            .post(&self.url)
+            .set("Content-Type", "application/json");
+        for (header, value) in &self.headers {
+            request = request.set(header, value);
+        }
+
+        send_with_breaker(
+            "webhook",
+            &self.name,
+            &mut self.breaker,
+            &self.shutdown,
+            &request,
+            body.as_bytes(),
+        )
+    }
+}
+
+/// Substitute the placeholders of `template` with the values of `event`. Values are JSON
+/// escaped so they can be used inside JSON strings, placeholders which don't apply to the
+/// event are replaced with an empty string.
+fn render(template: &str, event: &Event) -> String {
+    let mut values = HashMap::from([("event", event.kind().to_string())]);
+    match event {
+        Event::NewEmail {
+            account,
+            backend,
+            emails,
+            open_url,
+            redacted,
+        } => {
+            let visible = visible_emails(emails, *redacted);
+            values.insert("account", account.clone());
+            values.insert("backend", backend.clone());
+            values.insert("count", emails.len().to_string());
+            values.insert(
+                "title",
+                format!("{account} has {} new message(s)", emails.len()),
+            );
+            values.insert(
+                "senders",
+                visible
+                    .iter()
+                    .map(|e| e.sender.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            );
+            values.insert(
+                "subjects",
+                visible
+                    .iter()
+                    .map(|e| e.subject.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            );
+            values.insert("open_url", open_url.clone().unwrap_or_default());
+        }
+        Event::AccountLoggedOut { account, .. } => {
+            values.insert("account", account.clone());
+            values.insert("title", format!("{account} logged out or session expired"));
+        }
+        Event::AccountError { account, error, .. } => {
+            values.insert("account", account.clone());
+            values.insert("title", format!("{account} encountered an error"));
+            values.insert("message", error.clone());
+        }
+        Event::ConfigError { error } => {
+            values.insert("title", "Server Config Error".to_string());
+            values.insert("message", error.clone());
+        }
+        Event::Error { error } => {
+            values.insert("title", "Server Error".to_string());
+            values.insert("message", error.clone());
+        }
+        Event::Info { title, message } => {
+            values.insert("title", title.clone());
+            values.insert("message", message.clone().unwrap_or_default());
+        }
+    }
+
+    let mut rendered = template.to_string();
+    for placeholder in PLACEHOLDERS {
+        let value = values.get(placeholder).map(String::as_str).unwrap_or("");
+        rendered = rendered.replace(&format!("{{{placeholder}}}"), &json_escape(value));
+    }
+    rendered
+}
+
+/// Placeholders supported in webhook templates.
+const PLACEHOLDERS: [&str; 9] = [
+    "event", "account", "backend", "count", "title", "message", "senders", "subjects", "open_url",
+];
+
+/// Escape `value` for use inside a JSON string.
+fn json_escape(value: &str) -> String {
+    let quoted = serde_json::Value::String(value.to_string()).to_string();
+    quoted[1..quoted.len() - 1].to_string()
+}
@@ -0,0 +1,97 @@
+//! WebPush message encryption (RFC 8291), so that only the UnifiedPush app can read the
+//! notifications and not the push server relaying them.
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes128Gcm, Nonce};
+use anyhow::anyhow;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hkdf::Hkdf;
+use p256::ecdh::EphemeralSecret;
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use p256::PublicKey;
+use rand_core::{OsRng, RngCore};
+use sha2::Sha256;
+
+/// The message is sent as a single record, push services accept at most 4096 bytes.
+const RECORD_SIZE: u32 = 4096;
+/// Salt, record size, key id length and the uncompressed public key.
+const HEADER_SIZE: usize = 16 + 4 + 1 + 65;
+const TAG_SIZE: usize = 16;
+
+/// Keys of the app's push subscription the messages are encrypted for.
+pub struct WebPushKeys {
+    public_key: PublicKey,
+    auth: [u8; 16],
+}
+
+impl WebPushKeys {
+    /// `p256dh` and `auth` are the base64url encoded public key and authentication secret of the
+    /// subscription.
+    pub fn new(p256dh: &str, auth: &str) -> anyhow::Result<Self> {
+        let public_key = decode(p256dh)
+            .and_then(|key| {
+                PublicKey::from_sec1_bytes(&key).map_err(|_| anyhow!("not a P-256 public key"))
+            })
+            .map_err(|e| anyhow!("Invalid webpush_p256dh: {e}"))?;
+        let auth = decode(auth)
+            .and_then(|auth| {
+                auth.try_into()
+                    .map_err(|_| anyhow!("the secret must be 16 bytes"))
+            })
+            .map_err(|e| anyhow!("Invalid webpush_auth: {e}"))?;
+        Ok(Self { public_key, auth })
+    }
+
+    /// Encrypt `plaintext` with the `aes128gcm` content encoding.
+    pub fn encrypt(&self, plaintext: &[u8]) -> anyhow::Result<Vec<u8>> {
+        // The record ends with the padding delimiter.
+        if HEADER_SIZE + plaintext.len() + 1 + TAG_SIZE > RECORD_SIZE as usize {
+            return Err(anyhow!(
+                "Message of {} bytes is too large to be sent with WebPush",
+                plaintext.len()
+            ));
+        }
+
+        let secret = EphemeralSecret::random(&mut OsRng);
+        let server_key = secret.public_key().to_encoded_point(false);
+        let shared = secret.diffie_hellman(&self.public_key);
+
+        let mut key_info = b"WebPush: info\0".to_vec();
+        key_info.extend_from_slice(self.public_key.to_encoded_point(false).as_bytes());
+        key_info.extend_from_slice(server_key.as_bytes());
+        let mut ikm = [0u8; 32];
+        Hkdf::<Sha256>::new(Some(&self.auth[..]), shared.raw_secret_bytes())
+            .expand(&key_info, &mut ikm)
+            .map_err(|e| anyhow!("Failed to derive WebPush key: {e}"))?;
+
+        let mut salt = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        let hkdf = Hkdf::<Sha256>::new(Some(&salt[..]), &ikm);
+        let mut cek = [0u8; 16];
+        let mut nonce = [0u8; 12];
+        hkdf.expand(b"Content-Encoding: aes128gcm\0", &mut cek)
+            .and_then(|_| hkdf.expand(b"Content-Encoding: nonce\0", &mut nonce))
+            .map_err(|e| anyhow!("Failed to derive WebPush key: {e}"))?;
+
+        let mut record = Vec::with_capacity(plaintext.len() + 1);
+        record.extend_from_slice(plaintext);
+        record.push(2);
+        let ciphertext = Aes128Gcm::new(&cek.into())
+            .encrypt(Nonce::from_slice(&nonce), record.as_slice())
+            .map_err(|_| anyhow!("Failed to encrypt WebPush message"))?;
+
+        let mut message = Vec::with_capacity(HEADER_SIZE + ciphertext.len());
+        message.extend_from_slice(&salt);
+        message.extend_from_slice(&RECORD_SIZE.to_be_bytes());
+        message.push(server_key.len() as u8);
+        message.extend_from_slice(server_key.as_bytes());
+        message.extend_from_slice(&ciphertext);
+        Ok(message)
+    }
+}
+
+fn decode(value: &str) -> anyhow::Result<Vec<u8>> {
+    URL_SAFE_NO_PAD
+        .decode(value.trim().trim_end_matches('='))
+        .map_err(|e| anyhow!("{e}"))
+}
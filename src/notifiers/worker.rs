@@ -0,0 +1,170 @@
+use crate::notifiers::{Event, Notifier, NotifierContext};
+use anyhow::anyhow;
+use crossbeam_channel::{Sender, TrySendError};
+use log::{debug, error, warn};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Default number of notifications queued for a notifier thread.
+pub const fn default_queue_size() -> usize {
+    20
+}
+
+/// Tracks the notifications queued for a notifier thread which have not been processed yet.
+#[derive(Clone, Default)]
+pub struct InFlight(Arc<AtomicUsize>);
+
+impl InFlight {
+    pub fn start(&self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn done(&self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// Block until all queued notifications are processed or `timeout` expires.
+    /// Returns false on timeout.
+    pub fn wait(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while self.0.load(Ordering::SeqCst) != 0 {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        true
+    }
+}
+
+/// Notifier which hands the events to a dedicated thread, so slow deliveries don't block
+/// `observer.poll()`. Once `queue_size` events are waiting, new events are dropped instead of
+/// waiting for the thread to catch up.
+pub struct WorkerNotifier {
+    name: String,
+    /// `None` asks the thread to exit once it processed the events queued before it.
+    sender: Sender<Option<Event>>,
+    in_flight: InFlight,
+    ctx: NotifierContext,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl WorkerNotifier {
+    /// Spawn the thread processing the events with `handler`, which returns whether the event
+    /// was delivered. `kind` identifies the type of notifier in the logs and the thread name.
+    #[cfg(any(
+        feature = "notifier-telegram",
+        feature = "notifier-hass",
+        feature = "notifier-discord",
+        feature = "notifier-slack",
+        feature = "notifier-pushbullet",
+        feature = "notifier-signal",
+        feature = "notifier-gotify",
+        feature = "notifier-webhook",
+        feature = "notifier-desktop",
+        feature = "notifier-exec",
+        feature = "notifier-smtp",
+        all(unix, feature = "notifier-pipe")
+    ))]
+    pub fn spawn(
+        kind: &'static str,
+        name: String,
+        queue_size: usize,
+        ctx: &NotifierContext,
+        handler: impl FnMut(Event) -> bool + Send + 'static,
+    ) -> anyhow::Result<Self> {
+        Self::spawn_with_setup(kind, name, queue_size, ctx, move || handler)
+    }
+
+    /// [`WorkerNotifier::spawn`] with the handler returned by `setup`, which runs on the thread
+    /// before the first event is processed.
+    pub fn spawn_with_setup<H: FnMut(Event) -> bool>(
+        kind: &'static str,
+        name: String,
+        queue_size: usize,
+        ctx: &NotifierContext,
+        setup: impl FnOnce() -> H + Send + 'static,
+    ) -> anyhow::Result<Self> {
+        if queue_size == 0 {
+            return Err(anyhow!("{kind} ({name}): queue_size must be at least 1"));
+        }
+        let (sender, receiver) = crossbeam_channel::bounded::<Option<Event>>(queue_size);
+        let in_flight = InFlight::default();
+        let thread = {
+            let label = format!("{kind} ({name})");
+            let thread_name = name.clone();
+            let in_flight = in_flight.clone();
+            let ctx = ctx.clone();
+            std::thread::Builder::new()
+                .name(format!("{kind}-thread"))
+                .spawn(move || {
+                    debug!("Starting {kind} {thread_name} thread");
+                    let mut handler = setup();
+                    while let Ok(Some(event)) = receiver.recv() {
+                        let delivered = handler(event.clone());
+                        ctx.record_delivery(&label, &event, delivered);
+                        in_flight.done();
+                    }
+                    debug!("Exiting {kind} {thread_name} thread")
+                })
+                .map_err(|e| anyhow!("Failed to spawn {kind} ({name}) thread: {e}"))?
+        };
+
+        Ok(Self {
+            name,
+            sender,
+            in_flight,
+            ctx: ctx.clone(),
+            thread: Mutex::new(Some(thread)),
+        })
+    }
+}
+
+impl Notifier for WorkerNotifier {
+    fn notify(&self, event: &Event) {
+        self.in_flight.start();
+        let Err(e) = self.sender.try_send(Some(event.clone())) else {
+            return;
+        };
+        self.in_flight.done();
+        self.ctx.stats.record_dropped();
+        match e {
+            TrySendError::Full(_) => warn!(
+                "Notification queue of {} is full, dropped notification",
+                self.name
+            ),
+            TrySendError::Disconnected(_) => {
+                error!("Failed to sent notification to thread ({}): {e}", self.name)
+            }
+        }
+    }
+
+    fn flush(&self, timeout: Duration) -> bool {
+        self.in_flight.wait(timeout)
+    }
+
+    fn shutdown(&self, timeout: Duration) -> bool {
+        let Some(thread) = self.thread.lock().unwrap().take() else {
+            return true;
+        };
+
+        let deadline = Instant::now() + timeout;
+        if let Err(e) = self.sender.send_timeout(None, timeout) {
+            warn!("Failed to stop notifier thread ({}): {e}", self.name);
+            return false;
+        }
+        while !thread.is_finished() {
+            if Instant::now() >= deadline {
+                warn!(
+                    "Timed out waiting for notifier thread ({}) to exit",
+                    self.name
+                );
+                return false;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        thread.join().is_ok()
+    }
+}
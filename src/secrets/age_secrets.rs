@@ -0,0 +1,132 @@
+use crate::secrets::Secrets;
+use crate::utils::{create_dir_user_only, write_user_file};
+use age::secrecy::Secret as AgeSecret;
+use anyhow::anyhow;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use you_have_mail_common::{EncryptionKey, Secret};
+
+/// Store secrets on disk encrypted with age, either to the public key of an identity file or
+/// with a passphrase prompted on the terminal.
+pub struct AgeSecrets {
+    filepath: PathBuf,
+    identity_file: Option<PathBuf>,
+}
+
+impl AgeSecrets {
+    const FILENAME: &'static str = "encryption_key.age";
+
+    pub fn with_directory(
+        path: impl AsRef<Path>,
+        identity_file: Option<PathBuf>,
+    ) -> anyhow::Result<Self> {
+        create_dir_user_only(path.as_ref())
+            .map_err(|e| anyhow!("Failed to creat config dir:{e}"))?;
+        Ok(Self {
+            filepath: path.as_ref().join(Self::FILENAME),
+            identity_file,
+        })
+    }
+
+    /// Read the X25519 identity (`AGE-SECRET-KEY-...`) from the identity file.
+    fn identity(path: &Path) -> anyhow::Result<age::x25519::Identity> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read age identity file {path:?}: {e}"))?;
+        contents
+            .lines()
+            .map(str::trim)
+            .find(|line| line.starts_with("AGE-SECRET-KEY-"))
+            .ok_or_else(|| anyhow!("No age identity found in {path:?}"))?
+            .parse::<age::x25519::Identity>()
+            .map_err(|e| anyhow!("Invalid age identity in {path:?}: {e}"))
+    }
+
+    fn encrypt(&self, plaintext: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let encryptor = match &self.identity_file {
+            Some(path) => {
+                let recipient = Self::identity(path)?.to_public();
+                age::Encryptor::with_recipients(vec![Box::new(recipient)])
+                    .ok_or_else(|| anyhow!("No age recipient"))?
+            }
+            None => {
+                let passphrase =
+                    prompt_passphrase("Please type a passphrase for the encryption key:")?;
+                let confirmation = prompt_passphrase("Please type the passphrase again:")?;
+                if passphrase != confirmation {
+                    return Err(anyhow!("Passphrases do not match"));
+                }
+                age::Encryptor::with_user_passphrase(AgeSecret::new(passphrase))
+            }
+        };
+
+        let mut encrypted = Vec::new();
+        let mut writer = encryptor
+            .wrap_output(&mut encrypted)
+            .map_err(|e| anyhow!("Failed to encrypt key: {e}"))?;
+        writer
+            .write_all(plaintext)
+            .map_err(|e| anyhow!("Failed to encrypt key: {e}"))?;
+        writer
+            .finish()
+            .map_err(|e| anyhow!("Failed to encrypt key: {e}"))?;
+        Ok(encrypted)
+    }
+
+    fn decrypt(&self, encrypted: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let decryptor =
+            age::Decryptor::new(encrypted).map_err(|e| anyhow!("Invalid encrypted key: {e}"))?;
+        let mut reader = match decryptor {
+            age::Decryptor::Recipients(decryptor) => {
+                let path = self.identity_file.as_ref().ok_or_else(|| {
+                    anyhow!("Encryption key was encrypted to an identity, set `age_identity_file`")
+                })?;
+                let identity = Self::identity(path)?;
+                let identity: &dyn age::Identity = &identity;
+                decryptor
+                    .decrypt(std::iter::once(identity))
+                    .map_err(|e| anyhow!("Failed to decrypt key: {e}"))?
+            }
+            age::Decryptor::Passphrase(decryptor) => {
+                let passphrase = prompt_passphrase("Please type the encryption key passphrase:")?;
+                decryptor
+                    .decrypt(&AgeSecret::new(passphrase), None)
+                    .map_err(|e| anyhow!("Failed to decrypt key: {e}"))?
+            }
+        };
+
+        let mut decrypted = Vec::new();
+        reader
+            .read_to_end(&mut decrypted)
+            .map_err(|e| anyhow!("Failed to decrypt key: {e}"))?;
+        Ok(decrypted)
+    }
+}
+
+fn prompt_passphrase(prompt: &str) -> anyhow::Result<String> {
+    rpassword::prompt_password(prompt).map_err(|e| anyhow!("Failed to read passphrase: {e}"))
+}
+
+impl Secrets for AgeSecrets {
+    fn store(&mut self, key: &EncryptionKey) -> anyhow::Result<()> {
+        let encrypted = self.encrypt(key.as_ref())?;
+        write_user_file(&self.filepath, &encrypted)
+            .map_err(|e| anyhow!("Failed to write key to disk: {e}"))
+    }
+
+    fn load(&mut self) -> anyhow::Result<Option<Secret<EncryptionKey>>> {
+        let contents = match std::fs::read(&self.filepath) {
+            Ok(c) => c,
+            Err(e) => {
+                if e.kind() == std::io::ErrorKind::NotFound {
+                    return Ok(None);
+                }
+                return Err(anyhow!("Failed to open file :{e}"));
+            }
+        };
+
+        let decrypted = self.decrypt(&contents)?;
+        let key = EncryptionKey::try_from(decrypted.as_slice())
+            .map_err(|_| anyhow!("Invalid encryption key"))?;
+        Ok(Some(Secret::new(key)))
+    }
+}
@@ -0,0 +1,32 @@
+use crate::secrets::Secrets;
+use anyhow::anyhow;
+use you_have_mail_common::{EncryptionKey, Secret};
+
+/// Read the base64 encoded encryption key from an environment variable, e.g. a secret
+/// injected into a container. The variable is managed externally, so the key can't be stored.
+pub struct EnvSecrets;
+
+impl EnvSecrets {
+    const VARIABLE: &'static str = "YHM_ENCRYPTION_KEY";
+}
+
+impl Secrets for EnvSecrets {
+    fn store(&mut self, _: &EncryptionKey) -> anyhow::Result<()> {
+        Err(anyhow!(
+            "{} is not set and can't be written by the CLI, set it to a base64 encoded key (e.g. `openssl rand -base64 32`)",
+            Self::VARIABLE
+        ))
+    }
+
+    fn load(&mut self) -> anyhow::Result<Option<Secret<EncryptionKey>>> {
+        let key_str = match std::env::var(Self::VARIABLE) {
+            Ok(s) => s,
+            Err(std::env::VarError::NotPresent) => return Ok(None),
+            Err(e) => return Err(anyhow!("Failed to read {}: {e}", Self::VARIABLE)),
+        };
+
+        let key = EncryptionKey::with_base64(key_str.trim())
+            .map_err(|_| anyhow!("Invalid key format in {}", Self::VARIABLE))?;
+        Ok(Some(Secret::new(key)))
+    }
+}
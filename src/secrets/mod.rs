@@ -1,13 +1,22 @@
 //! Collection of secret storage services.
+use log::info;
 use serde::Deserialize;
 use std::path::Path;
-use you_have_mail_common::{EncryptionKey, Secret};
+use you_have_mail_common::{EncryptionKey, ExposeSecret, Secret};
 
+mod env_secrets;
 mod plain_secrets;
+mod systemd_secrets;
 
 #[cfg(feature = "keyring-secrets")]
 mod keyring_secrets;
 
+#[cfg(feature = "age-secrets")]
+mod age_secrets;
+
+#[cfg(feature = "pass-secrets")]
+mod pass_secrets;
+
 /// Controls how the secret encryption is stored on disk.
 pub trait Secrets {
     /// Store the encryption key in the secret store.
@@ -20,12 +29,34 @@ pub trait Secrets {
 pub enum SecretsType {
     Plain,
     Keyring,
+    Age,
+    Env,
+    Pass,
+    SystemdCreds,
 }
 
-pub fn new_secrets(t: SecretsType, config_dir: &Path) -> Result<Box<dyn Secrets>, anyhow::Error> {
+/// Settings of the secret stores which need more than the config directory.
+#[derive(Debug, Default, Clone)]
+pub struct SecretsOptions {
+    /// Identity the `Age` store encrypts the key to, a passphrase is prompted if `None`.
+    #[cfg(feature = "age-secrets")]
+    pub age_identity_file: Option<std::path::PathBuf>,
+    /// Entry of the `Pass` store, `you-have-mail-cli/encryption-key` if `None`.
+    #[cfg(feature = "pass-secrets")]
+    pub pass_entry: Option<String>,
+    /// Fail instead of warning when a key file can be read by other users.
+    pub strict_permissions: bool,
+}
+
+pub fn new_secrets(
+    t: SecretsType,
+    config_dir: &Path,
+    options: &SecretsOptions,
+) -> Result<Box<dyn Secrets>, anyhow::Error> {
     match t {
         SecretsType::Plain => Ok(Box::new(plain_secrets::PlainSecrets::with_directory(
             config_dir,
+            options.strict_permissions,
         )?)),
         #[cfg(feature = "keyring-secrets")]
         SecretsType::Keyring => Ok(Box::new(keyring_secrets::KeyringSecrets::new()?)),
@@ -34,5 +65,60 @@ pub fn new_secrets(t: SecretsType, config_dir: &Path) -> Result<Box<dyn Secrets>
             use anyhow::anyhow;
             Err(anyhow!("keyring-secrets feature is not enabled"))
         }
+        SecretsType::Env => Ok(Box::new(env_secrets::EnvSecrets)),
+        SecretsType::SystemdCreds => Ok(Box::new(systemd_secrets::SystemdCredsSecrets)),
+        #[cfg(feature = "age-secrets")]
+        SecretsType::Age => Ok(Box::new(age_secrets::AgeSecrets::with_directory(
+            config_dir,
+            options.age_identity_file.clone(),
+        )?)),
+        #[cfg(not(feature = "age-secrets"))]
+        SecretsType::Age => {
+            use anyhow::anyhow;
+            Err(anyhow!("age-secrets feature is not enabled"))
+        }
+        #[cfg(feature = "pass-secrets")]
+        SecretsType::Pass => Ok(Box::new(pass_secrets::PassSecrets::new(
+            options.pass_entry.clone(),
+        ))),
+        #[cfg(not(feature = "pass-secrets"))]
+        SecretsType::Pass => {
+            use anyhow::anyhow;
+            Err(anyhow!("pass-secrets feature is not enabled"))
+        }
+    }
+}
+
+/// Read the key just stored in the `t` store back and compare it, so a store which doesn't
+/// keep the key is reported now rather than by losing the observer state on the next start.
+pub fn self_test(
+    t: SecretsType,
+    secrets: &mut dyn Secrets,
+    key: &EncryptionKey,
+) -> anyhow::Result<()> {
+    let loaded = secrets
+        .load()
+        .map_err(|e| anyhow::anyhow!("Failed to read the stored key back: {e}"))?
+        .ok_or_else(|| anyhow::anyhow!("The stored key could not be read back"))?;
+    let (loaded, stored): (&[u8], &[u8]) = (loaded.expose_secret().as_ref(), key.as_ref());
+    if loaded != stored {
+        return Err(anyhow::anyhow!(
+            "The key read back differs from the stored key"
+        ));
+    }
+    info!("{t:?} secret store self-test passed");
+    Ok(())
+}
+
+/// Explain the usual cause of the `t` store failing, if it depends on the environment.
+pub fn failure_hint(t: SecretsType) -> Option<&'static str> {
+    match t {
+        SecretsType::Keyring => Some(
+            "Is a keychain available? Headless machines usually have no Secret Service running, use the Age, Pass, Env or SystemdCreds store instead",
+        ),
+        SecretsType::Pass => Some("Is `pass` initialized with `pass init`?"),
+        SecretsType::Plain | SecretsType::Age | SecretsType::Env | SecretsType::SystemdCreds => {
+            None
+        }
     }
 }
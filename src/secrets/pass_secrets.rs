@@ -0,0 +1,103 @@
+use crate::secrets::Secrets;
+use anyhow::anyhow;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+use you_have_mail_common::{EncryptionKey, Secret};
+
+/// Store the base64 encoded encryption key in the password store managed by `pass`.
+pub struct PassSecrets {
+    entry: String,
+}
+
+impl PassSecrets {
+    pub const DEFAULT_ENTRY: &'static str = "you-have-mail-cli/encryption-key";
+
+    pub fn new(entry: Option<String>) -> Self {
+        Self {
+            entry: entry.unwrap_or_else(|| Self::DEFAULT_ENTRY.to_string()),
+        }
+    }
+
+    fn run(args: &[&str], input: Option<&[u8]>) -> anyhow::Result<Output> {
+        let mut child = Command::new("pass")
+            .args(args)
+            .stdin(if input.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| {
+                if e.kind() == std::io::ErrorKind::NotFound {
+                    anyhow!("`pass` is not installed or not in PATH")
+                } else {
+                    anyhow!("Failed to run `pass`: {e}")
+                }
+            })?;
+
+        if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+            stdin
+                .write_all(input)
+                .map_err(|e| anyhow!("Failed to write to `pass`: {e}"))?;
+        }
+
+        child
+            .wait_with_output()
+            .map_err(|e| anyhow!("Failed to run `pass`: {e}"))
+    }
+}
+
+/// Explain a failed `pass` command, gpg errors usually mean the agent could not unlock the key.
+fn describe_failure(output: &Output) -> String {
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    if stderr.contains("gpg") {
+        format!("{stderr} (is the GPG agent running and the key unlocked?)")
+    } else {
+        stderr
+    }
+}
+
+impl Secrets for PassSecrets {
+    fn store(&mut self, key: &EncryptionKey) -> anyhow::Result<()> {
+        let output = Self::run(
+            &["insert", "--multiline", "--force", &self.entry],
+            Some(key.to_base64().as_bytes()),
+        )?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "Failed to store key in pass entry '{}': {}",
+                self.entry,
+                describe_failure(&output)
+            ));
+        }
+        Ok(())
+    }
+
+    fn load(&mut self) -> anyhow::Result<Option<Secret<EncryptionKey>>> {
+        let output = Self::run(&["show", &self.entry], None)?;
+        if !output.status.success() {
+            let failure = describe_failure(&output);
+            if failure.contains("is not in the password store") {
+                return Ok(None);
+            }
+            return Err(anyhow!(
+                "Failed to load key from pass entry '{}': {failure}",
+                self.entry
+            ));
+        }
+
+        let contents = String::from_utf8(output.stdout)
+            .map_err(|_| anyhow!("Invalid key format in pass entry '{}'", self.entry))?;
+        let key_str = contents
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        let key = EncryptionKey::with_base64(key_str)
+            .map_err(|_| anyhow!("Invalid key format in pass entry '{}'", self.entry))?;
+        Ok(Some(Secret::new(key)))
+    }
+}
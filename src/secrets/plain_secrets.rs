@@ -1,5 +1,5 @@
 use crate::secrets::Secrets;
-use crate::utils::{create_dir_user_only, write_user_file};
+use crate::utils::{check_user_only_permissions, create_dir_user_only, write_user_file};
 use anyhow::anyhow;
 use std::path::{Path, PathBuf};
 use you_have_mail_common::{EncryptionKey, Secret};
@@ -7,17 +7,23 @@ use you_have_mail_common::{EncryptionKey, Secret};
 /// Store secrets *UNENCRYPTED* on disk in a file.
 pub struct PlainSecrets {
     filepath: PathBuf,
+    strict_permissions: bool,
 }
 
 impl PlainSecrets {
     const FILENAME: &'static str = "encryption_key";
 
-    /// Use a custom directory to store the secret key
-    pub fn with_directory(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+    /// Use a custom directory to store the secret key. With `strict_permissions` loading fails
+    /// when the key file can be read by other users.
+    pub fn with_directory(
+        path: impl AsRef<Path>,
+        strict_permissions: bool,
+    ) -> anyhow::Result<Self> {
         create_dir_user_only(path.as_ref())
             .map_err(|e| anyhow!("Failed to creat config dir:{e}"))?;
         Ok(Self {
             filepath: path.as_ref().join(Self::FILENAME),
+            strict_permissions,
         })
     }
 }
@@ -29,6 +35,7 @@ impl Secrets for PlainSecrets {
     }
 
     fn load(&mut self) -> anyhow::Result<Option<Secret<EncryptionKey>>> {
+        check_user_only_permissions(&self.filepath, self.strict_permissions)?;
         let contents = match std::fs::read(&self.filepath) {
             Ok(c) => c,
             Err(e) => {
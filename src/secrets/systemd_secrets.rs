@@ -0,0 +1,46 @@
+use crate::secrets::Secrets;
+use anyhow::anyhow;
+use std::path::PathBuf;
+use you_have_mail_common::{EncryptionKey, Secret};
+
+/// Read the encryption key from the credentials systemd passes to the service with
+/// `LoadCredential=` or `LoadCredentialEncrypted=`. The credentials are read-only.
+pub struct SystemdCredsSecrets;
+
+impl SystemdCredsSecrets {
+    const CREDENTIAL: &'static str = "yhm-key";
+
+    fn path() -> anyhow::Result<PathBuf> {
+        let dir = std::env::var_os("CREDENTIALS_DIRECTORY").ok_or_else(|| {
+            anyhow!("CREDENTIALS_DIRECTORY is not set, is the CLI running as a systemd service with LoadCredential?")
+        })?;
+        Ok(PathBuf::from(dir).join(Self::CREDENTIAL))
+    }
+}
+
+impl Secrets for SystemdCredsSecrets {
+    fn store(&mut self, _: &EncryptionKey) -> anyhow::Result<()> {
+        Err(anyhow!(
+            "Credential '{}' not found, systemd credentials can't be written by the CLI. Create the key file and pass it with LoadCredential={}:/path/to/key",
+            Self::CREDENTIAL,
+            Self::CREDENTIAL
+        ))
+    }
+
+    fn load(&mut self) -> anyhow::Result<Option<Secret<EncryptionKey>>> {
+        let path = Self::path()?;
+        let contents = match std::fs::read(&path) {
+            Ok(c) => c,
+            Err(e) => {
+                if e.kind() == std::io::ErrorKind::NotFound {
+                    return Ok(None);
+                }
+                return Err(anyhow!("Failed to open credential {path:?}: {e}"));
+            }
+        };
+
+        let key = EncryptionKey::try_from(contents.as_slice())
+            .map_err(|_| anyhow!("Invalid encryption key"))?;
+        Ok(Some(Secret::new(key)))
+    }
+}
@@ -0,0 +1,98 @@
+//! Active/passive failover between two instances sharing a heartbeat file.
+use crate::utils::write_user_file;
+use log::{error, info, warn};
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Deserialize)]
+pub enum StandbyRole {
+    /// Always poll and write the heartbeat.
+    Primary,
+    /// Only poll while the primary's heartbeat is stale.
+    Standby,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StandbyConfig {
+    pub role: StandbyRole,
+    /// File shared by both instances, must be reachable by both (e.g. network share).
+    pub heartbeat_file: PathBuf,
+    /// Seconds after which the primary is considered down if the heartbeat was not updated.
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+}
+
+const fn default_timeout() -> u64 {
+    600
+}
+
+pub struct Standby {
+    config: StandbyConfig,
+    active: bool,
+}
+
+impl Standby {
+    pub fn new(config: StandbyConfig) -> Self {
+        info!(
+            "Running as {:?}, heartbeat file {:?}",
+            config.role, config.heartbeat_file
+        );
+        Self {
+            active: config.role == StandbyRole::Primary,
+            config,
+        }
+    }
+
+    /// Check whether this instance should poll in this loop iteration.
+    pub fn should_poll(&mut self) -> bool {
+        match self.config.role {
+            StandbyRole::Primary => {
+                self.write_heartbeat();
+                true
+            }
+            StandbyRole::Standby => {
+                let primary_alive = self.primary_alive();
+                if primary_alive && self.active {
+                    info!("Primary instance is back, standing by");
+                } else if !primary_alive && !self.active {
+                    warn!("Primary instance heartbeat lost, taking over polling");
+                }
+                self.active = !primary_alive;
+                self.active
+            }
+        }
+    }
+
+    fn write_heartbeat(&self) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        if let Err(e) = write_user_file(&self.config.heartbeat_file, now.to_string().as_bytes()) {
+            error!("Failed to write heartbeat: {e}");
+        }
+    }
+
+    fn primary_alive(&self) -> bool {
+        let content = match std::fs::read_to_string(&self.config.heartbeat_file) {
+            Ok(c) => c,
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    error!("Failed to read heartbeat: {e}");
+                }
+                return false;
+            }
+        };
+
+        let Ok(timestamp) = content.trim().parse::<u64>() else {
+            error!("Invalid heartbeat file contents");
+            return false;
+        };
+
+        let elapsed = SystemTime::now()
+            .duration_since(UNIX_EPOCH + Duration::from_secs(timestamp))
+            .unwrap_or_default();
+        elapsed < Duration::from_secs(self.config.timeout)
+    }
+}
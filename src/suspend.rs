@@ -0,0 +1,36 @@
+//! Detection of system suspends, the poll timer doesn't advance while the system is asleep.
+use std::time::{Duration, Instant, SystemTime};
+
+/// The poll loop checks for a suspend at least this often while waiting.
+pub const SUSPEND_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Wall clock jumps smaller than this are not considered a suspend.
+const MIN_SUSPEND: Duration = Duration::from_secs(60);
+
+/// Compares the monotonic clock, which stops while the system is suspended, with the wall clock.
+pub struct SuspendDetector {
+    instant: Instant,
+    wall: SystemTime,
+}
+
+impl SuspendDetector {
+    pub fn new() -> Self {
+        Self {
+            instant: Instant::now(),
+            wall: SystemTime::now(),
+        }
+    }
+
+    /// Returns how long the system was suspended since the previous check, if it was.
+    pub fn check(&mut self) -> Option<Duration> {
+        let (instant, wall) = (Instant::now(), SystemTime::now());
+        let elapsed = instant.duration_since(self.instant);
+        // The wall clock going backwards is a clock change, not a suspend.
+        let wall_elapsed = wall.duration_since(self.wall).unwrap_or_default();
+        self.instant = instant;
+        self.wall = wall;
+
+        let suspended = wall_elapsed.saturating_sub(elapsed);
+        (suspended >= MIN_SUSPEND).then_some(suspended)
+    }
+}
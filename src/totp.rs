@@ -0,0 +1,201 @@
+//! Generation of TOTP 2FA codes (RFC 6238) from the secret shown when enabling 2FA, and the
+//! encrypted store of the secrets.
+use crate::accounts::copy_key;
+use crate::utils::write_user_file;
+use anyhow::anyhow;
+use hmac::{Hmac, Mac};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use you_have_mail_common::{EncryptionKey, ExposeSecret, Secret};
+
+const STEP_SECS: u64 = 30;
+const DIGITS: u32 = 6;
+
+/// File of the config directory with the TOTP secrets.
+const SECRETS_FILENAME: &str = "totp_secrets";
+
+/// Base32 encoded TOTP secret, never printed.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct TotpSecret(String);
+
+impl std::fmt::Debug for TotpSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TotpSecret(<redacted>)")
+    }
+}
+
+impl TotpSecret {
+    pub fn new(secret: &str) -> Self {
+        Self(secret.to_string())
+    }
+
+    /// Code valid at the current time.
+    pub fn code(&self) -> anyhow::Result<String> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| anyhow!("System time is before the unix epoch: {e}"))?;
+        self.code_at(now.as_secs())
+    }
+
+    fn code_at(&self, unix_time: u64) -> anyhow::Result<String> {
+        let key = decode_base32(&self.0)?;
+        let mut mac =
+            Hmac::<Sha1>::new_from_slice(&key).map_err(|e| anyhow!("Invalid TOTP secret: {e}"))?;
+        mac.update(&(unix_time / STEP_SECS).to_be_bytes());
+        let hash = mac.finalize().into_bytes();
+
+        let offset = usize::from(hash[hash.len() - 1] & 0x0f);
+        let value = u32::from_be_bytes([
+            hash[offset] & 0x7f,
+            hash[offset + 1],
+            hash[offset + 2],
+            hash[offset + 3],
+        ]);
+        Ok(format!(
+            "{:0width$}",
+            value % 10u32.pow(DIGITS),
+            width = DIGITS as usize
+        ))
+    }
+}
+
+/// Decode RFC 4648 base32, ignoring case, spaces and padding as authenticator apps do.
+fn decode_base32(input: &str) -> anyhow::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in input.chars().filter(|c| !c.is_whitespace() && *c != '=') {
+        let value = match c.to_ascii_uppercase() {
+            c @ 'A'..='Z' => c as u32 - 'A' as u32,
+            c @ '2'..='7' => c as u32 - '2' as u32 + 26,
+            _ => return Err(anyhow!("Invalid character in TOTP secret")),
+        };
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+
+    if bytes.is_empty() {
+        return Err(anyhow!("TOTP secret is empty"));
+    }
+    Ok(bytes)
+}
+
+/// TOTP secrets of the accounts, encrypted with the key of the secret store like the observer
+/// states, so the daemon can log the accounts in again without a secret in the config file.
+pub struct TotpSecrets {
+    path: PathBuf,
+    key: Secret<EncryptionKey>,
+    secrets: BTreeMap<String, TotpSecret>,
+}
+
+impl TotpSecrets {
+    /// Load the secrets of `config_dir`, there are none if the file doesn't exist.
+    pub fn load(config_dir: &Path, key: &Secret<EncryptionKey>) -> anyhow::Result<Self> {
+        Self::open(secrets_path(config_dir), key)
+    }
+
+    /// Load the secrets stored at `path`.
+    pub fn open(path: PathBuf, key: &Secret<EncryptionKey>) -> anyhow::Result<Self> {
+        let secrets = match std::fs::read(&path) {
+            Ok(encrypted) => {
+                let contents = key.expose_secret().decrypt(&encrypted).map_err(|e| {
+                    anyhow!("Failed to decrypt the TOTP secrets at {path:?}, remove it to configure them again: {e}")
+                })?;
+                serde_json::from_slice(&contents)
+                    .map_err(|e| anyhow!("Failed to parse the TOTP secrets at {path:?}: {e}"))?
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(anyhow!("Failed to read the TOTP secrets at {path:?}: {e}")),
+        };
+        Ok(Self {
+            path,
+            key: copy_key(key)?,
+            secrets,
+        })
+    }
+
+    pub fn get(&self, email: &str) -> Option<&TotpSecret> {
+        self.secrets.get(email)
+    }
+
+    pub fn insert(&mut self, email: &str, secret: TotpSecret) {
+        self.secrets.insert(email.to_string(), secret);
+    }
+
+    pub fn remove(&mut self, email: &str) {
+        self.secrets.remove(email);
+    }
+
+    /// Write the secrets, the file is deleted once there are none.
+    pub fn save(&self) -> anyhow::Result<()> {
+        if self.secrets.is_empty() {
+            return match std::fs::remove_file(&self.path) {
+                Ok(()) => Ok(()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                Err(e) => Err(anyhow!("Failed to delete {:?}: {e}", self.path)),
+            };
+        }
+        self.write_to(&self.path, &self.key)
+    }
+
+    /// Write the secrets encrypted with `key` to `path`, to rotate the key.
+    pub fn write_to(&self, path: &Path, key: &Secret<EncryptionKey>) -> anyhow::Result<()> {
+        let contents = serde_json::to_vec(&self.secrets)
+            .map_err(|e| anyhow!("Failed to serialize the TOTP secrets: {e}"))?;
+        let encrypted = key
+            .expose_secret()
+            .encrypt(&contents)
+            .map_err(|e| anyhow!("Failed to encrypt the TOTP secrets: {e}"))?;
+        write_user_file(path, &encrypted).map_err(|e| anyhow!("Failed to write {path:?}: {e}"))
+    }
+}
+
+pub fn secrets_path(config_dir: &Path) -> PathBuf {
+    config_dir.join(SECRETS_FILENAME)
+}
+
+/// Move the TOTP secrets of `config_dir` aside as they can't be decrypted with a new key.
+pub fn discard_secrets(config_dir: &Path) -> anyhow::Result<()> {
+    let path = secrets_path(config_dir);
+    if !path.exists() {
+        return Ok(());
+    }
+    let lost = path.with_extension("lost");
+    warn!("Existing TOTP secrets {path:?} detected but we got a new encryption key, moving them to {lost:?}");
+    std::fs::rename(&path, &lost).map_err(|e| anyhow!("Failed to move {path:?} to {lost:?}: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secrets_are_saved_and_deleted_once_empty() {
+        let dir = std::env::temp_dir().join(format!("yhm-totp-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let key = EncryptionKey::new();
+
+        let mut secrets = TotpSecrets::load(&dir, &key).unwrap();
+        assert!(secrets.get("foo@proton.me").is_none());
+        secrets.insert("foo@proton.me", TotpSecret("JBSWY3DPEHPK3PXP".to_string()));
+        secrets.save().unwrap();
+        assert!(secrets_path(&dir).exists());
+
+        let mut secrets = TotpSecrets::load(&dir, &key).unwrap();
+        assert!(secrets.get("foo@proton.me").is_some());
+        secrets.remove("foo@proton.me");
+        secrets.save().unwrap();
+        assert!(!secrets_path(&dir).exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
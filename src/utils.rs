@@ -1,9 +1,10 @@
 use crate::secrets::Secrets;
 use crate::APP_IDENTIFIER;
 use anyhow::anyhow;
-use log::{debug, error};
+use log::{debug, error, warn};
 use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use you_have_mail_common::{EncryptionKey, ExposeSecret, Secret};
 
 #[cfg(unix)]
@@ -42,6 +43,59 @@ pub fn write_user_file(p: impl AsRef<Path>, content: &[u8]) -> std::io::Result<(
     file.write_all(content)
 }
 
+#[cfg(all(unix, feature = "notifier-file"))]
+pub fn append_user_file(p: impl AsRef<Path>, content: &[u8]) -> std::io::Result<()> {
+    use std::os::unix::fs::OpenOptionsExt;
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .mode(0o600)
+        .open(p.as_ref())?;
+    file.write_all(content)
+}
+
+#[cfg(all(not(unix), feature = "notifier-file"))]
+pub fn append_user_file(p: impl AsRef<Path>, content: &[u8]) -> std::io::Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(p.as_ref())?;
+    file.write_all(content)
+}
+
+/// Check that `p` can't be accessed by the group or other users. Logs a warning, or fails if
+/// `strict` is set, when it can.
+#[cfg(unix)]
+pub fn check_user_only_permissions(p: impl AsRef<Path>, strict: bool) -> anyhow::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+    let path = p.as_ref();
+    let metadata = match std::fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(anyhow!("Failed to read permissions of {path:?}: {e}")),
+    };
+
+    let mode = metadata.mode() & 0o777;
+    if mode & 0o077 == 0 {
+        return Ok(());
+    }
+
+    let msg = format!(
+        "{path:?} is accessible by other users (mode {mode:o}), restrict it with `chmod go-rwx {}`",
+        path.display()
+    );
+    if strict {
+        return Err(anyhow!(msg));
+    }
+    warn!("{msg}");
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn check_user_only_permissions(_: impl AsRef<Path>, _: bool) -> anyhow::Result<()> {
+    Ok(())
+}
+
 pub fn get_default_config_dir() -> anyhow::Result<PathBuf> {
     let config_dir =
         dirs::config_local_dir().ok_or(anyhow!("Failed to get configuration directory"))?;
@@ -85,3 +139,115 @@ pub fn get_or_create_secret_key(secrets: &mut dyn Secrets) -> anyhow::Result<Get
 
     Ok(GetSecretKeyState::New(new_key))
 }
+
+/// Check whether `err` or any of its sources is caused by the storage device being full.
+pub fn is_disk_full(err: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if let Some(io_err) = err.downcast_ref::<std::io::Error>() {
+            if io_err.kind() == std::io::ErrorKind::StorageFull {
+                return true;
+            }
+        }
+        source = err.source();
+    }
+    false
+}
+
+/// Check whether `err` can't be fixed by retrying, such as the observer state not being readable
+/// or writable. Other errors, e.g. network failures, are assumed to be transient.
+pub fn is_fatal_error(err: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if err.is::<serde_json::Error>() {
+            return true;
+        }
+        if let Some(io_err) = err.downcast_ref::<std::io::Error>() {
+            if matches!(
+                io_err.kind(),
+                std::io::ErrorKind::PermissionDenied
+                    | std::io::ErrorKind::InvalidData
+                    | std::io::ErrorKind::InvalidInput
+            ) {
+                return true;
+            }
+        }
+        source = err.source();
+    }
+    false
+}
+
+/// Randomizes durations by up to `max` in either direction. A xorshift generator is good enough
+/// to spread the polls, it must not be used for anything security related.
+pub struct Jitter {
+    max: Duration,
+    state: u64,
+}
+
+impl Jitter {
+    pub fn new(max: Duration) -> Self {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        let seed = nanos ^ (u64::from(std::process::id()) << 32);
+        Self {
+            max,
+            // xorshift never leaves the zero state.
+            state: if seed == 0 {
+                0x9E37_79B9_7F4A_7C15
+            } else {
+                seed
+            },
+        }
+    }
+
+    /// `duration` moved by a random offset in `[-max, max]`, never below zero.
+    pub fn apply(&mut self, duration: Duration) -> Duration {
+        if self.max.is_zero() {
+            return duration;
+        }
+        let max_millis = self.max.as_millis() as u64;
+        let offset = Duration::from_millis(self.next() % (2 * max_millis + 1));
+        (duration + offset).saturating_sub(self.max)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+}
+
+/// Allow an action to happen at most once per interval.
+pub struct Throttle {
+    interval: Duration,
+    last: Option<Instant>,
+}
+
+impl Throttle {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last: None,
+        }
+    }
+
+    /// Returns true if the interval has elapsed since the last time this returned true.
+    pub fn ready(&mut self) -> bool {
+        let now = Instant::now();
+        match self.last {
+            Some(last) if now.duration_since(last) < self.interval => false,
+            _ => {
+                self.last = Some(now);
+                true
+            }
+        }
+    }
+
+    /// Forget the last occurrence so the next call to [`Throttle::ready`] succeeds.
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+}